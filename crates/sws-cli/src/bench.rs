@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::ValueHint;
+use sws_crawler::{PageLocation, Scrapable, ScrapingContext};
+use sws_lua::writer::SharedSink;
+use sws_lua::{LuaScraper, LuaScraperConfig, SharedProfiler};
+
+#[cfg(feature = "count-allocs")]
+use crate::alloc::AllocStats;
+use crate::key_value;
+
+/// Measure the parsing and scraping speed of a script over local pages
+#[derive(Debug, clap::Args)]
+pub struct BenchArgs {
+    /// Path to the Lua script that defines scraping logic
+    #[clap(display_order(1), long, short, value_hint = ValueHint::FilePath)]
+    pub script: PathBuf,
+
+    /// A glob pattern to select the local html files to scrap
+    #[clap(display_order(2), long = "files")]
+    pub glob: String,
+
+    /// The number of times each file is scraped
+    #[clap(display_order(3), long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub iterations: u32,
+
+    /// Add a directory Lua modules can be required from
+    #[clap(display_order(4), long = "lua-path", value_hint = ValueHint::DirPath)]
+    pub lua_paths: Vec<PathBuf>,
+
+    /// Add a variable passed to the script as sws.args, e.g. 'category=books'
+    #[clap(display_order(5), long = "var", value_parser = key_value)]
+    pub vars: Vec<(String, String)>,
+}
+
+/// Scraps the files `iterations` times on a single thread, the records being discarded,
+/// then prints the throughput, the allocations (with the `count-allocs` feature) and the
+/// timings of the script.
+pub fn bench(args: BenchArgs) -> anyhow::Result<()> {
+    let pages = glob::glob(&args.glob)?
+        .map(|path| {
+            let path = path?;
+            let page = fs_err::read_to_string(&path)?;
+            Ok((path, page))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if pages.is_empty() {
+        anyhow::bail!("No file matches {}", args.glob);
+    }
+
+    let records = Arc::new(AtomicU64::new(0));
+    let records_c = records.clone();
+    let profiler = SharedProfiler::default();
+    let config = LuaScraperConfig {
+        script: args.script,
+        lua_paths: args.lua_paths,
+        vars: args.vars.into_iter().collect(),
+        profiler: Some(profiler.clone()),
+        sink: Some(SharedSink(Arc::new(move |_| {
+            records_c.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }))),
+        ..Default::default()
+    };
+
+    let mut scraper = LuaScraper::new(&config)?;
+    scraper.init(ScrapingContext::default())?;
+    let mut elapsed = Duration::ZERO;
+    #[cfg(feature = "count-allocs")]
+    let mut allocs = AllocStats::default();
+    for _ in 0..args.iterations {
+        for (path, page) in &pages {
+            // Copying the page isn't measured
+            let page = page.clone();
+            let ctx = ScrapingContext::with_location(PageLocation::Path(path.clone()));
+            #[cfg(feature = "count-allocs")]
+            let before = AllocStats::now();
+            let start = Instant::now();
+            scraper.scrap(page, ctx)?;
+            elapsed += start.elapsed();
+            #[cfg(feature = "count-allocs")]
+            {
+                let scrap_allocs = AllocStats::now().since(before);
+                allocs.allocations += scrap_allocs.allocations;
+                allocs.bytes += scrap_allocs.bytes;
+            }
+        }
+    }
+    scraper.finish(ScrapingContext::default())?;
+    scraper.finalizer();
+
+    let scraped = pages.len() as u64 * u64::from(args.iterations);
+    let rows = [
+        ("Files", pages.len().to_string()),
+        ("Pages scraped", scraped.to_string()),
+        ("Records", records.load(Ordering::Relaxed).to_string()),
+        ("Duration", format!("{elapsed:.3?}")),
+        (
+            "Pages/sec",
+            format!("{:.1}", scraped as f64 / elapsed.as_secs_f64()),
+        ),
+        ("Mean per page", format!("{:.3?}", elapsed / scraped as u32)),
+    ];
+    #[cfg(feature = "count-allocs")]
+    let alloc_rows = [
+        (
+            "Allocs per page",
+            (allocs.allocations / scraped).to_string(),
+        ),
+        ("Bytes per page", (allocs.bytes / scraped).to_string()),
+    ];
+    #[cfg(not(feature = "count-allocs"))]
+    let alloc_rows = [];
+    for (name, value) in rows.into_iter().chain(alloc_rows) {
+        println!("{name:<16}{value:>12}");
+    }
+    println!();
+    print!("{profiler}");
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use anyhow::anyhow;
+use sws_crawler::serde_json;
+use sws_crawler::{fetch_page, CrawlerConfig, UserAgentPreset};
+use sws_lua::BaseConfig;
+use sws_scraper::{Html, Selector};
+use tokio::runtime;
+
+use crate::header;
+
+/// Download a page as the crawler would and print its HTML
+#[derive(Debug, clap::Args)]
+pub struct FetchArgs {
+    /// A distant html page (http or https URL)
+    #[clap(display_order(1))]
+    pub url: String,
+
+    /// Only print the elements matching this CSS selector
+    #[clap(display_order(2), long)]
+    pub select: Option<String>,
+
+    /// Indent the HTML, one node per line
+    #[clap(display_order(3), long)]
+    pub pretty: bool,
+
+    /// Override crawler's user agent
+    #[clap(display_order(4), long)]
+    pub user_agent: Option<String>,
+
+    /// A header sent with the request (repeatable)
+    #[clap(display_order(5), long = "header", value_parser = header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Use the user agent of a common browser (or crawler), along with its usual headers
+    #[clap(display_order(6), value_enum, long, conflicts_with = "user_agent")]
+    pub user_agent_preset: Option<UserAgentPreset>,
+}
+
+pub fn fetch(args: FetchArgs, base_config: BaseConfig) -> anyhow::Result<()> {
+    let selector = args
+        .select
+        .as_deref()
+        .map(|css| Selector::parse(css).map_err(|e| anyhow!("Invalid CSS selector {css:?}: {e:?}")))
+        .transpose()?;
+
+    let mut crawler_conf: CrawlerConfig =
+        serde_json::from_value(serde_json::Value::Object(base_config.crawler))?;
+    if let Some(user_agent) = args.user_agent {
+        crawler_conf.user_agent = user_agent;
+    }
+    crawler_conf.http.headers.extend(args.headers);
+    if let Some(preset) = args.user_agent_preset {
+        preset.apply(&mut crawler_conf);
+    }
+
+    let rt = runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let fetched = rt.block_on(fetch_page(&crawler_conf, &args.url))?;
+    let info = &fetched.fetch_info;
+    if !info.status.is_success() {
+        log::warn!("Got status {} for {}", info.status, info.final_url);
+    }
+
+    match (selector, args.pretty) {
+        (None, false) => print!("{}", fetched.page),
+        (None, true) => println!(
+            "{}",
+            Html::parse_document(&fetched.page)
+                .root_element()
+                .pretty_html()
+        ),
+        (Some(selector), pretty) => {
+            let html = Html::parse_document(&fetched.page);
+            for elem in html.select(selector) {
+                if pretty {
+                    println!("{}", elem.pretty_html());
+                } else {
+                    println!("{}", elem.html());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
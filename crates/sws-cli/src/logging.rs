@@ -0,0 +1,76 @@
+use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::ValueHint;
+use env_logger::{Builder, Target};
+use log::LevelFilter;
+use sws_crawler::serde_json::json;
+
+/// The crates whose logs are output
+const LOGGED_CRATES: [&str; 3] = ["sws", "sws_crawler", "sws_lua"];
+
+#[derive(Debug, clap::Args)]
+pub struct LogArgs {
+    /// Output more logs (-v for info, -vv for debug, -vvv for trace)
+    #[clap(long, short, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Format of the logs
+    #[clap(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// File the logs are appended to, stderr otherwise
+    #[clap(long, global = true, value_hint = ValueHint::FilePath)]
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, with `ts`, `level`, `target` and `message` fields
+    Json,
+}
+
+/// Sets up the logger, only warnings and errors are output by default.
+///
+/// The directives of an existing `RUST_LOG` env var take precedence over the verbosity
+/// flags, for instance `RUST_LOG=sws_crawler=debug` outputs the crawler debug logs
+/// whatever the verbosity.
+pub fn init(args: &LogArgs, quiet: bool) -> anyhow::Result<()> {
+    let level = match (quiet, args.verbose) {
+        (true, _) => LevelFilter::Off,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Info,
+        (false, 2) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+
+    let mut builder = Builder::new();
+    for krate in LOGGED_CRATES {
+        builder.filter_module(krate, level);
+    }
+    if let Ok(filters) = env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
+    }
+    if let Some(path) = &args.log_file {
+        let file = fs_err::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        builder.target(Target::Pipe(Box::new(file)));
+    }
+    if let LogFormat::Json = args.log_format {
+        builder.format(|buf, record| {
+            let line = json!({
+                "ts": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+    builder.try_init()?;
+    Ok(())
+}
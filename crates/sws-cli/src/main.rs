@@ -1,16 +1,53 @@
-use std::path::PathBuf;
-use std::{cmp, env, io};
-
-use clap::{CommandFactory, Parser};
+#[cfg(feature = "count-allocs")]
+mod alloc;
+mod bench;
+mod fetch;
+mod logging;
+mod new;
+mod progress;
+mod shell;
+mod sitemap;
+mod summary;
+mod watch;
+
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{cmp, io, iter};
+
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{CommandFactory, Parser, ValueHint};
 use clap_complete::{generate, Shell};
-use sws_crawler::{crawl_site, CrawlerConfig, OnError, PageLocation, Scrapable, Throttle};
-use sws_lua::{scrap_glob, scrap_page, writer::FileMode, LuaScraper, LuaScraperConfig};
+use sws_crawler::{
+    crawl_site, ClientCertificate, CrawlEvent, CrawlerConfig, EventObserver, Interrupted, OnError,
+    PageLocation, Revisit, RobotsFallback, Throttle, UserAgentPreset,
+};
+use sws_lua::writer::{Compression, FileMode, Format};
+use sws_lua::{
+    scrap_glob, scrap_page, scrap_urls, BaseConfig, LuaScraper, LuaScraperConfig, MultiLuaScraper,
+    MultiLuaScraperConfig, SharedProfiler, SharedStats,
+};
 use tokio::runtime;
 
+#[cfg(feature = "count-allocs")]
+#[global_allocator]
+static GLOBAL: alloc::CountingAlloc = alloc::CountingAlloc;
+
 /// Sitemap Web Scraper
 #[derive(Debug, Parser)]
 #[clap(version)]
 pub struct Args {
+    /// TOML file of crawler and CSV writer parameters, overridden by the script ones
+    #[clap(long, global = true, env = "SWS_CONFIG", value_hint = ValueHint::FilePath)]
+    pub config: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub log: logging::LogArgs,
+
     #[clap(subcommand)]
     pub cmd: SubCommand,
 }
@@ -18,25 +55,55 @@ pub struct Args {
 #[derive(Debug, clap::Subcommand)]
 pub enum SubCommand {
     #[clap(display_order(1), name = "crawl")]
-    Crawl(CrawlArgs),
+    Crawl(Box<CrawlArgs>),
     #[clap(display_order(2), name = "scrap")]
     Scrap(ScrapArgs),
-    #[clap(hide = true)]
-    Completion,
+    #[clap(display_order(3), name = "shell")]
+    Shell(shell::ShellArgs),
+    #[clap(display_order(4), name = "sitemap")]
+    Sitemap(sitemap::SitemapArgs),
+    #[clap(display_order(5), name = "fetch")]
+    Fetch(fetch::FetchArgs),
+    #[clap(display_order(6), name = "new")]
+    New(new::NewArgs),
+    #[clap(display_order(7), name = "bench")]
+    Bench(bench::BenchArgs),
+    #[clap(display_order(8), name = "lua-stubs")]
+    LuaStubs(LuaStubsArgs),
+    #[clap(display_order(9), name = "completions", alias = "completion")]
+    Completions(CompletionsArgs),
+}
+
+/// Print the LuaLS (EmmyLua) definitions of the Lua API, for editor autocompletion
+#[derive(Debug, clap::Args)]
+pub struct LuaStubsArgs {
+    /// File to write the definitions to, stdout otherwise
+    #[clap(long, short, value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+/// Print the completion script of a shell
+#[derive(Debug, clap::Args)]
+pub struct CompletionsArgs {
+    /// The shell to generate completions for
+    #[clap(value_enum, default_value_t = Shell::Bash)]
+    pub shell: Shell,
 }
 
 /// Crawl sitemaps and scrap pages content
 #[derive(Debug, clap::Args)]
-#[clap(group = clap::ArgGroup::new("mode").requires_all(&["output_file"]))]
+#[clap(group = clap::ArgGroup::new("mode").requires_all(&["output_files"]))]
 #[clap(group = clap::ArgGroup::new("throttle"))]
 pub struct CrawlArgs {
-    /// Path to the Lua script that defines scraping logic
-    #[clap(display_order(1), long, short)]
-    pub script: PathBuf,
+    /// Path to the Lua script that defines scraping logic (repeatable, the first one drives
+    /// the crawl)
+    #[clap(display_order(1), long = "script", short, required = true, value_hint = ValueHint::FilePath)]
+    pub scripts: Vec<PathBuf>,
 
-    /// Optional file that will contain scraped data, stdout otherwise
-    #[clap(display_order(2), long, short)]
-    pub output_file: Option<PathBuf>,
+    /// Optional file that will contain scraped data, stdout otherwise (one per script,
+    /// in the same order, when using multiple scripts)
+    #[clap(display_order(2), long = "output-file", short, value_hint = ValueHint::FilePath)]
+    pub output_files: Vec<PathBuf>,
 
     /// Append to output file
     #[clap(display_order(3), group = "mode", long)]
@@ -46,49 +113,222 @@ pub struct CrawlArgs {
     #[clap(display_order(4), group = "mode", long)]
     pub truncate: bool,
 
+    /// Compress output (inferred from .gz or .zst output file extension otherwise)
+    #[clap(display_order(5), long, value_parser = compression())]
+    pub compress: Option<Compression>,
+
     /// Override crawler's user agent
-    #[clap(display_order(5), long)]
+    #[clap(display_order(6), long)]
     pub user_agent: Option<String>,
 
     /// Override crawler's page buffer size
-    #[clap(display_order(6), long)]
+    #[clap(display_order(7), long)]
     pub page_buffer: Option<usize>,
 
+    /// Override crawler's URL buffer size
+    #[clap(display_order(8), long)]
+    pub url_buffer: Option<usize>,
+
     /// Override crawler's maximum concurrent downloads for pages
-    #[clap(display_order(7), group = "throttle", long = "conc-dl")]
+    #[clap(display_order(9), group = "throttle", long = "conc-dl")]
     pub concurrent_downloads: Option<usize>,
 
     /// Override crawler's number of requests per second
-    #[clap(display_order(8), group = "throttle", long = "rps")]
+    #[clap(display_order(10), group = "throttle", long = "rps")]
     pub requests_per_second: Option<usize>,
 
     /// Override crawler's delay between requests
-    #[clap(display_order(9), group = "throttle", long = "delay", value_parser = delay_positive)]
+    #[clap(display_order(11), group = "throttle", long = "delay", value_parser = delay_positive)]
     pub requests_delay: Option<f32>,
 
+    /// Override crawler's number of requests that can be sent at once when using --rps or --delay
+    #[clap(display_order(12), long)]
+    pub burst: Option<usize>,
+
     /// Override crawler's number of CPU workers used to scrap pages
-    #[clap(display_order(10), long)]
+    #[clap(display_order(13), long)]
     pub num_workers: Option<usize>,
 
     /// Override crawler's download error handling strategy
-    #[clap(display_order(11), value_enum, long)]
+    #[clap(display_order(14), value_enum, long)]
     pub on_dl_error: Option<OnError>,
 
     /// Override crawler's xml error handling strategy
-    #[clap(display_order(12), value_enum, long)]
+    #[clap(display_order(15), value_enum, long)]
     pub on_xml_error: Option<OnError>,
 
     /// Override crawler's scrap error handling strategy
-    #[clap(display_order(13), value_enum, long)]
+    #[clap(display_order(16), value_enum, long)]
     pub on_scrap_error: Option<OnError>,
 
+    /// Override crawler's maximum duration (in seconds) of a page scrap
+    #[clap(display_order(17), long, value_parser = timeout_positive)]
+    pub scrap_timeout: Option<f32>,
+
     /// Override crawler's robots.txt URL
-    #[clap(display_order(14), long)]
+    #[clap(display_order(18), long, value_hint = ValueHint::Url)]
     pub robot: Option<String>,
 
-    /// Don't output logs
-    #[clap(display_order(15), long, short)]
+    /// Fetch and enforce the robots.txt of each crawled host
+    #[clap(display_order(19), long)]
+    pub robots_per_host: bool,
+
+    /// Override crawler's behaviour when a robots.txt cannot be retrieved
+    #[clap(display_order(20), value_enum, long)]
+    pub on_robots_error: Option<OnError>,
+
+    /// Override crawler's assumption when a robots.txt cannot be retrieved
+    #[clap(display_order(21), value_enum, long)]
+    pub robots_fallback: Option<RobotsFallback>,
+
+    /// Override crawler's directory where downloaded pages are saved
+    #[clap(display_order(22), long, value_hint = ValueHint::DirPath)]
+    pub save_pages_dir: Option<PathBuf>,
+
+    /// Override crawler's state file, persisted across crawls
+    #[clap(display_order(23), long = "state", value_hint = ValueHint::FilePath)]
+    pub state_file: Option<PathBuf>,
+
+    /// Skip scraping pages whose content didn't change since the last crawl
+    #[clap(display_order(24), long, requires = "state_file")]
+    pub skip_unchanged: bool,
+
+    /// Override crawler's revisit policy for sitemap URLs
+    #[clap(display_order(25), value_enum, long)]
+    pub revisit: Option<Revisit>,
+
+    /// Override crawler's DNS resolution of a host, e.g. 'example.com=127.0.0.1'
+    #[clap(display_order(26), long = "resolve", value_parser = host_ip)]
+    pub dns_overrides: Vec<(String, IpAddr)>,
+
+    /// Override crawler's DNS cache duration in seconds
+    #[clap(display_order(27), long)]
+    pub dns_cache_ttl: Option<u64>,
+
+    /// Add a PEM encoded certificate to crawler's trusted root certificates
+    #[clap(display_order(28), long = "root-cert", value_hint = ValueHint::FilePath)]
+    pub root_certificates: Vec<PathBuf>,
+
+    /// Override crawler's PEM encoded client certificate
+    #[clap(display_order(29), long, requires = "client_key", value_hint = ValueHint::FilePath)]
+    pub client_cert: Option<PathBuf>,
+
+    /// Override crawler's PEM encoded PKCS#8 client private key
+    #[clap(display_order(30), long, requires = "client_cert", value_hint = ValueHint::FilePath)]
+    pub client_key: Option<PathBuf>,
+
+    /// Accept invalid TLS certificates (dangerous, only for internal environments)
+    #[clap(display_order(31), long)]
+    pub accept_invalid_certs: bool,
+
+    /// Override crawler's maximum number of idle connections per host
+    #[clap(display_order(32), long)]
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// Only use HTTP/1 for crawler's requests
+    #[clap(display_order(33), long, conflicts_with = "http2_prior_knowledge")]
+    pub http1_only: bool,
+
+    /// Use HTTP/2 without protocol negotiation for crawler's requests
+    #[clap(display_order(34), long)]
+    pub http2_prior_knowledge: bool,
+
+    /// Add a regex to crawler's accepted URL patterns
+    #[clap(display_order(35), long = "accept-url")]
+    pub accept_urls: Vec<String>,
+
+    /// Add a regex to crawler's denied URL patterns
+    #[clap(display_order(36), long = "deny-url")]
+    pub deny_urls: Vec<String>,
+
+    /// Override crawler's Redis URL of a frontier shared by multiple crawlers
+    #[clap(display_order(37), long, value_hint = ValueHint::Url)]
+    pub redis_frontier: Option<String>,
+
+    /// Add a directory Lua modules can be required from
+    #[clap(display_order(38), long = "lua-path", value_hint = ValueHint::DirPath)]
+    pub lua_paths: Vec<PathBuf>,
+
+    /// Reload the script between pages when it is modified
+    #[clap(display_order(39), long)]
+    pub hot_reload: bool,
+
+    /// Add a variable passed to the script as sws.args, e.g. 'category=books'
+    #[clap(display_order(40), long = "var", value_parser = key_value)]
+    pub vars: Vec<(String, String)>,
+
+    /// Allow the script to read an environment variable with sws.env
+    #[clap(display_order(41), long = "allow-env")]
+    pub allowed_env_vars: Vec<String>,
+
+    /// Print the time spent in each Lua function and CSS selector once done
+    #[clap(display_order(42), long)]
+    pub profile: bool,
+
+    /// Don't output logs nor the progress of the crawl
+    #[clap(display_order(43), long, short)]
     pub quiet: bool,
+
+    /// Print the URLs of the pages that would be scraped, without downloading them
+    #[clap(display_order(44), long)]
+    pub dry_run: bool,
+
+    /// Stop the crawl once this number of pages have been scraped
+    #[clap(display_order(45), long)]
+    pub limit: Option<usize>,
+
+    /// A header sent with every request (repeatable)
+    #[clap(display_order(46), long = "header", value_parser = header)]
+    pub headers: Vec<(String, String)>,
+
+    /// A proxy URL requests go through
+    #[clap(display_order(47), long, value_hint = ValueHint::Url)]
+    pub proxy: Option<String>,
+
+    /// A file of proxy URLs (one per line) that requests go through in turn
+    #[clap(display_order(48), long, conflicts_with = "proxy", value_hint = ValueHint::FilePath)]
+    pub proxy_file: Option<PathBuf>,
+
+    /// Output format, overriding the writer configured by the script
+    #[clap(display_order(49), long, value_parser = output_format())]
+    pub format: Option<Format>,
+
+    /// Resume the unfinished crawl recorded in the state file, skipping its scraped pages
+    #[clap(display_order(50), long, requires = "state_file")]
+    pub resume: bool,
+
+    /// Override crawler's maximum duration (in seconds) of a request
+    #[clap(display_order(51), long, value_parser = timeout_positive)]
+    pub timeout: Option<f32>,
+
+    /// Override crawler's maximum duration (in seconds) of the whole crawl
+    #[clap(display_order(52), long, value_parser = timeout_positive)]
+    pub max_duration: Option<f32>,
+
+    /// Override crawler's maximum number of links followed from a seed page
+    #[clap(display_order(53), long)]
+    pub max_depth: Option<usize>,
+
+    /// Only follow links to the host of the page they were sent from
+    #[clap(display_order(54), long)]
+    pub same_host_only: bool,
+
+    /// Override crawler's directory where responses are cached and revalidated
+    #[clap(display_order(55), long, value_hint = ValueHint::DirPath)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Use the user agent of a common browser (or crawler), along with its usual headers
+    #[clap(display_order(56), value_enum, long, conflicts_with = "user_agent")]
+    pub user_agent_preset: Option<UserAgentPreset>,
+
+    /// Apply --rps or --delay to each host separately instead of to all requests
+    #[clap(
+        display_order(57),
+        long,
+        requires = "throttle",
+        conflicts_with = "concurrent_downloads"
+    )]
+    pub per_host: bool,
 }
 
 fn delay_positive(s: &str) -> Result<f32, String> {
@@ -102,7 +342,57 @@ fn delay_positive(s: &str) -> Result<f32, String> {
     }
 }
 
-pub fn crawl(args: CrawlArgs) -> anyhow::Result<()> {
+fn timeout_positive(s: &str) -> Result<f32, String> {
+    let timeout: f32 = s
+        .parse()
+        .map_err(|_| format!("`{}` isn't a f32 value", s))?;
+    if timeout > 0. {
+        Ok(timeout)
+    } else {
+        Err("timeout must be strictly positive".into())
+    }
+}
+
+fn compression() -> impl TypedValueParser<Value = Compression> {
+    PossibleValuesParser::new(["gzip", "zstd"]).map(|s| match s.as_str() {
+        "gzip" => Compression::Gzip,
+        _ => Compression::Zstd,
+    })
+}
+
+fn output_format() -> impl TypedValueParser<Value = Format> {
+    PossibleValuesParser::new(["csv", "jsonl", "parquet"]).map(|s| match s.as_str() {
+        "csv" => Format::Csv,
+        "jsonl" => Format::Jsonl,
+        _ => Format::Parquet,
+    })
+}
+
+fn key_value(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("`{}` isn't a KEY=VALUE value", s))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("`{}` isn't a 'NAME: VALUE' value", s))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+fn host_ip(s: &str) -> Result<(String, IpAddr), String> {
+    let (host, ip) = s
+        .split_once('=')
+        .ok_or_else(|| format!("`{}` isn't a HOST=IP value", s))?;
+    let ip = ip
+        .parse()
+        .map_err(|_| format!("`{}` isn't an IP address", ip))?;
+    Ok((host.to_string(), ip))
+}
+
+pub fn crawl(args: CrawlArgs, base_config: BaseConfig) -> anyhow::Result<ExitCode> {
     let file_mode = if args.append {
         Some(FileMode::Append)
     } else if args.truncate {
@@ -111,27 +401,66 @@ pub fn crawl(args: CrawlArgs) -> anyhow::Result<()> {
         None
     };
 
-    let scraper_conf = LuaScraperConfig {
-        script: args.script,
-        csv_file: args.output_file,
-        file_mode,
-    };
+    let single_output = args.scripts.len() == 1 && args.output_files.is_empty();
+    if !single_output && args.output_files.len() != args.scripts.len() {
+        anyhow::bail!("Each script requires its own output file, given in the same order");
+    }
 
-    let mut crawler_conf = CrawlerConfig::try_from(&scraper_conf)?;
+    let vars = args.vars.into_iter().collect::<HashMap<_, _>>();
+    let profiler = args.profile.then(SharedProfiler::default);
+    let stats = SharedStats::default();
+    let output_files = args.output_files.into_iter().map(Some);
+    let scraper_confs = args
+        .scripts
+        .into_iter()
+        .zip(output_files.chain(iter::repeat(None)))
+        .map(|(script, csv_file)| LuaScraperConfig {
+            script,
+            csv_file,
+            file_mode: file_mode.clone(),
+            compression: args.compress,
+            format: args.format,
+            lua_paths: args.lua_paths.clone(),
+            hot_reload: args.hot_reload,
+            vars: vars.clone(),
+            allowed_env_vars: args.allowed_env_vars.clone(),
+            profiler: profiler.clone(),
+            stats: stats.clone(),
+            base_config: base_config.clone(),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let scraper_conf = &scraper_confs[0];
+
+    let mut crawler_conf = CrawlerConfig::try_from(scraper_conf)?;
     if let Some(user_agent) = &args.user_agent {
         crawler_conf.user_agent = user_agent.to_string();
     }
     if let Some(page_buffer) = args.page_buffer {
         crawler_conf.page_buffer = page_buffer;
     }
+    if let Some(url_buffer) = args.url_buffer {
+        crawler_conf.url_buffer = url_buffer;
+    }
     if let Some(conc_dl) = args.concurrent_downloads {
         crawler_conf.throttle = Some(Throttle::Concurrent(conc_dl.try_into()?));
     }
     if let Some(rps) = args.requests_per_second {
-        crawler_conf.throttle = Some(Throttle::PerSecond(rps.try_into()?));
+        crawler_conf.throttle = Some(if args.per_host {
+            Throttle::PerHostPerSecond(rps.try_into()?)
+        } else {
+            Throttle::PerSecond(rps.try_into()?)
+        });
     }
     if let Some(delay) = args.requests_delay {
-        crawler_conf.throttle = Some(Throttle::Delay(delay));
+        crawler_conf.throttle = Some(if args.per_host {
+            Throttle::PerHostDelay(delay)
+        } else {
+            Throttle::Delay(delay)
+        });
+    }
+    if let Some(burst) = args.burst {
+        crawler_conf.throttle_burst = burst;
     }
     if let Some(num_workers) = args.num_workers {
         crawler_conf.num_workers = num_workers;
@@ -145,12 +474,140 @@ pub fn crawl(args: CrawlArgs) -> anyhow::Result<()> {
     if let Some(on_scrap_error) = args.on_scrap_error {
         crawler_conf.on_scrap_error = on_scrap_error;
     }
+    if let Some(scrap_timeout) = args.scrap_timeout {
+        crawler_conf.scrap_timeout = Some(scrap_timeout);
+    }
     if let Some(robot) = args.robot {
         crawler_conf.robot = Some(robot);
     }
+    if args.robots_per_host {
+        crawler_conf.robots_per_host = true;
+    }
+    if let Some(on_robots_error) = args.on_robots_error {
+        crawler_conf.on_robots_error = on_robots_error;
+    }
+    if let Some(robots_fallback) = args.robots_fallback {
+        crawler_conf.robots_fallback = robots_fallback;
+    }
+    if let Some(save_pages_dir) = args.save_pages_dir {
+        crawler_conf.save_pages_dir = Some(save_pages_dir);
+    }
+    if let Some(cache_dir) = args.cache_dir {
+        crawler_conf.cache_dir = Some(cache_dir);
+    }
+    if let Some(state_file) = args.state_file {
+        crawler_conf.state_file = Some(state_file);
+    }
+    if args.skip_unchanged {
+        crawler_conf.skip_unchanged = true;
+    }
+    if args.resume {
+        crawler_conf.resume = true;
+    }
+    if let Some(revisit) = args.revisit {
+        crawler_conf.revisit = revisit;
+    }
+    for (host, ip) in args.dns_overrides {
+        crawler_conf.dns.overrides.entry(host).or_default().push(ip);
+    }
+    if let Some(dns_cache_ttl) = args.dns_cache_ttl {
+        crawler_conf.dns.cache_ttl = Some(dns_cache_ttl);
+    }
+    crawler_conf
+        .tls
+        .root_certificates
+        .extend(args.root_certificates);
+    if let (Some(cert), Some(key)) = (args.client_cert, args.client_key) {
+        crawler_conf.tls.client_certificate = Some(ClientCertificate { cert, key });
+    }
+    if args.accept_invalid_certs {
+        crawler_conf.tls.accept_invalid_certs = true;
+    }
+    if let Some(max) = args.pool_max_idle_per_host {
+        crawler_conf.http.pool_max_idle_per_host = Some(max);
+    }
+    if args.http1_only {
+        crawler_conf.http.http1_only = true;
+        crawler_conf.http.http2_prior_knowledge = false;
+    }
+    if args.http2_prior_knowledge {
+        crawler_conf.http.http2_prior_knowledge = true;
+        crawler_conf.http.http1_only = false;
+    }
+    crawler_conf.url_filters.accept.extend(args.accept_urls);
+    crawler_conf.url_filters.deny.extend(args.deny_urls);
+    if let Some(redis_url) = args.redis_frontier {
+        crawler_conf.frontier.redis_url = Some(redis_url);
+    }
+    crawler_conf.http.headers.extend(args.headers);
+    if let Some(preset) = args.user_agent_preset {
+        preset.apply(&mut crawler_conf);
+    }
+    if let Some(proxy) = args.proxy {
+        crawler_conf.proxies = vec![proxy];
+    }
+    if let Some(proxy_file) = args.proxy_file {
+        crawler_conf.proxies = fs_err::read_to_string(proxy_file)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+    }
+    if let Some(limit) = args.limit {
+        crawler_conf.max_pages = Some(limit);
+    }
+    if let Some(timeout) = args.timeout {
+        crawler_conf.http.timeout = Some(timeout);
+    }
+    if let Some(max_duration) = args.max_duration {
+        crawler_conf.max_duration = Some(max_duration);
+    }
+    if let Some(max_depth) = args.max_depth {
+        crawler_conf.max_depth = Some(max_depth);
+    }
+    if args.same_host_only {
+        crawler_conf.same_host_only = true;
+    }
+    if args.dry_run {
+        crawler_conf.dry_run = true;
+        crawler_conf.on_event = Some(EventObserver(Arc::new(|event| {
+            if let CrawlEvent::Planned(url) = event {
+                println!("{url}");
+            }
+        })));
+    }
+
+    let summary = summary::Summary::start(stats);
+    summary.install(&mut crawler_conf);
+    let progress = (!args.quiet && io::stderr().is_terminal())
+        .then(|| progress::Progress::install(&mut crawler_conf));
 
     let rt = runtime::Builder::new_multi_thread().enable_all().build()?;
-    rt.block_on(crawl_site::<LuaScraper>(&crawler_conf, &scraper_conf))
+    let res = if let [scraper_conf] = &scraper_confs[..] {
+        rt.block_on(crawl_site::<LuaScraper>(&crawler_conf, scraper_conf))
+    } else {
+        let multi_conf = MultiLuaScraperConfig::from(scraper_confs.clone());
+        rt.block_on(crawl_site::<MultiLuaScraper>(&crawler_conf, &multi_conf))
+    };
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    print_profile(scraper_conf);
+    let interrupted = res.as_ref().is_err_and(|e| e.is::<Interrupted>());
+    if !args.quiet {
+        summary.print(res.is_err() && !interrupted);
+    }
+    if interrupted {
+        return Ok(ExitCode::from(summary::EXIT_INTERRUPTED));
+    }
+    res.map(|()| summary.exit_code())
+}
+
+fn print_profile(config: &LuaScraperConfig) {
+    if let Some(profiler) = &config.profiler {
+        eprint!("{profiler}");
+    }
 }
 
 /// Scrap a single remote page or multiple local pages
@@ -159,45 +616,89 @@ pub fn crawl(args: CrawlArgs) -> anyhow::Result<()> {
 #[clap(group = clap::ArgGroup::new("mode").requires_all(&["output_file"]))]
 pub struct ScrapArgs {
     /// Path to the Lua script that defines scraping logic
-    #[clap(display_order(1), long, short)]
+    #[clap(display_order(1), long, short, value_hint = ValueHint::FilePath)]
     pub script: PathBuf,
 
     /// A distant html page to scrap
-    #[clap(display_order(2), group = "pages", long)]
+    #[clap(display_order(2), group = "pages", long, value_hint = ValueHint::Url)]
     pub url: Option<String>,
 
-    /// A glob pattern to select local files to scrap
-    #[clap(display_order(3), group = "pages", long = "files")]
+    /// A file listing distant html pages to scrap (one URL per line), '-' for stdin
+    #[clap(display_order(3), group = "pages", long, alias = "urls", value_hint = ValueHint::FilePath)]
+    pub url_file: Option<PathBuf>,
+
+    /// A glob pattern to select local files to scrap (possibly gzipped, or WARC archives)
+    #[clap(display_order(4), group = "pages", long = "files")]
     pub glob: Option<String>,
 
     /// Optional file that will contain scraped data, stdout otherwise
-    #[clap(display_order(4), long, short)]
+    #[clap(display_order(5), long, short, value_hint = ValueHint::FilePath)]
     pub output_file: Option<PathBuf>,
 
     /// Append to output file
-    #[clap(display_order(5), group = "mode", long)]
+    #[clap(display_order(6), group = "mode", long)]
     pub append: bool,
 
     /// Truncate output file
-    #[clap(display_order(6), group = "mode", long)]
+    #[clap(display_order(7), group = "mode", long)]
     pub truncate: bool,
 
-    /// Set the number of CPU workers when scraping local files
-    #[clap(display_order(7), long)]
-    #[clap(conflicts_with = "url")]
+    /// Compress output (inferred from .gz or .zst output file extension otherwise)
+    #[clap(display_order(8), long, value_parser = compression())]
+    pub compress: Option<Compression>,
+
+    /// Set the number of CPU workers when scraping multiple pages
+    #[clap(display_order(9), long)]
+    #[clap(conflicts_with_all = ["url", "stdin"])]
     pub num_workers: Option<usize>,
 
-    /// Scrap error handling strategy when scraping local files
-    #[clap(display_order(8), value_enum, long)]
-    #[clap(conflicts_with = "url")]
+    /// Scrap error handling strategy when scraping multiple pages
+    #[clap(display_order(10), value_enum, long)]
+    #[clap(conflicts_with_all = ["url", "stdin"])]
     pub on_error: Option<OnError>,
 
+    /// Add a directory Lua modules can be required from
+    #[clap(display_order(11), long = "lua-path", value_hint = ValueHint::DirPath)]
+    pub lua_paths: Vec<PathBuf>,
+
+    /// Add a variable passed to the script as sws.args, e.g. 'category=books'
+    #[clap(display_order(12), long = "var", value_parser = key_value)]
+    pub vars: Vec<(String, String)>,
+
+    /// Allow the script to read an environment variable with sws.env
+    #[clap(display_order(13), long = "allow-env")]
+    pub allowed_env_vars: Vec<String>,
+
+    /// Print the time spent in each Lua function and CSS selector once done
+    #[clap(display_order(14), long)]
+    pub profile: bool,
+
     /// Don't output logs
-    #[clap(display_order(9), long, short)]
+    #[clap(display_order(15), long, short)]
     pub quiet: bool,
+
+    /// A header sent with the requests of distant pages (repeatable)
+    #[clap(display_order(16), long = "header", value_parser = header, conflicts_with_all = ["glob", "stdin"])]
+    pub headers: Vec<(String, String)>,
+
+    /// Output format, overriding the writer configured by the script
+    #[clap(display_order(17), long, value_parser = output_format())]
+    pub format: Option<Format>,
+
+    /// Scrap the files again when they or the script change
+    #[clap(display_order(18), long, requires = "glob")]
+    pub watch: bool,
+
+    /// Read a single html page from stdin
+    #[clap(display_order(19), group = "pages", long)]
+    pub stdin: bool,
+
+    /// The URL of the page read from stdin, given to the script as its page location
+    #[clap(display_order(20), long, requires = "stdin", value_hint = ValueHint::Url)]
+    pub location: Option<String>,
 }
 
-pub fn scrap(args: ScrapArgs) -> anyhow::Result<()> {
+pub fn scrap(args: ScrapArgs, base_config: BaseConfig) -> anyhow::Result<ExitCode> {
     let file_mode = if args.append {
         Some(FileMode::Append)
     } else if args.truncate {
@@ -210,54 +711,118 @@ pub fn scrap(args: ScrapArgs) -> anyhow::Result<()> {
         script: args.script,
         csv_file: args.output_file,
         file_mode,
+        compression: args.compress,
+        format: args.format,
+        lua_paths: args.lua_paths,
+        vars: args.vars.into_iter().collect(),
+        allowed_env_vars: args.allowed_env_vars,
+        profiler: args.profile.then(SharedProfiler::default),
+        base_config,
+        ..Default::default()
     };
 
-    match (args.url, args.glob) {
-        (Some(url), None) => {
-            let ua = CrawlerConfig::try_from(&config)?.user_agent;
-            let client = reqwest::blocking::ClientBuilder::new()
-                .user_agent(ua)
-                .build()?;
+    let num_workers = args
+        .num_workers
+        .unwrap_or_else(|| cmp::max(1, num_cpus::get()));
+    let on_error = args.on_error.unwrap_or(OnError::Fail);
+
+    let summary = summary::Summary::start(config.stats.clone());
+    let res = match (args.url, args.url_file, args.glob) {
+        (None, None, None) if args.stdin => {
+            let page = io::read_to_string(io::stdin())?;
+            let location = args.location.map(PageLocation::Url);
+            scrap_page(&config, page, location)
+        }
+        (Some(url), None, None) => {
+            let client = blocking_client(&config, args.headers)?;
             let page = client.get(&url).send()?.text()?;
-            scrap_page(&config, page, PageLocation::Url(url))?
+            scrap_page(&config, page, Some(PageLocation::Url(url)))
         }
-        (None, Some(pattern)) => {
-            let num_workers = args
-                .num_workers
-                .unwrap_or_else(|| cmp::max(1, num_cpus::get()));
-            let on_error = args.on_error.unwrap_or(OnError::Fail);
-            let mut scraper = LuaScraper::new(&config)?;
-            let res = scrap_glob(&config, &pattern, on_error, num_workers);
-            scraper.finalizer();
-            res?;
+        (None, Some(url_file), None) => {
+            let client = blocking_client(&config, args.headers)?;
+            let urls = if url_file == Path::new("-") {
+                io::read_to_string(io::stdin())?
+            } else {
+                fs_err::read_to_string(url_file)?
+            };
+            let urls = urls
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect::<Vec<_>>();
+            let fetch =
+                move |url: &str| -> anyhow::Result<String> { Ok(client.get(url).send()?.text()?) };
+            scrap_urls(&config, urls, fetch, on_error, num_workers)
         }
+        (None, None, Some(pattern)) if args.watch => {
+            watch::watch_glob(&config, &pattern, on_error, num_workers)
+        }
+        (None, None, Some(pattern)) => scrap_glob(&config, &pattern, on_error, num_workers),
         _ => anyhow::bail!("Invalid arguments"),
+    };
+    print_profile(&config);
+    if !args.quiet {
+        summary.print(res.is_err());
     }
+    res.map(|()| summary.exit_code())
+}
+
+fn blocking_client(
+    config: &LuaScraperConfig,
+    headers: Vec<(String, String)>,
+) -> anyhow::Result<reqwest::blocking::Client> {
+    let mut crawler_conf = CrawlerConfig::try_from(config)?;
+    crawler_conf.http.headers.extend(headers);
+    let mut builder = reqwest::blocking::ClientBuilder::new()
+        .user_agent(crawler_conf.user_agent)
+        .default_headers(crawler_conf.http.header_map()?);
+    if let Some(timeout) = crawler_conf.http.timeout {
+        builder = builder.timeout(Duration::from_secs_f32(timeout));
+    }
+    Ok(builder.build()?)
+}
 
-    Ok(())
+fn base_config(path: Option<&Path>) -> anyhow::Result<BaseConfig> {
+    let Some(path) = path else {
+        return Ok(BaseConfig::default());
+    };
+    toml::from_str(&fs_err::read_to_string(path)?)
+        .map_err(|e| anyhow::anyhow!("Invalid config file {}: {e}", path.display()))
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<ExitCode> {
     let args = Args::parse();
+    let base_config = base_config(args.config.as_deref())?;
+
+    let quiet = match &args.cmd {
+        SubCommand::Crawl(args) => args.quiet,
+        SubCommand::Scrap(args) => args.quiet,
+        _ => false,
+    };
+    logging::init(&args.log, quiet)?;
 
     match args.cmd {
-        SubCommand::Crawl(args) => {
-            if !args.quiet {
-                env::set_var("RUST_LOG", "sws_lua=warn,sws_crawler=warn");
-                env_logger::init();
-            }
-            crawl(args)
+        SubCommand::Crawl(args) => crawl(*args, base_config),
+        SubCommand::Scrap(args) => scrap(args, base_config),
+        SubCommand::Shell(args) => shell::shell(args).map(|()| ExitCode::SUCCESS),
+        SubCommand::Sitemap(args) => {
+            sitemap::sitemap(args, base_config).map(|()| ExitCode::SUCCESS)
         }
-        SubCommand::Scrap(args) => {
-            if !args.quiet {
-                env::set_var("RUST_LOG", "sws_lua=warn");
-                env_logger::init();
+        SubCommand::Fetch(args) => fetch::fetch(args, base_config).map(|()| ExitCode::SUCCESS),
+        SubCommand::New(args) => new::new_scraper(args).map(|()| ExitCode::SUCCESS),
+        SubCommand::Bench(args) => bench::bench(args).map(|()| ExitCode::SUCCESS),
+        SubCommand::LuaStubs(args) => {
+            let definitions = sws_lua::stubs::definitions();
+            match args.output {
+                Some(path) => fs_err::write(path, definitions)?,
+                None => print!("{definitions}"),
             }
-            scrap(args)
+            Ok(ExitCode::SUCCESS)
         }
-        SubCommand::Completion => {
-            generate(Shell::Bash, &mut Args::command(), "sws", &mut io::stdout());
-            Ok(())
+        SubCommand::Completions(args) => {
+            generate(args.shell, &mut Args::command(), "sws", &mut io::stdout());
+            Ok(ExitCode::SUCCESS)
         }
     }
 }
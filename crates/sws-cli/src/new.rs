@@ -0,0 +1,59 @@
+use std::path::Path;
+
+/// The files of a new scraper, relative to its directory
+const TEMPLATES: [(&str, &str); 5] = [
+    ("scraper.lua", include_str!("../templates/new/scraper.lua")),
+    (
+        "fixtures/product.html",
+        include_str!("../templates/new/fixtures/product.html"),
+    ),
+    (
+        "tests/scraper.rs",
+        include_str!("../templates/new/tests/scraper.rs"),
+    ),
+    ("src/lib.rs", include_str!("../templates/new/src/lib.rs")),
+    (
+        "Cargo.toml",
+        include_str!("../templates/new/Cargo.toml.tmpl"),
+    ),
+];
+
+/// Create a starter scraper: a Lua script, an HTML fixture and tests scraping it
+#[derive(Debug, clap::Args)]
+pub struct NewArgs {
+    /// The name of the scraper, also the directory it is created in
+    #[clap(display_order(1), value_parser = scraper_name)]
+    pub name: String,
+}
+
+fn scraper_name(s: &str) -> Result<String, String> {
+    let valid = s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !s.is_empty() && valid {
+        Ok(s.to_string())
+    } else {
+        Err("name must only contain ASCII letters, digits, '-' or '_'".into())
+    }
+}
+
+pub fn new_scraper(args: NewArgs) -> anyhow::Result<()> {
+    let dir = Path::new(&args.name);
+    if dir.exists() {
+        anyhow::bail!("{} already exists", dir.display());
+    }
+
+    for (path, content) in TEMPLATES {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs_err::create_dir_all(parent)?;
+        }
+        fs_err::write(&path, content.replace("{name}", &args.name))?;
+    }
+
+    eprintln!("Created scraper {}, try it with:", args.name);
+    eprintln!("  cd {}", args.name);
+    eprintln!("  sws scrap --script scraper.lua --files 'fixtures/*.html'");
+    eprintln!("  cargo test");
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use sws_crawler::{CrawlEvent, CrawlerConfig, ErrorObserver, EventObserver};
+
+/// A spinner on stderr that displays the live counts of a crawl
+pub struct Progress {
+    bar: ProgressBar,
+    counts: Arc<Counts>,
+}
+
+#[derive(Default)]
+struct Counts {
+    discovered: AtomicU64,
+    downloaded: AtomicU64,
+    scraped: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl Progress {
+    /// Hooks the progress display to the events and errors of the crawl
+    pub fn install(crawler_conf: &mut CrawlerConfig) -> Self {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(
+            ProgressStyle::with_template("{spinner} [{elapsed_precise}] {msg}")
+                .expect("valid progress template"),
+        );
+        bar.enable_steady_tick(Duration::from_millis(200));
+        let counts = Arc::new(Counts::default());
+
+        let (bar_c, counts_c) = (bar.clone(), counts.clone());
+        let on_event = crawler_conf.on_event.take();
+        crawler_conf.on_event = Some(EventObserver(Arc::new(move |event| {
+            if let Some(on_event) = &on_event {
+                (on_event.0)(event);
+            }
+            let count = match event {
+                CrawlEvent::Discovered(_) => &counts_c.discovered,
+                CrawlEvent::Downloaded(_) => &counts_c.downloaded,
+                CrawlEvent::Scraped(_) => &counts_c.scraped,
+                CrawlEvent::Planned(_) => return,
+            };
+            count.fetch_add(1, Ordering::Relaxed);
+            bar_c.set_message(counts_c.message(&bar_c));
+        })));
+
+        let (bar_c, counts_c) = (bar.clone(), counts.clone());
+        let on_error = crawler_conf.on_error.take();
+        crawler_conf.on_error = Some(ErrorObserver(Arc::new(move |err| {
+            counts_c.errors.fetch_add(1, Ordering::Relaxed);
+            bar_c.set_message(counts_c.message(&bar_c));
+            if let Some(on_error) = &on_error {
+                (on_error.0)(err);
+            }
+        })));
+
+        Self { bar, counts }
+    }
+
+    /// Stops the spinner and leaves the final counts displayed
+    pub fn finish(&self) {
+        self.bar.finish_with_message(self.counts.message(&self.bar));
+    }
+}
+
+impl Counts {
+    fn message(&self, bar: &ProgressBar) -> String {
+        let downloaded = self.downloaded.load(Ordering::Relaxed);
+        let elapsed = bar.elapsed().as_secs_f64();
+        let rate = if elapsed > 0. {
+            downloaded as f64 / elapsed
+        } else {
+            0.
+        };
+        format!(
+            "{} discovered, {} downloaded, {} scraped, {} errors ({:.1} req/s)",
+            self.discovered.load(Ordering::Relaxed),
+            downloaded,
+            self.scraped.load(Ordering::Relaxed),
+            self.errors.load(Ordering::Relaxed),
+            rate,
+        )
+    }
+}
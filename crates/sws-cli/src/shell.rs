@@ -0,0 +1,91 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use clap::ValueHint;
+use sws_crawler::CrawlerConfig;
+use sws_lua::inspect::PageInspector;
+use sws_lua::LuaScraperConfig;
+
+use crate::key_value;
+
+/// The length after which matched elements are truncated
+const MAX_MATCH_LEN: usize = 300;
+
+/// Explore a page interactively with CSS selectors and Lua snippets
+#[derive(Debug, clap::Args)]
+pub struct ShellArgs {
+    /// A distant html page (http or https URL) or a local html file to explore
+    #[clap(display_order(1), long, value_hint = ValueHint::AnyPath)]
+    pub url: String,
+
+    /// Add a directory Lua modules can be required from
+    #[clap(display_order(2), long = "lua-path", value_hint = ValueHint::DirPath)]
+    pub lua_paths: Vec<PathBuf>,
+
+    /// Add a variable passed to Lua snippets as sws.args, e.g. 'category=books'
+    #[clap(display_order(3), long = "var", value_parser = key_value)]
+    pub vars: Vec<(String, String)>,
+}
+
+pub fn shell(args: ShellArgs) -> anyhow::Result<()> {
+    let page = if args.url.starts_with("http://") || args.url.starts_with("https://") {
+        let client = reqwest::blocking::ClientBuilder::new()
+            .user_agent(CrawlerConfig::default().user_agent)
+            .build()?;
+        client.get(&args.url).send()?.text()?
+    } else {
+        fs_err::read_to_string(&args.url)?
+    };
+
+    let config = LuaScraperConfig {
+        lua_paths: args.lua_paths,
+        vars: args.vars.into_iter().collect(),
+        ..Default::default()
+    };
+    let inspector = PageInspector::new(&config, &page)?;
+
+    eprintln!("Type a CSS selector to list its matches, or '=' followed by Lua code to run it");
+    eprintln!("(the parsed page being `page`). Exit with Ctrl-D.");
+
+    let mut stdin = io::stdin().lock();
+    let mut line = String::new();
+    loop {
+        eprint!("sws> ");
+        io::stderr().flush()?;
+        line.clear();
+        if stdin.read_line(&mut line)? == 0 {
+            eprintln!();
+            return Ok(());
+        }
+
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+        let res = match input.strip_prefix('=') {
+            Some(code) => inspector.eval(code).map(|values| {
+                for value in values {
+                    println!("{value}");
+                }
+            }),
+            None => inspector.select(input).map(|matches| {
+                for (i, elem) in matches.iter().enumerate() {
+                    println!("[{}] {}", i + 1, truncate(elem));
+                }
+                println!("{} match(es)", matches.len());
+            }),
+        };
+        if let Err(e) = res {
+            eprintln!("Error: {e}");
+        }
+    }
+}
+
+/// Collapses the whitespaces of an element's HTML and truncates it.
+fn truncate(html: &str) -> String {
+    let html = html.split_whitespace().collect::<Vec<_>>().join(" ");
+    match html.char_indices().nth(MAX_MATCH_LEN) {
+        Some((end, _)) => format!("{}…", &html[..end]),
+        None => html,
+    }
+}
@@ -0,0 +1,88 @@
+use sws_crawler::serde_json::{self, json};
+use sws_crawler::{
+    crawl_site, CrawlerConfig, CrawlingContext, Scrapable, ScrapingContext, Seed, Sitemap,
+};
+use sws_lua::BaseConfig;
+use tokio::runtime;
+
+/// List the page URLs of a sitemap (or robots.txt), without downloading the pages
+#[derive(Debug, Clone, clap::Args)]
+pub struct SitemapArgs {
+    /// A sitemap or robots.txt URL, nested sitemaps are expanded recursively
+    #[clap(display_order(1))]
+    pub url: String,
+
+    /// Also output the lastmod of the URLs, if any
+    #[clap(display_order(2), long)]
+    pub lastmod: bool,
+
+    /// Format of the listed URLs
+    #[clap(display_order(3), long, value_enum, default_value_t = ListFormat::Text)]
+    pub format: ListFormat,
+
+    /// Override crawler's user agent
+    #[clap(display_order(4), long)]
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ListFormat {
+    /// One URL per line, followed by a tab and its lastmod with --lastmod
+    Text,
+    /// One JSON object per line, with `url` and `lastmod` (with --lastmod) fields
+    Json,
+}
+
+pub fn sitemap(args: SitemapArgs, base_config: BaseConfig) -> anyhow::Result<()> {
+    let mut crawler_conf: CrawlerConfig =
+        serde_json::from_value(serde_json::Value::Object(base_config.crawler))?;
+    if let Some(user_agent) = &args.user_agent {
+        crawler_conf.user_agent = user_agent.clone();
+    }
+    // No page is ever downloaded
+    crawler_conf.num_workers = 1;
+
+    let rt = runtime::Builder::new_multi_thread().enable_all().build()?;
+    rt.block_on(crawl_site::<SitemapLister>(&crawler_conf, &args))
+}
+
+/// Prints the URLs of urlset sitemaps and only accepts nested sitemaps
+struct SitemapLister {
+    args: SitemapArgs,
+}
+
+impl Scrapable for SitemapLister {
+    type Config = SitemapArgs;
+
+    fn new(config: &Self::Config) -> anyhow::Result<Self> {
+        Ok(Self {
+            args: config.clone(),
+        })
+    }
+
+    fn seed(&self) -> Seed {
+        if self.args.url.ends_with("/robots.txt") {
+            Seed::RobotsTxt(self.args.url.clone())
+        } else {
+            Seed::Sitemaps(vec![self.args.url.clone()])
+        }
+    }
+
+    fn accept(&self, url: &str, crawling_ctx: CrawlingContext) -> bool {
+        if let Sitemap::Index = crawling_ctx.sitemap() {
+            return true;
+        }
+        let lastmod = crawling_ctx.lastmod();
+        match (self.args.format, self.args.lastmod) {
+            (ListFormat::Text, false) => println!("{url}"),
+            (ListFormat::Text, true) => println!("{url}\t{}", lastmod.unwrap_or_default()),
+            (ListFormat::Json, false) => println!("{}", json!({ "url": url })),
+            (ListFormat::Json, true) => println!("{}", json!({ "url": url, "lastmod": lastmod })),
+        }
+        false
+    }
+
+    fn scrap(&mut self, _page: String, _scraping_ctx: ScrapingContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
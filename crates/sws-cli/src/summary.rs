@@ -0,0 +1,90 @@
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+
+use sws_crawler::{CrawlEvent, CrawlPhase, CrawlerConfig, ErrorObserver, EventObserver, OnError};
+use sws_lua::SharedStats;
+
+/// Exit code of runs that completed but skipped pages because of errors (hard failures
+/// exit with `1`)
+const EXIT_SKIPPED_ERRORS: u8 = 2;
+
+/// Exit code of crawls interrupted with Ctrl-C, once their records were written (the one
+/// shells use for processes killed by `SIGINT`)
+pub const EXIT_INTERRUPTED: u8 = 130;
+
+/// The counts of a crawl or scrap run, printed on stderr once done
+pub struct Summary {
+    started: Instant,
+    stats: SharedStats,
+}
+
+impl Summary {
+    pub fn start(stats: SharedStats) -> Self {
+        Self {
+            started: Instant::now(),
+            stats,
+        }
+    }
+
+    /// Counts the scraped and skipped pages of a crawl
+    pub fn install(&self, crawler_conf: &mut CrawlerConfig) {
+        let stats = self.stats.clone();
+        let on_event = crawler_conf.on_event.take();
+        crawler_conf.on_event = Some(EventObserver(Arc::new(move |event| {
+            if let Some(on_event) = &on_event {
+                (on_event.0)(event);
+            }
+            if let CrawlEvent::Scraped(_) = event {
+                stats.add_page_ok();
+            }
+        })));
+
+        let stats = self.stats.clone();
+        let (on_dl_error, on_xml_error, on_robots_error, on_scrap_error) = (
+            crawler_conf.on_dl_error,
+            crawler_conf.on_xml_error,
+            crawler_conf.on_robots_error,
+            crawler_conf.on_scrap_error,
+        );
+        let on_error = crawler_conf.on_error.take();
+        crawler_conf.on_error = Some(ErrorObserver(Arc::new(move |err| {
+            // Errors that aren't skipped fail the crawl
+            let skipped = match err.phase {
+                CrawlPhase::Download => on_dl_error,
+                CrawlPhase::Sitemap => on_xml_error,
+                CrawlPhase::Robots => on_robots_error,
+                CrawlPhase::Scrap => on_scrap_error,
+            };
+            if let OnError::SkipAndLog = skipped {
+                stats.add_page_skipped();
+            }
+            if let Some(on_error) = &on_error {
+                (on_error.0)(err);
+            }
+        })));
+    }
+
+    /// Prints the counts, `failed` when the run was aborted by a hard failure
+    pub fn print(&self, failed: bool) {
+        let rows = [
+            ("Pages ok", self.stats.pages_ok().to_string()),
+            ("Pages skipped", self.stats.pages_skipped().to_string()),
+            ("Aborted", if failed { "yes" } else { "no" }.to_string()),
+            ("Records written", self.stats.records_written().to_string()),
+            ("Duration", format!("{:.1?}", self.started.elapsed())),
+        ];
+        for (name, value) in rows {
+            eprintln!("{name:<16}{value:>10}");
+        }
+    }
+
+    /// `0` when no page was skipped, [`EXIT_SKIPPED_ERRORS`] otherwise
+    pub fn exit_code(&self) -> ExitCode {
+        if self.stats.pages_skipped() == 0 {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::from(EXIT_SKIPPED_ERRORS)
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use sws_crawler::OnError;
+use sws_lua::writer::FileMode;
+use sws_lua::{scrap_files, LuaScraperConfig};
+
+/// The period between two checks of the watched files
+const WATCH_PERIOD: Duration = Duration::from_millis(500);
+
+/// Scraps the files matched by `pattern`, then scraps all of them again whenever one of
+/// them or the script changes, the output being truncated so that records aren't written
+/// twice. Scraping errors are logged, and the files are watched until the process is
+/// interrupted.
+pub fn watch_glob(
+    config: &LuaScraperConfig,
+    pattern: &str,
+    on_error: OnError,
+    num_workers: usize,
+) -> anyhow::Result<()> {
+    let mut config = config.clone();
+    let mut script_modified = modified(&config.script);
+    let mut files_modified = glob_modified(pattern)?;
+
+    let mut changed = true;
+    loop {
+        if changed {
+            let paths = files_modified.keys().cloned().collect::<Vec<_>>();
+            log::info!("Scraping {} file(s)", paths.len());
+            if let Err(e) = scrap_files(&config, pattern, paths, on_error, num_workers) {
+                log::error!("{e}");
+            }
+            log::info!(
+                "Watching {pattern} and {} (Ctrl-C to exit)",
+                config.script.display()
+            );
+            // Appending the records of the changed files would duplicate their previous
+            // ones, all the records are written again instead
+            config.file_mode = Some(FileMode::Truncate);
+        }
+
+        thread::sleep(WATCH_PERIOD);
+
+        let script = modified(&config.script);
+        let files = glob_modified(pattern)?;
+        changed = script != script_modified || files != files_modified;
+        script_modified = script;
+        files_modified = files;
+    }
+}
+
+/// The last modification time of a file, unknown while it is being replaced.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs_err::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn glob_modified(pattern: &str) -> anyhow::Result<HashMap<PathBuf, SystemTime>> {
+    let mut files = HashMap::new();
+    for path in glob::glob(pattern)?.flatten() {
+        if let Some(time) = modified(&path) {
+            files.insert(path, time);
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,33 @@
+-- Seed: where the crawl starts, see the "Seed definition" section of the Lua scraper docs
+sws.seedSitemaps = {
+   "https://www.example.com/sitemap.xml"
+}
+
+-- Output: the named columns of the records written by scrapPage
+sws.csvWriterConfig = {
+   columns = { "url", "title", "price" },
+}
+
+-- Only product pages are scraped, sitemap indexes are always followed
+function acceptUrl(url, context)
+   if context:sitemap() == sws.Sitemap.INDEX then
+      return true
+   end
+   return string.find(url, "/products/", 1, true) ~= nil
+end
+
+function scrapPage(page, context)
+   local title = page:select("h1"):iter()()
+   if not title then
+      return
+   end
+
+   local record = sws.Record()
+   record:set("url", context:pageLocation():get())
+   record:set("title", title:innerText())
+   local price = page:select(".price"):iter()()
+   if price then
+      record:set("price", price:innerText())
+   end
+   context:sendRecord(record)
+end
@@ -0,0 +1,2 @@
+//! The scraper is `scraper.lua`, its tests against the HTML pages of `fixtures/` are run
+//! with `cargo test`.
@@ -0,0 +1,26 @@
+use sws_crawler::{CrawlingContext, Sitemap};
+use sws_lua::writer::Record;
+
+#[test]
+fn scrap_product_page() {
+    let records = sws_lua::test::run_fixture("scraper.lua", "fixtures/product.html").unwrap();
+    let [Record::Named(fields)] = &records[..] else {
+        panic!("Expected a single named record, got: {records:?}");
+    };
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+    assert_eq!(field("title"), Some("Some product"));
+    assert_eq!(field("price"), Some("9.99"));
+}
+
+#[test]
+fn accept_product_urls() {
+    let ctx = || CrawlingContext::new(Sitemap::Urlset, None, None, 0, None);
+    let accept = |url| sws_lua::test::accept_url("scraper.lua", url, ctx()).unwrap();
+    assert!(accept("https://www.example.com/products/some-product"));
+    assert!(!accept("https://www.example.com/about"));
+}
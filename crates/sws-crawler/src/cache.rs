@@ -0,0 +1,118 @@
+//! On-disk cache of downloaded pages, revalidated with conditional requests.
+
+use std::path::{Path, PathBuf};
+
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The metadata of a cached response, its raw body is stored alongside.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    final_url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+/// A response previously received for a URL.
+#[derive(Debug)]
+pub(crate) struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub final_url: String,
+    /// The body as received, before decompression
+    pub body: Vec<u8>,
+}
+
+impl CachedResponse {
+    /// The headers asking the server to answer `304 Not Modified` if this response is
+    /// still fresh.
+    pub fn validators(&self) -> HeaderMap {
+        let mut validators = HeaderMap::new();
+        if let Some(etag) = self.headers.get(ETAG) {
+            validators.insert(IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = self.headers.get(LAST_MODIFIED) {
+            validators.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+        validators
+    }
+}
+
+/// Loads the cached response of `url`, if any.
+pub(crate) async fn load(dir: &Path, url: &str) -> Option<CachedResponse> {
+    let (meta_path, body_path) = entry_paths(dir, url);
+    let url = url.to_string();
+    tokio::task::spawn_blocking(move || {
+        let entry = std::fs::read(&meta_path).ok()?;
+        let entry: Entry = serde_json::from_slice(&entry)
+            .inspect_err(|e| log::warn!("Invalid cache entry {} got: {e}", meta_path.display()))
+            .ok()?;
+        // Hash collisions are unlikely but harmless
+        if entry.url != url {
+            return None;
+        }
+        let body = std::fs::read(&body_path).ok()?;
+        let headers = entry
+            .headers
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+                let value = HeaderValue::from_str(&value).ok()?;
+                Some((name, value))
+            })
+            .collect();
+        Some(CachedResponse {
+            status: StatusCode::from_u16(entry.status).ok()?,
+            headers,
+            final_url: entry.final_url,
+            body,
+        })
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Stores a successful response that can be revalidated later, that is with an `ETag` or
+/// a `Last-Modified` header.
+pub(crate) fn store(dir: &Path, url: &str, resp: CachedResponse) {
+    if !resp.status.is_success()
+        || !(resp.headers.contains_key(ETAG) || resp.headers.contains_key(LAST_MODIFIED))
+    {
+        return;
+    }
+    let entry = Entry {
+        url: url.to_string(),
+        final_url: resp.final_url,
+        status: resp.status.as_u16(),
+        headers: resp
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+    };
+    let (meta_path, body_path) = entry_paths(dir, url);
+    tokio::task::spawn_blocking(move || {
+        // The metadata is written last, so that an entry is only loaded once complete
+        let res = std::fs::write(&body_path, resp.body).and_then(|_| {
+            let entry = serde_json::to_vec(&entry)?;
+            std::fs::write(&meta_path, entry)
+        });
+        if let Err(e) = res {
+            log::warn!("Couldn't cache page to {} got: {e}", meta_path.display());
+        }
+    });
+}
+
+fn entry_paths(dir: &Path, url: &str) -> (PathBuf, PathBuf) {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    (
+        dir.join(format!("{hash}.json")),
+        dir.join(format!("{hash}.body")),
+    )
+}
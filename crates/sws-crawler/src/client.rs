@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+
+use crate::config::CrawlerConfig;
+
+/// Builds the HTTP client used for all the requests of a crawl.
+pub(crate) fn build_client(config: &CrawlerConfig) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::ClientBuilder::new();
+
+    let dns = &config.dns;
+    for (host, ips) in &dns.overrides {
+        // Ports are ignored by reqwest, the URL's port is used instead
+        let addrs = ips
+            .iter()
+            .map(|ip| SocketAddr::new(*ip, 0))
+            .collect::<Vec<_>>();
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    if dns.resolver.is_some() || dns.cache_ttl.is_some() {
+        let resolver = CachingResolver {
+            inner: dns.resolver.clone().map(|r| r.0),
+            ttl: dns.cache_ttl.map(Duration::from_secs),
+            cache: Default::default(),
+        };
+        builder = builder.dns_resolver(Arc::new(resolver));
+    }
+
+    let tls = &config.tls;
+    for path in &tls.root_certificates {
+        let cert = reqwest::Certificate::from_pem(&fs_err::read(path)?)?;
+        builder = builder.add_root_certificate(cert);
+    }
+    if let Some(client_cert) = &tls.client_certificate {
+        let identity = reqwest::Identity::from_pkcs8_pem(
+            &fs_err::read(&client_cert.cert)?,
+            &fs_err::read(&client_cert.key)?,
+        )?;
+        builder = builder.identity(identity);
+    }
+    if tls.accept_invalid_certs {
+        log::warn!("TLS certificates validation is disabled");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    let http = &config.http;
+    if let Some(timeout) = http.timeout {
+        builder = builder.timeout(Duration::from_secs_f32(timeout));
+    }
+    if let Some(max) = http.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(timeout) = http.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(Duration::from_secs_f32(timeout));
+    }
+    if let Some(interval) = http.tcp_keepalive {
+        builder = builder.tcp_keepalive(Duration::from_secs_f32(interval));
+    }
+    if let Some(nodelay) = http.tcp_nodelay {
+        builder = builder.tcp_nodelay(nodelay);
+    }
+    match (http.http1_only, http.http2_prior_knowledge) {
+        (true, true) => anyhow::bail!(
+            "Invalid http config, cannot use both `http1_only` and `http2_prior_knowledge`"
+        ),
+        (true, false) => builder = builder.http1_only(),
+        (false, true) => builder = builder.http2_prior_knowledge(),
+        (false, false) => (),
+    }
+    if let Some(interval) = http.http2_keep_alive_interval {
+        builder = builder.http2_keep_alive_interval(Duration::from_secs_f32(interval));
+    }
+    builder = builder.http2_adaptive_window(http.http2_adaptive_window);
+    builder = builder.default_headers(http.header_map()?);
+
+    if !config.proxies.is_empty() {
+        let proxies = config
+            .proxies
+            .iter()
+            .map(|url| {
+                reqwest::Url::parse(url)
+                    .map_err(|e| anyhow::anyhow!("Invalid proxy URL `{url}`: {e}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // Proxies are picked in turn for each request (connections being pooled per proxy)
+        let next = AtomicUsize::new(0);
+        builder = builder.proxy(reqwest::Proxy::custom(move |_| {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            Some(proxies[i % proxies.len()].clone())
+        }));
+    }
+
+    Ok(builder.build()?)
+}
+
+type DnsCache = HashMap<String, (Instant, Vec<SocketAddr>)>;
+
+/// A resolver that delegates to a custom resolver (or to the system one when unset),
+/// optionally caching resolved addresses.
+struct CachingResolver {
+    inner: Option<Arc<dyn Resolve>>,
+    ttl: Option<Duration>,
+    cache: Arc<Mutex<DnsCache>>,
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+
+        if let Some(ttl) = self.ttl {
+            let mut cache = self.cache.lock().unwrap();
+            match cache.get(&host) {
+                Some((at, addrs)) if at.elapsed() < ttl => {
+                    let addrs: Addrs = Box::new(addrs.clone().into_iter());
+                    return Box::pin(async move { Ok(addrs) });
+                }
+                Some(_) => {
+                    cache.remove(&host);
+                }
+                None => (),
+            }
+        }
+
+        let inner = self.inner.clone();
+        let ttl = self.ttl;
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = match inner {
+                Some(resolver) => resolver.resolve(name).await?.collect(),
+                None => tokio::net::lookup_host((host.as_str(), 0)).await?.collect(),
+            };
+            if ttl.is_some() {
+                let entry = (Instant::now(), addrs.clone());
+                cache.lock().unwrap().insert(host, entry);
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
@@ -1,8 +1,17 @@
-use std::cmp;
+use std::collections::HashMap;
+use std::net::IpAddr;
 use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{cmp, fmt};
 
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
 
+use crate::error::ErrorObserver;
+use crate::event::EventObserver;
+use crate::frontier::FrontierBackend;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CrawlerConfig {
@@ -12,9 +21,15 @@ pub struct CrawlerConfig {
     #[serde(default = "default_page_buffer")]
     pub page_buffer: usize,
 
+    #[serde(default = "default_url_buffer")]
+    pub url_buffer: usize,
+
     #[serde(default = "default_throttle")]
     pub throttle: Option<Throttle>,
 
+    #[serde(default = "default_throttle_burst")]
+    pub throttle_burst: usize,
+
     #[serde(default = "default_num_workers")]
     pub num_workers: usize,
 
@@ -27,8 +42,80 @@ pub struct CrawlerConfig {
     #[serde(default = "default_on_scrap_error")]
     pub on_scrap_error: OnError,
 
+    #[serde(default = "default_scrap_timeout")]
+    pub scrap_timeout: Option<f32>,
+
+    #[serde(default = "default_max_scrap_retries")]
+    pub max_scrap_retries: usize,
+
+    #[serde(default = "default_max_pages")]
+    pub max_pages: Option<usize>,
+
+    #[serde(default = "default_max_duration")]
+    pub max_duration: Option<f32>,
+
+    #[serde(default = "default_max_depth")]
+    pub max_depth: Option<usize>,
+
+    #[serde(default = "default_same_host_only")]
+    pub same_host_only: bool,
+
+    #[serde(skip, default = "default_on_error")]
+    pub on_error: Option<ErrorObserver>,
+
+    #[serde(skip, default = "default_on_event")]
+    pub on_event: Option<EventObserver>,
+
     #[serde(default = "default_robot")]
     pub robot: Option<String>,
+
+    #[serde(default = "default_on_robots_error")]
+    pub on_robots_error: OnError,
+
+    #[serde(default = "default_robots_fallback")]
+    pub robots_fallback: RobotsFallback,
+
+    #[serde(default = "default_robots_per_host")]
+    pub robots_per_host: bool,
+
+    #[serde(default = "default_save_pages_dir")]
+    pub save_pages_dir: Option<PathBuf>,
+
+    #[serde(default = "default_cache_dir")]
+    pub cache_dir: Option<PathBuf>,
+
+    #[serde(default = "default_state_file")]
+    pub state_file: Option<PathBuf>,
+
+    #[serde(default = "default_skip_unchanged")]
+    pub skip_unchanged: bool,
+
+    #[serde(default = "default_revisit")]
+    pub revisit: Revisit,
+
+    #[serde(default = "default_resume")]
+    pub resume: bool,
+
+    #[serde(default = "default_dns")]
+    pub dns: DnsConfig,
+
+    #[serde(default = "default_tls")]
+    pub tls: TlsConfig,
+
+    #[serde(default = "default_http")]
+    pub http: HttpConfig,
+
+    #[serde(default = "default_proxies")]
+    pub proxies: Vec<String>,
+
+    #[serde(default = "default_url_filters")]
+    pub url_filters: UrlFilters,
+
+    #[serde(default = "default_frontier")]
+    pub frontier: FrontierConfig,
+
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool,
 }
 
 impl Default for CrawlerConfig {
@@ -36,12 +123,38 @@ impl Default for CrawlerConfig {
         Self {
             user_agent: default_user_agent(),
             page_buffer: default_page_buffer(),
+            url_buffer: default_url_buffer(),
             throttle: default_throttle(),
+            throttle_burst: default_throttle_burst(),
             num_workers: default_num_workers(),
             on_dl_error: default_on_dl_error(),
             on_xml_error: default_on_xml_error(),
             on_scrap_error: default_on_scrap_error(),
+            scrap_timeout: default_scrap_timeout(),
+            max_scrap_retries: default_max_scrap_retries(),
+            max_pages: default_max_pages(),
+            max_duration: default_max_duration(),
+            max_depth: default_max_depth(),
+            same_host_only: default_same_host_only(),
+            on_error: default_on_error(),
+            on_event: default_on_event(),
             robot: default_robot(),
+            on_robots_error: default_on_robots_error(),
+            robots_fallback: default_robots_fallback(),
+            robots_per_host: default_robots_per_host(),
+            save_pages_dir: default_save_pages_dir(),
+            cache_dir: default_cache_dir(),
+            state_file: default_state_file(),
+            skip_unchanged: default_skip_unchanged(),
+            revisit: default_revisit(),
+            resume: default_resume(),
+            dns: default_dns(),
+            tls: default_tls(),
+            http: default_http(),
+            proxies: default_proxies(),
+            url_filters: default_url_filters(),
+            frontier: default_frontier(),
+            dry_run: default_dry_run(),
         }
     }
 }
@@ -54,10 +167,18 @@ fn default_page_buffer() -> usize {
     10_000
 }
 
+fn default_url_buffer() -> usize {
+    100_000
+}
+
 fn default_throttle() -> Option<Throttle> {
     None
 }
 
+fn default_throttle_burst() -> usize {
+    1
+}
+
 fn default_num_workers() -> usize {
     cmp::max(1, num_cpus::get().saturating_sub(2))
 }
@@ -74,10 +195,106 @@ fn default_on_scrap_error() -> OnError {
     OnError::SkipAndLog
 }
 
+fn default_scrap_timeout() -> Option<f32> {
+    None
+}
+
+fn default_max_scrap_retries() -> usize {
+    3
+}
+
+fn default_max_pages() -> Option<usize> {
+    None
+}
+
+fn default_max_duration() -> Option<f32> {
+    None
+}
+
+fn default_max_depth() -> Option<usize> {
+    None
+}
+
+fn default_same_host_only() -> bool {
+    false
+}
+
+fn default_on_error() -> Option<ErrorObserver> {
+    None
+}
+
+fn default_on_event() -> Option<EventObserver> {
+    None
+}
+
 fn default_robot() -> Option<String> {
     None
 }
 
+fn default_on_robots_error() -> OnError {
+    OnError::Fail
+}
+
+fn default_robots_fallback() -> RobotsFallback {
+    RobotsFallback::Allow
+}
+
+fn default_robots_per_host() -> bool {
+    false
+}
+
+fn default_save_pages_dir() -> Option<PathBuf> {
+    None
+}
+
+fn default_cache_dir() -> Option<PathBuf> {
+    None
+}
+
+fn default_state_file() -> Option<PathBuf> {
+    None
+}
+
+fn default_skip_unchanged() -> bool {
+    false
+}
+
+fn default_revisit() -> Revisit {
+    Revisit::Always
+}
+
+fn default_resume() -> bool {
+    false
+}
+
+fn default_dns() -> DnsConfig {
+    DnsConfig::default()
+}
+
+fn default_tls() -> TlsConfig {
+    TlsConfig::default()
+}
+
+fn default_http() -> HttpConfig {
+    HttpConfig::default()
+}
+
+fn default_proxies() -> Vec<String> {
+    vec![]
+}
+
+fn default_url_filters() -> UrlFilters {
+    UrlFilters::default()
+}
+
+fn default_frontier() -> FrontierConfig {
+    FrontierConfig::default()
+}
+
+fn default_dry_run() -> bool {
+    false
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 pub enum OnError {
@@ -93,6 +310,10 @@ pub enum Throttle {
     PerSecond(NonZeroUsize),
     /// The delay in seconds between requests
     Delay(f32),
+    /// The number of requests per second to each host
+    PerHostPerSecond(NonZeroUsize),
+    /// The delay in seconds between requests to each host
+    PerHostDelay(f32),
 }
 
 impl Default for Throttle {
@@ -100,3 +321,255 @@ impl Default for Throttle {
         Self::Concurrent(100.try_into().unwrap())
     }
 }
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum RobotsFallback {
+    /// Assume everything is allowed
+    Allow,
+    /// Assume everything is disallowed
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Revisit {
+    /// Always fetch accepted URLs
+    Always,
+    /// Only fetch URLs whose sitemap `lastmod` is newer than their last successful scrap
+    IfModified,
+}
+
+/// The user agent of a common browser (or crawler) along with the headers it sends, as
+/// some sites serve degraded pages to unknown user agents.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum UserAgentPreset {
+    Chrome,
+    Firefox,
+    Safari,
+    Googlebot,
+}
+
+impl UserAgentPreset {
+    pub fn user_agent(&self) -> &'static str {
+        match self {
+            Self::Chrome => {
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+                 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36"
+            }
+            Self::Firefox => {
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:125.0) Gecko/20100101 Firefox/125.0"
+            }
+            Self::Safari => {
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 \
+                 (KHTML, like Gecko) Version/17.4.1 Safari/605.1.15"
+            }
+            Self::Googlebot => {
+                "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)"
+            }
+        }
+    }
+
+    /// The headers sent along with the user agent, `Accept-Encoding` is always set by the
+    /// crawler.
+    pub fn headers(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Chrome => &[
+                (
+                    "Accept",
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,\
+                     image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7",
+                ),
+                ("Accept-Language", "en-US,en;q=0.9"),
+                (
+                    "sec-ch-ua",
+                    r#""Chromium";v="124", "Google Chrome";v="124", "Not-A.Brand";v="99""#,
+                ),
+                ("sec-ch-ua-mobile", "?0"),
+                ("sec-ch-ua-platform", r#""Windows""#),
+                ("Upgrade-Insecure-Requests", "1"),
+            ],
+            Self::Firefox => &[
+                (
+                    "Accept",
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,\
+                     image/webp,*/*;q=0.8",
+                ),
+                ("Accept-Language", "en-US,en;q=0.5"),
+                ("Upgrade-Insecure-Requests", "1"),
+            ],
+            Self::Safari => &[
+                (
+                    "Accept",
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                ),
+                ("Accept-Language", "en-US,en;q=0.9"),
+            ],
+            Self::Googlebot => &[(
+                "Accept",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            )],
+        }
+    }
+
+    /// Sets the user agent of `config` and adds the preset headers, the headers already
+    /// set are kept.
+    pub fn apply(&self, config: &mut CrawlerConfig) {
+        config.user_agent = self.user_agent().to_string();
+        for (name, value) in self.headers() {
+            let exists = config
+                .http
+                .headers
+                .keys()
+                .any(|set| set.eq_ignore_ascii_case(name));
+            if !exists {
+                config
+                    .http
+                    .headers
+                    .insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsConfig {
+    /// Hosts resolved to the given IPs instead of querying the resolver
+    #[serde(default)]
+    pub overrides: HashMap<String, Vec<IpAddr>>,
+    /// The duration in seconds during which resolved addresses are cached
+    #[serde(default)]
+    pub cache_ttl: Option<u64>,
+    /// A custom resolver used instead of the system one
+    #[serde(skip)]
+    pub resolver: Option<DnsResolver>,
+}
+
+#[derive(Clone)]
+pub struct DnsResolver(pub Arc<dyn reqwest::dns::Resolve>);
+
+impl fmt::Debug for DnsResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DnsResolver").finish()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// PEM encoded certificates trusted in addition to the system ones
+    #[serde(default)]
+    pub root_certificates: Vec<PathBuf>,
+    /// A client certificate used to authenticate to servers
+    #[serde(default)]
+    pub client_certificate: Option<ClientCertificate>,
+    /// Whether to accept invalid certificates, only meant for internal environments
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientCertificate {
+    /// A PEM encoded certificate chain
+    pub cert: PathBuf,
+    /// A PEM encoded PKCS#8 private key
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpConfig {
+    /// The duration in seconds after which a request (until its body is read) fails
+    #[serde(default)]
+    pub timeout: Option<f32>,
+    /// The maximum number of idle connections kept alive per host
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// The duration in seconds after which idle connections are closed
+    #[serde(default)]
+    pub pool_idle_timeout: Option<f32>,
+    /// The interval in seconds of TCP keep-alive probes, disabled when not set
+    #[serde(default)]
+    pub tcp_keepalive: Option<f32>,
+    /// Whether to set `TCP_NODELAY` on connections, enabled when not set
+    #[serde(default)]
+    pub tcp_nodelay: Option<bool>,
+    /// Whether to only use HTTP/1
+    #[serde(default)]
+    pub http1_only: bool,
+    /// Whether to use HTTP/2 without protocol negotiation
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+    /// The interval in seconds of HTTP/2 keep-alive pings, disabled when not set
+    #[serde(default)]
+    pub http2_keep_alive_interval: Option<f32>,
+    /// Whether to use adaptive flow control for HTTP/2
+    #[serde(default)]
+    pub http2_adaptive_window: bool,
+    /// Headers sent with every request, the `User-Agent` one is set by `user_agent`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl HttpConfig {
+    /// The default headers of HTTP clients, failing on invalid header names or values
+    pub fn header_map(&self) -> anyhow::Result<HeaderMap> {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| anyhow::anyhow!("Invalid header name `{name}`: {e}"))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| anyhow::anyhow!("Invalid value for header `{name}`: {e}"))?;
+            header_map.insert(name, value);
+        }
+        Ok(header_map)
+    }
+}
+
+/// Filters of the page URLs found in sitemaps, nested sitemaps are never filtered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlFilters {
+    /// Regexes of URLs to crawl, all URLs are crawled when no accept pattern is set
+    #[serde(default)]
+    pub accept: Vec<String>,
+    /// Regexes of URLs to never crawl, taking precedence over accept patterns
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Same as `accept` but with globs matching whole URLs
+    #[serde(default)]
+    pub accept_globs: Vec<String>,
+    /// Same as `deny` but with globs matching whole URLs
+    #[serde(default)]
+    pub deny_globs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrontierConfig {
+    /// The URL of a Redis server holding a frontier shared by multiple crawlers, this
+    /// requires the `redis` feature
+    #[serde(default)]
+    pub redis_url: Option<String>,
+    /// The prefix of the Redis keys used by the shared frontier, `sws` when not set
+    #[serde(default)]
+    pub redis_key: Option<String>,
+    /// The duration in seconds a shared frontier must stay empty for the crawl to be
+    /// considered done, `10` when not set
+    #[serde(default)]
+    pub idle_timeout: Option<f32>,
+    /// A custom shared frontier used instead of the in-memory one
+    #[serde(skip)]
+    pub backend: Option<SharedFrontier>,
+}
+
+#[derive(Clone)]
+pub struct SharedFrontier(pub Arc<dyn FrontierBackend>);
+
+impl fmt::Debug for SharedFrontier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedFrontier").finish()
+    }
+}
@@ -1,63 +1,114 @@
 use std::future::Future;
-use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Error, Result};
-use flate2::read::GzDecoder;
 use futures::{future, stream, try_join, Stream, StreamExt};
 use lazy_static::lazy_static;
-use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_TYPE, USER_AGENT};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
 use sxd_document::parser;
 use texting_robots::Robot;
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tokio::time::timeout;
-use tokio_stream::wrappers::UnboundedReceiverStream;
 
-use crate::config::{CrawlerConfig, OnError, Throttle};
+use crate::cache::{self, CachedResponse};
+use crate::client;
+use crate::config::{CrawlerConfig, FrontierConfig, OnError, Revisit, Throttle};
+use crate::decode;
+use crate::error::{self, CrawlPhase, Interrupted};
+use crate::event::{self, CrawlEvent};
+use crate::filter::UrlFilter;
+use crate::frontier::{self, Frontier, Prioritized, UrlQueue};
 use crate::limiter::{RateLimitedExt, RateLimiter};
+use crate::robots::{self, HostRobots};
 use crate::scrapable::{
-    CountedTx, CrawlingContext, PageLocation, Scrapable, ScrapingContext, Seed, Sitemap,
+    CountedTx, CrawlStop, CrawlingContext, FetchInfo, FetchRequest, FetchedPage, Fetcher, InFlight,
+    PageLocation, PageOutcome, PageRequest, Scrapable, ScrapingContext, Seed, Sitemap,
 };
+use crate::state::{content_hash, StateStore};
+
+/// The number of seconds between two saves of the crawl state during a crawl
+const STATE_SAVE_PERIOD: u32 = 30;
 
 lazy_static! {
-    static ref HTTP_CLI: reqwest::Client = reqwest::ClientBuilder::new()
-        .gzip(true)
-        .deflate(true)
-        .build()
-        .unwrap();
     static ref XP_FACTORY: sxd_xpath::Factory = sxd_xpath::Factory::new();
 }
 
+/// Handles shared by all the tasks gathering URLs from sitemaps.
+#[derive(Debug, Clone)]
+struct Gathering {
+    tx_url: CountedTx,
+    throttler: Throttler,
+    robot: Option<Arc<Robot>>,
+    state: Option<Arc<StateStore>>,
+    filter: Arc<UrlFilter>,
+    host_robots: Option<Arc<HostRobots>>,
+    /// The nesting depth of the sitemap being gathered
+    depth: usize,
+}
+
 fn gather_urls<'a, T>(
     config: &'a CrawlerConfig,
+    client: &'a reqwest::Client,
     scraper: &'a T,
     sitemap_url: &'a str,
-    tx_url: CountedTx,
-    throttler: Throttler,
-    robot: Option<Arc<Robot>>,
+    gathering: Gathering,
 ) -> Pin<Box<dyn Future<Output = Result<()>> + 'a>>
 where
     T: Scrapable,
 {
     Box::pin(async move {
+        let Gathering {
+            tx_url,
+            throttler,
+            robot,
+            state,
+            filter,
+            host_robots,
+            depth,
+        } = &gathering;
+
+        // Sitemaps entries are expected to belong to the sitemap's host
+        let robot = match host_robots {
+            Some(host_robots) => host_robots
+                .get(config, client, sitemap_url)
+                .await?
+                .or_else(|| robot.clone()),
+            None => robot.clone(),
+        };
+
         let Page {
-            page: sitemap_xml, ..
-        } = download(config, sitemap_url).await?;
+            page: sitemap_xml,
+            fetch_info,
+            ..
+        } = download(config, client, &sitemap_url.into())
+            .await
+            .inspect_err(|e| error::report(config, sitemap_url, CrawlPhase::Download, e, 1))?;
+        let sitemap_xml = match decode::decompress_sitemap(&fetch_info.headers, &fetch_info.body) {
+            Some(body) => decode::decode_text(&body, decode::charset(&fetch_info.headers)),
+            None => sitemap_xml,
+        };
 
         let package = match parser::parse(&sitemap_xml) {
             Ok(package) => package,
-            Err(e) => match config.on_xml_error {
-                OnError::SkipAndLog => {
-                    log::warn!("Skipping XML: {sitemap_url} got: {e}");
-                    return Ok(());
+            Err(e) => {
+                error::report(config, sitemap_url, CrawlPhase::Sitemap, &anyhow!("{e}"), 1);
+                match config.on_xml_error {
+                    OnError::SkipAndLog => {
+                        log::warn!("Skipping XML: {sitemap_url} got: {e}");
+                        return Ok(());
+                    }
+                    OnError::Fail => return Err(anyhow!("Couldn't parse {sitemap_url} got: {e}")),
                 }
-                OnError::Fail => return Err(anyhow!("Couldn't parse {sitemap_url} got: {e}")),
-            },
+            }
         };
         let document = package.as_document();
 
@@ -70,17 +121,20 @@ where
             .ok_or_else(|| anyhow!("Missing XPath"))?;
         let value = match xpath.evaluate(&context, document.root()) {
             Ok(value) => value,
-            Err(e) => match config.on_xml_error {
-                OnError::SkipAndLog => {
-                    log::warn!("Skipping XML: {sitemap_url} xpath {xpath:?} got: {e}");
-                    return Ok(());
-                }
-                OnError::Fail => {
-                    return Err(anyhow!(
-                        "Couldn't evaluate {xpath:?} for {sitemap_url} got: {e}"
-                    ))
+            Err(e) => {
+                error::report(config, sitemap_url, CrawlPhase::Sitemap, &anyhow!("{e}"), 1);
+                match config.on_xml_error {
+                    OnError::SkipAndLog => {
+                        log::warn!("Skipping XML: {sitemap_url} xpath {xpath:?} got: {e}");
+                        return Ok(());
+                    }
+                    OnError::Fail => {
+                        return Err(anyhow!(
+                            "Couldn't evaluate {xpath:?} for {sitemap_url} got: {e}"
+                        ))
+                    }
                 }
-            },
+            }
         };
 
         if let sxd_xpath::Value::Nodeset(nodes) = value {
@@ -88,17 +142,31 @@ where
                 Sitemap::Index => {
                     let urls = nodes
                         .iter()
-                        .map(|node| node.string_value())
-                        .filter(|sm_url| {
-                            let ctx = CrawlingContext::new(sm_kind, robot.clone());
+                        .map(|node| (node.string_value(), entry_lastmod(node)))
+                        .filter(|(sm_url, lastmod)| {
+                            let ctx = CrawlingContext::new(
+                                sm_kind,
+                                robot.clone(),
+                                Some(sitemap_url.to_string()),
+                                *depth,
+                                lastmod.clone(),
+                            );
                             scraper.accept(sm_url, ctx)
                         })
-                        .map(|url| (url, tx_url.clone(), throttler.clone(), robot.clone()));
-
-                    let stream =
-                        stream::iter(urls).map(|(sm_url, tx_url, limiter, robot)| async move {
-                            gather_urls(config, scraper, &sm_url, tx_url, limiter, robot).await
+                        .map(|(url, _)| {
+                            let gathering = Gathering {
+                                depth: depth + 1,
+                                ..gathering.clone()
+                            };
+                            (url, gathering)
                         });
+
+                    let stream = stream::iter(urls).map(|(sm_url, gathering)| {
+                        let url = sm_url.clone();
+                        (url, async move {
+                            gather_urls(config, client, scraper, &sm_url, gathering).await
+                        })
+                    });
                     let stream = throttler.throttle(stream);
 
                     match config.on_dl_error {
@@ -120,9 +188,26 @@ where
                 Sitemap::Urlset => {
                     for node in nodes {
                         let page_url = node.string_value();
-                        let ctx = CrawlingContext::new(sm_kind, robot.clone());
-                        if scraper.accept(&page_url, ctx) {
-                            tx_url.send(page_url);
+                        if !filter.allows(&page_url) {
+                            continue;
+                        }
+                        let lastmod = entry_lastmod(node);
+                        if let (Revisit::IfModified, Some(state)) = (config.revisit, state) {
+                            if !modified_since_scrap(state, &page_url, lastmod.as_deref()) {
+                                log::debug!("Skipping unmodified URL {page_url}");
+                                continue;
+                            }
+                        }
+                        let ctx = CrawlingContext::new(
+                            sm_kind,
+                            robot.clone(),
+                            Some(sitemap_url.to_string()),
+                            *depth,
+                            lastmod,
+                        );
+                        if scraper.accept(&page_url, ctx.clone()) {
+                            let priority = scraper.priority(&page_url, ctx);
+                            tx_url.send_when_ready(page_url, priority).await;
                         }
                     }
                 }
@@ -133,12 +218,59 @@ where
     })
 }
 
+/// Reads the `<lastmod>` sibling of a sitemap `<loc>` node, if any.
+fn entry_lastmod(loc: sxd_xpath::nodeset::Node) -> Option<String> {
+    loc.parent()?
+        .children()
+        .into_iter()
+        .find(|n| {
+            n.expanded_name()
+                .map(|name| name.local_part() == "lastmod")
+                .unwrap_or(false)
+        })
+        .map(|n| n.string_value().trim().to_string())
+}
+
+/// Parses a [W3C Datetime](https://www.w3.org/TR/NOTE-datetime) as found in sitemaps.
+fn lastmod_timestamp(lastmod: &str) -> Option<i64> {
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(lastmod) {
+        return Some(dt.timestamp());
+    }
+    if let Ok(dt) = chrono::DateTime::parse_from_str(lastmod, "%Y-%m-%dT%H:%M%:z") {
+        return Some(dt.timestamp());
+    }
+    chrono::NaiveDate::parse_from_str(lastmod, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc().timestamp())
+}
+
+/// Whether `url` should be fetched again, URLs without a known `lastmod` or without a
+/// previous successful scrap are always considered as modified.
+fn modified_since_scrap(state: &StateStore, url: &str, lastmod: Option<&str>) -> bool {
+    match (state.get(url), lastmod.and_then(lastmod_timestamp)) {
+        (Some(prev), Some(lastmod)) => lastmod > prev.scraped_at as i64,
+        _ => true,
+    }
+}
+
 #[derive(Debug, Clone)]
-struct Page {
-    page: String,
+pub(crate) struct Page {
+    pub(crate) page: String,
+    pub(crate) fetch_info: FetchInfo,
+    request: Arc<PageRequest>,
     location: PageLocation,
+    robot: Option<Arc<Robot>>,
 }
 
+/// The maximum number of requests being sent at once, when throttling per host.
+const PER_HOST_IN_FLIGHT: usize = 100;
+
+/// The maximum number of requests waiting for their host's rate limit, when throttling
+/// per host. Requests are pulled from the queue ahead of their turn so that throttled hosts
+/// don't hold the others, until this many are waiting.
+const PER_HOST_QUEUED: usize = 10_000;
+
 #[derive(Debug, Clone)]
 struct Throttler {
     throttle: Throttle,
@@ -146,60 +278,214 @@ struct Throttler {
 }
 
 impl Throttler {
-    pub fn new(throttle: Throttle) -> Self {
+    pub fn new(throttle: Throttle, burst: usize) -> Self {
         let limiter = match throttle {
             Throttle::Concurrent(_) => None,
-            Throttle::PerSecond(n) => Some(RateLimiter::with_limit(n.get())),
-            Throttle::Delay(delay) => Some(RateLimiter::with_delay(delay)),
+            Throttle::PerSecond(n) => Some(RateLimiter::with_limit(n.get(), burst)),
+            Throttle::Delay(delay) => Some(RateLimiter::with_delay(delay, burst)),
+            Throttle::PerHostPerSecond(n) => {
+                Some(RateLimiter::with_limit(n.get(), burst).per_host())
+            }
+            Throttle::PerHostDelay(delay) => Some(RateLimiter::with_delay(delay, burst).per_host()),
         };
         Self { throttle, limiter }
     }
 
+    /// Throttles a stream of downloads, each one paired with its URL.
     pub fn throttle<'a, S, F, T>(
         &self,
         stream: S,
     ) -> Pin<Box<dyn Stream<Item = Result<T, anyhow::Error>> + 'a>>
     where
-        S: Stream<Item = F> + 'a,
-        F: Future<Output = Result<T, anyhow::Error>>,
+        S: Stream<Item = (String, F)> + 'a,
+        F: Future<Output = Result<T, anyhow::Error>> + 'a,
     {
         match (self.throttle, &self.limiter) {
-            (Throttle::Concurrent(n), _) => stream.buffer_unordered(n.get()).boxed_local(),
-            (Throttle::PerSecond(_), Some(limiter)) => {
-                stream.rate_limited(limiter.clone()).boxed_local()
-            }
-            (Throttle::Delay(_), Some(limiter)) => {
-                stream.rate_limited(limiter.clone()).boxed_local()
-            }
+            (Throttle::Concurrent(n), _) => stream
+                .map(|(_, fut)| fut)
+                .buffer_unordered(n.get())
+                .boxed_local(),
+            (Throttle::PerSecond(_) | Throttle::Delay(_), Some(limiter)) => stream
+                .map(|(_, fut)| fut)
+                .rate_limited(limiter.clone())
+                .boxed_local(),
+            // Downloads wait in their own host's queue, so a throttled host doesn't hold others
+            (Throttle::PerHostPerSecond(_) | Throttle::PerHostDelay(_), Some(limiter)) => stream
+                .rate_limited_per_host(limiter.clone(), PER_HOST_QUEUED, PER_HOST_IN_FLIGHT)
+                .boxed_local(),
             _ => unreachable!(),
         }
     }
+
+    /// Waits for the throttle to allow one more request to `url`, concurrency limits only
+    /// apply to streams.
+    pub async fn wait(&self, url: &str) {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire_for(url).await;
+        }
+    }
 }
 
-async fn download(config: &CrawlerConfig, url: &str) -> Result<Page> {
-    let resp = HTTP_CLI
-        .get(url)
+pub(crate) async fn download(
+    config: &CrawlerConfig,
+    client: &reqwest::Client,
+    request: &PageRequest,
+) -> Result<Page> {
+    let PageRequest {
+        url,
+        method,
+        body,
+        content_type,
+        ..
+    } = request;
+
+    let mut req = client
+        .request(reqwest::Method::from_bytes(method.as_bytes())?, url)
         .header(USER_AGENT, &config.user_agent)
-        .send()
-        .await?;
-
-    let page = match resp.headers().get(CONTENT_TYPE) {
-        Some(c) if c == "application/x-gzip" || c == "application/gzip" => {
-            let compressed = resp.bytes().await?;
-            let mut gz = GzDecoder::new(&compressed[..]);
-            let mut page = String::new();
-            gz.read_to_string(&mut page)?;
-            page
+        .header(ACCEPT_ENCODING, decode::ACCEPT_ENCODING);
+    if let Some(content_type) = content_type {
+        req = req.header(CONTENT_TYPE, content_type);
+    }
+    if let Some(body) = body {
+        req = req.body(body.clone());
+    }
+
+    // Only plain GET requests are cached
+    let cache_dir = config
+        .cache_dir
+        .as_deref()
+        .filter(|_| method.eq_ignore_ascii_case("GET") && body.is_none());
+    let cached = match cache_dir {
+        Some(dir) => cache::load(dir, url).await,
+        None => None,
+    };
+    if let Some(cached) = &cached {
+        req = req.headers(cached.validators());
+    }
+
+    let start = Instant::now();
+    let resp = req.send().await?;
+
+    let content_length = resp.content_length();
+    let (status, headers, final_url, body) = match cached {
+        Some(cached) if resp.status() == StatusCode::NOT_MODIFIED => {
+            log::debug!("Using cached page {url}");
+            (cached.status, cached.headers, cached.final_url, cached.body)
+        }
+        _ => {
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let final_url = resp.url().to_string();
+            let body = resp.bytes().await?.to_vec();
+            if let Some(dir) = cache_dir {
+                let resp = CachedResponse {
+                    status,
+                    headers: headers.clone(),
+                    final_url: final_url.clone(),
+                    body: body.clone(),
+                };
+                cache::store(dir, url, resp);
+            }
+            (status, headers, final_url, body)
         }
-        _ => resp.text().await?,
     };
+    let latency = start.elapsed();
+    let body = decode::decompress_body(&headers, body)?;
+    let page = decode::decode_text(&body, decode::charset(&headers));
 
     Ok(Page {
         page,
+        fetch_info: FetchInfo {
+            status,
+            headers,
+            content_length,
+            latency,
+            final_url,
+            body: body.into(),
+        },
+        request: Arc::new(request.clone()),
         location: PageLocation::Url(url.to_string()),
+        robot: None,
     })
 }
 
+/// Fetches an auxiliary URL for a scraper, once allowed by its host's robots.txt and by the
+/// throttle.
+async fn fetch_auxiliary(
+    config: &CrawlerConfig,
+    client: &reqwest::Client,
+    throttler: &Throttler,
+    host_robots: &HostRobots,
+    url: &str,
+) -> Result<FetchedPage> {
+    let robot = host_robots.get(config, client, url).await?;
+    if robot.is_some_and(|r| !r.allowed(url)) {
+        anyhow::bail!("URL disallowed by robots.txt {url}");
+    }
+    throttler.wait(url).await;
+    let Page {
+        page, fetch_info, ..
+    } = download(config, client, &url.into())
+        .await
+        .inspect_err(|e| error::report(config, url, CrawlPhase::Download, e, 1))?;
+    Ok(FetchedPage { page, fetch_info })
+}
+
+/// Downloads a single page with the HTTP client and headers a crawl would use, for
+/// instance to check what the crawler actually receives.
+pub async fn fetch_page(config: &CrawlerConfig, url: &str) -> Result<FetchedPage> {
+    if let Some(dir) = &config.cache_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let client = client::build_client(config)?;
+    let Page {
+        page, fetch_info, ..
+    } = download(config, &client, &url.into()).await?;
+    Ok(FetchedPage { page, fetch_info })
+}
+
+/// Writes the downloaded `page` to `dir` in the background, it is only saved when it was
+/// downloaded from a URL.
+fn save_page(dir: &Path, request: &PageRequest, page: &Page) -> Option<JoinHandle<()>> {
+    let PageLocation::Url(_) = &page.location else {
+        return None;
+    };
+    let path = dir.join(page_file_name(request));
+    let content = page.page.clone();
+    Some(tokio::task::spawn_blocking(move || {
+        if let Err(e) = std::fs::write(&path, content) {
+            log::warn!("Couldn't save page to {} got: {e}", path.display());
+        }
+    }))
+}
+
+/// Builds a file name that is readable (derived from the URL) and unique (suffixed by
+/// a hash of the full URL, along with the method and body of the request).
+fn page_file_name(request: &PageRequest) -> PathBuf {
+    let url = &request.url;
+    let stem = url
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(url)
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+            _ => '_',
+        })
+        .take(100)
+        .collect::<String>();
+    let mut hasher = Sha256::new();
+    hasher.update(request.method.as_bytes());
+    hasher.update(b" ");
+    hasher.update(url.as_bytes());
+    if let Some(body) = &request.body {
+        hasher.update(b"\n");
+        hasher.update(body.as_bytes());
+    }
+    let hash = format!("{:x}", hasher.finalize());
+    PathBuf::from(format!("{stem}-{}.html", &hash[..16]))
+}
+
 fn until_err<T, E>(
     err: &mut &mut Result<(), E>,
     item: Result<T, E>,
@@ -213,6 +499,19 @@ fn until_err<T, E>(
     }
 }
 
+/// Runs `task` until it completes or the crawl is stopped.
+async fn until_stopped<F>(task: F, crawl_stop: &CrawlStop) -> Result<()>
+where
+    F: Future<Output = Result<()>>,
+{
+    let stopped = crawl_stop.stopped();
+    futures::pin_mut!(task, stopped);
+    match future::select(task, stopped).await {
+        future::Either::Left((res, _)) => res,
+        future::Either::Right(((), _)) => Ok(()),
+    }
+}
+
 pub async fn crawl_site<T>(
     crawler_conf: &CrawlerConfig,
     scraper_conf: &T::Config,
@@ -223,15 +522,67 @@ where
     // Initialize shared components
 
     let scraper = <T as Scrapable>::new(scraper_conf)?;
-    let seed = scraper.seed();
 
-    let (robot, throttle) = match (&seed, &crawler_conf.robot) {
-        (Seed::RobotsTxt(_), Some(_)) => anyhow::bail!(
+    let mut seed_sitemaps = vec![];
+    let mut seed_requests = vec![];
+    let mut seed_robots = None;
+    let mut seed_generators = vec![];
+    for seed in scraper.seed().flatten() {
+        match seed {
+            Seed::Sitemaps(urls) => seed_sitemaps.extend(urls),
+            Seed::Pages(urls) => seed_requests.extend(urls.into_iter().map(PageRequest::from)),
+            Seed::Requests(requests) => seed_requests.extend(requests),
+            Seed::RobotsTxt(url) => {
+                if seed_robots.replace(url).is_some() {
+                    anyhow::bail!("Invalid seed config, cannot use more than one Seed::RobotsTxt");
+                }
+            }
+            Seed::Generator(generator) => seed_generators.push(generator),
+            Seed::Many(_) => unreachable!(),
+        }
+    }
+
+    let client = client::build_client(crawler_conf)?;
+    let client = &client;
+
+    anyhow::ensure!(
+        crawler_conf.url_buffer > 0,
+        "Crawler url_buffer must be > 0"
+    );
+    anyhow::ensure!(
+        crawler_conf.throttle_burst > 0,
+        "Crawler throttle_burst must be > 0"
+    );
+    if let Some(scrap_timeout) = crawler_conf.scrap_timeout {
+        anyhow::ensure!(scrap_timeout > 0.0, "Crawler scrap_timeout must be > 0.0");
+    }
+    if let Some(max_pages) = crawler_conf.max_pages {
+        anyhow::ensure!(max_pages > 0, "Crawler max_pages must be > 0");
+    }
+    if let Some(max_duration) = crawler_conf.max_duration {
+        anyhow::ensure!(max_duration > 0.0, "Crawler max_duration must be > 0.0");
+    }
+    if let Some(timeout) = crawler_conf.http.timeout {
+        anyhow::ensure!(timeout > 0.0, "Crawler http.timeout must be > 0.0");
+    }
+
+    if let (Revisit::IfModified, None) = (crawler_conf.revisit, &crawler_conf.state_file) {
+        anyhow::bail!(
+            "Invalid revisit config, cannot use Revisit::IfModified when `crawler_conf.state_file` is not defined"
+        );
+    }
+    if crawler_conf.resume && crawler_conf.state_file.is_none() {
+        anyhow::bail!(
+            "Invalid resume config, cannot resume a crawl when `crawler_conf.state_file` is not defined"
+        );
+    }
+
+    let (robot, throttle) = match (&seed_robots, &crawler_conf.robot) {
+        (Some(_), Some(_)) => anyhow::bail!(
             "Invalid seed config, cannot use Seed::RobotsTxt when `crawler_conf.robot` is defined"
         ),
-        (Seed::RobotsTxt(url), None) | (_, Some(url)) => {
-            let robot = HTTP_CLI.get(url).send().await?.bytes().await?;
-            let robot = Robot::new(&crawler_conf.user_agent, &robot)?;
+        (Some(url), None) | (None, Some(url)) => {
+            let robot = robots::fetch_robot(crawler_conf, client, url).await?;
             let throttle = match (robot.delay, crawler_conf.throttle) {
                 (_, Some(throttle)) => throttle,
                 (Some(delay), None) => {
@@ -243,60 +594,233 @@ where
             let robot = Some(Arc::new(robot));
             (robot, throttle)
         }
-        _ => (None, crawler_conf.throttle.unwrap_or_default()),
+        (None, None) => (None, crawler_conf.throttle.unwrap_or_default()),
+    };
+    let throttler = Throttler::new(throttle, crawler_conf.throttle_burst);
+
+    if let Some(dir) = &crawler_conf.save_pages_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    if let Some(dir) = &crawler_conf.cache_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let filter = Arc::new(UrlFilter::new(&crawler_conf.url_filters)?);
+    let host_robots = crawler_conf
+        .robots_per_host
+        .then(|| Arc::new(HostRobots::default()));
+
+    let state = match &crawler_conf.state_file {
+        Some(path) => Some(Arc::new(StateStore::open(path)?)),
+        None => None,
+    };
+    // Pages scraped since the start of the resumed crawl are not downloaded again
+    let resumed = state.as_ref().and_then(|state| {
+        let resumed = crawler_conf
+            .resume
+            .then(|| state.unfinished_crawl())
+            .flatten();
+        state.start_crawl(resumed);
+        resumed
+    });
+    if crawler_conf.resume && resumed.is_none() {
+        log::info!("No unfinished crawl to resume, starting a new one");
+    }
+    // The requests the resumed crawl didn't scrap, found by pages that won't be scraped again
+    let resumed_requests = match (&state, resumed) {
+        (Some(state), Some(_)) => state.pending(),
+        _ => vec![],
     };
-    let throttler = Throttler::new(throttle);
 
     // Setup workers task
 
     let (tx_stop, rx_stop) = crossbeam_channel::unbounded::<()>();
-    let (tx_url, rx_url) = mpsc::unbounded_channel::<String>();
+    let (tx_url, rx_url) = mpsc::unbounded_channel::<Prioritized>();
     let (tx_page, rx_page) = crossbeam_channel::bounded::<Page>(crawler_conf.page_buffer);
 
     let failed = Arc::new(AtomicBool::new(false));
     let pages_in = Arc::new(AtomicUsize::new(0));
     let pages_out = Arc::new(AtomicUsize::new(0));
+    let pages_scraped = Arc::new(AtomicUsize::new(0));
+
+    let in_flight = InFlight::default();
+
+    // Scratch directories are removed once the crawl is over
+    let scratch = tempfile::Builder::new().prefix("sws-").tempdir()?;
+    let scratch_dir: Arc<Path> = scratch.path().into();
+
+    // With a shared frontier, pages are counted when popped as they may be fetched by
+    // other crawlers, and requests are queued in the shared backend instead
+    let shared = frontier::shared_backend(&crawler_conf.frontier)?;
+    let tx_url = match shared {
+        Some(_) => {
+            let queue = UrlQueue::new(tx_url, None, &scratch_dir);
+            CountedTx::new(queue, Arc::default(), in_flight.clone(), None, crawler_conf)
+        }
+        None => {
+            let queue = UrlQueue::new(tx_url, Some(crawler_conf.url_buffer), &scratch_dir);
+            CountedTx::new(
+                queue,
+                pages_in.clone(),
+                in_flight.clone(),
+                state.clone(),
+                crawler_conf,
+            )
+        }
+    };
+    let release_url = tx_url.releaser();
+    let last_pop = Arc::new(Mutex::new(Instant::now()));
 
-    let tx_url = CountedTx::new(tx_url, pages_in.clone());
+    let crawl_stop = CrawlStop::new();
+
+    let (tx_fetch, mut rx_fetch) = mpsc::unbounded_channel::<FetchRequest>();
+    let fetcher = Fetcher::new(tx_fetch);
 
     let mut workers = vec![];
     for id in 0..crawler_conf.num_workers {
+        let scratch_dir = scratch_dir.clone();
+        let worker_scratch_dir: Arc<Path> = scratch_dir.join(format!("worker-{id}")).into();
+        std::fs::create_dir(&worker_scratch_dir)?;
         let rx_stop = rx_stop.clone();
         let rx_page = rx_page.clone();
         let tx_url = tx_url.clone();
         let robot = robot.clone();
         let pages_out = pages_out.clone();
+        let pages_scraped = pages_scraped.clone();
         let scraper_conf = scraper_conf.clone();
         let crawler_conf = crawler_conf.clone();
         let failed = failed.clone();
+        let state = state.clone();
+        let crawl_stop = crawl_stop.clone();
+        let fetcher = fetcher.clone();
         let worker = thread::Builder::new()
             .name(format!("{id}"))
             .spawn(move || {
                 let mut scraper = <T as Scrapable>::new(&scraper_conf)?;
+                let worker_ctx = ScrapingContext {
+                    tx_url: Some(tx_url.clone()),
+                    robot: robot.clone(),
+                    scratch_dir: Some(scratch_dir.clone()),
+                    worker_scratch_dir: Some(worker_scratch_dir.clone()),
+                    crawl_stop: Some(crawl_stop.clone()),
+                    fetcher: Some(fetcher.clone()),
+                    ..Default::default()
+                };
+                if let Err(e) = scraper.init(worker_ctx.clone()) {
+                    failed.store(true, Ordering::SeqCst);
+                    return Err(e);
+                }
                 loop {
                     crossbeam_channel::select! {
                         recv(rx_page) -> page => {
-                            if failed.load(Ordering::Relaxed) {
+                            if failed.load(Ordering::Relaxed) || crawl_stop.reason().is_some() {
                                 break;
                             }
-                            if let Ok(Page { page, location }) = page {
+                            if let Ok(Page { page, location, robot: page_robot, fetch_info, request }) = page {
+                                let tracked = match (&state, &location) {
+                                    (Some(state), PageLocation::Url(url)) => {
+                                        Some((state, url.clone(), content_hash(&page)))
+                                    }
+                                    _ => None,
+                                };
+                                if let Some((state, url, hash)) = &tracked {
+                                    let unchanged = state
+                                        .get(url)
+                                        .map(|prev| &prev.hash == hash)
+                                        .unwrap_or(false);
+                                    if crawler_conf.skip_unchanged && unchanged {
+                                        log::debug!("Skipping unchanged page {url}");
+                                        state.remove_pending(&request);
+                                        pages_out.fetch_add(1, Ordering::SeqCst);
+                                        continue;
+                                    }
+                                }
+                                // The crawl stops once the last page of the budget is taken
+                                if let Some(max_pages) = crawler_conf.max_pages {
+                                    let taken = pages_scraped.fetch_add(1, Ordering::SeqCst);
+                                    if taken + 1 >= max_pages {
+                                        crawl_stop.stop(&format!("Reached max_pages ({max_pages})"));
+                                    }
+                                    if taken >= max_pages {
+                                        pages_out.fetch_add(1, Ordering::SeqCst);
+                                        continue;
+                                    }
+                                }
                                 let location = Rc::new(location);
-                                let ctx = ScrapingContext::new (
-                                    location.clone(),
-                                    Some(tx_url.clone()),
-                                    robot.clone()
-                                );
-                                match scraper.scrap(page, ctx) {
-                                    Ok(()) => (),
-                                    Err(e) => match crawler_conf.on_scrap_error {
-                                        OnError::SkipAndLog => {
-                                            log::error!("Skipping scrap for page {location:?} got: {e}");
+                                let timeout = crawler_conf.scrap_timeout.map(Duration::from_secs_f32);
+                                let deadline = timeout.map(|timeout| Instant::now() + timeout);
+                                let ctx = ScrapingContext {
+                                    location: Some(location.clone()),
+                                    tx_url: Some(tx_url.referred_by(&request)),
+                                    robot: page_robot.or_else(|| robot.clone()),
+                                    fetch_info: Some(Arc::new(fetch_info)),
+                                    request: Some(request.clone()),
+                                    deadline,
+                                    ..worker_ctx.with_own_outcome()
+                                };
+                                let outcome = ctx.outcome();
+                                // Only Lua scrapers are interrupted at their deadline, others
+                                // are checked once they return (a hung scraper blocks a worker)
+                                let res = scraper.scrap(page, ctx).and_then(|()| match deadline {
+                                    Some(deadline) if Instant::now() > deadline => {
+                                        Err(anyhow!("Scrap timed out after {timeout:?}"))
+                                    }
+                                    _ => Ok(()),
+                                });
+                                // Whether the page is done, as opposed to requeued
+                                let res = res.and_then(|()| match outcome.take() {
+                                    Some(PageOutcome::Retry(reason))
+                                        if request.retries < crawler_conf.max_scrap_retries =>
+                                    {
+                                        log::info!("Retrying page {location:?}: {reason}");
+                                        tx_url.send(PageRequest {
+                                            retries: request.retries + 1,
+                                            ..PageRequest::clone(&request)
+                                        });
+                                        Ok(false)
+                                    }
+                                    Some(PageOutcome::Retry(reason)) => Err(anyhow!(
+                                        "Giving up page after {} retries: {reason}",
+                                        request.retries
+                                    )),
+                                    Some(PageOutcome::Skip(reason)) => {
+                                        log::info!("Skipped page {location:?}: {reason}");
+                                        Ok(true)
+                                    }
+                                    None => Ok(true),
+                                });
+                                // Requeued pages stay pending until their last attempt, and
+                                // failing ones so that the failed crawl can be resumed
+                                if let Some(state) = &state {
+                                    let skipped = matches!(crawler_conf.on_scrap_error, OnError::SkipAndLog);
+                                    if matches!(res, Ok(true)) || (res.is_err() && skipped) {
+                                        state.remove_pending(&request);
+                                    }
+                                }
+                                match res {
+                                    Ok(done) => {
+                                        // Retried pages are only scraped once done
+                                        if let (true, PageLocation::Url(url)) = (done, location.as_ref()) {
+                                            event::emit(&crawler_conf, CrawlEvent::Scraped(url));
+                                        }
+                                        if let (true, Some((state, url, hash))) = (done, tracked) {
+                                            state.record(&url, hash);
                                         }
-                                        OnError::Fail => {
-                                            failed.store(true, Ordering::SeqCst);
-                                            return Err(e);
+                                    }
+                                    Err(e) => {
+                                        if let PageLocation::Url(url) = location.as_ref() {
+                                            error::report(&crawler_conf, url, CrawlPhase::Scrap, &e, request.attempt());
                                         }
-                                    },
+                                        match crawler_conf.on_scrap_error {
+                                            OnError::SkipAndLog => {
+                                                log::error!("Skipping scrap for page {location:?} got: {e}");
+                                            }
+                                            OnError::Fail => {
+                                                failed.store(true, Ordering::SeqCst);
+                                                return Err(e);
+                                            }
+                                        }
+                                    }
                                 }
                                 pages_out.fetch_add(1, Ordering::SeqCst);
                             } else {
@@ -306,85 +830,220 @@ where
                         recv(rx_stop) -> _ => break
                     }
                 }
-                Ok::<(), Error>(())
+                scraper.finish(worker_ctx)
             })?;
         workers.push(worker);
     }
+    // Auxiliary fetches are served until all the workers stopped
+    drop(fetcher);
     let workers = async move {
         tokio::task::spawn_blocking(|| {
+            let mut results = vec![];
             for w in workers {
-                w.join().unwrap()?;
+                results.extend(w.join().unwrap()?);
             }
-            Ok::<(), Error>(())
+            Ok::<_, Error>(results)
         })
         .await?
     };
 
     // Setup crawler task
 
-    let throttler_c = throttler.clone();
+    let gathering = Gathering {
+        tx_url: tx_url.clone(),
+        throttler: throttler.clone(),
+        robot: robot.clone(),
+        state: state.clone(),
+        filter: filter.clone(),
+        host_robots: host_robots.clone(),
+        depth: 0,
+    };
 
     let crawler_done = Arc::new(AtomicBool::new(false));
     let crawler_done_c = crawler_done.clone();
 
-    let crawler: Pin<Box<dyn Future<Output = Result<()>>>> = match seed {
-        Seed::Sitemaps(urls) => Box::pin(async move {
-            for sm_url in urls {
-                gather_urls(
-                    crawler_conf,
-                    &scraper,
-                    &sm_url,
-                    tx_url.clone(),
-                    throttler_c.clone(),
-                    robot.clone(),
-                )
-                .await?;
+    let crawl_stop_c = crawl_stop.clone();
+    let crawler = async move {
+        for (request, priority) in resumed_requests {
+            tx_url.send_when_ready(request, priority).await;
+        }
+        for request in seed_requests {
+            tx_url.send(request);
+        }
+        for generator in seed_generators {
+            for page_url in generator {
+                // Doesn't yield until the queue is full, so it must check by itself
+                if crawl_stop_c.reason().is_some() {
+                    break;
+                }
+                tx_url.send_when_ready(page_url, 0).await;
             }
-            crawler_done_c.store(true, Ordering::SeqCst);
-            drop(tx_url);
-            Ok(())
-        }),
-        Seed::RobotsTxt(_) => Box::pin(async move {
-            if let Some(r) = &robot {
-                let crawling_ctx = CrawlingContext::new(Sitemap::Index, robot.clone());
-                for sm_url in &r.sitemaps {
-                    if scraper.accept(sm_url, crawling_ctx.clone()) {
-                        gather_urls(
-                            crawler_conf,
-                            &scraper,
-                            sm_url,
-                            tx_url.clone(),
-                            throttler_c.clone(),
-                            robot.clone(),
-                        )
-                        .await?;
-                    }
+        }
+        for sm_url in seed_sitemaps {
+            gather_urls(crawler_conf, client, &scraper, &sm_url, gathering.clone()).await?;
+        }
+        if let (Some(_), Some(r)) = (&seed_robots, &robot) {
+            let crawling_ctx =
+                CrawlingContext::new(Sitemap::Index, robot.clone(), seed_robots.clone(), 0, None);
+            for sm_url in &r.sitemaps {
+                if scraper.accept(sm_url, crawling_ctx.clone()) {
+                    gather_urls(crawler_conf, client, &scraper, sm_url, gathering.clone()).await?;
                 }
             }
-            Ok(())
-        }),
-        Seed::Pages(urls) => {
-            urls.into_iter().for_each(|page_url| {
-                tx_url.send(page_url);
-            });
-            crawler_done_c.store(true, Ordering::SeqCst);
-            drop(tx_url);
-            Box::pin(async move { Ok(()) })
         }
+        crawler_done_c.store(true, Ordering::SeqCst);
+        drop(gathering);
+        drop(tx_url);
+        Ok(())
+    };
+
+    // Setup auxiliary fetches task, robots.txt are always honored for them
+
+    let fetcher_throttler = throttler.clone();
+    let fetcher_robots = host_robots.clone().unwrap_or_default();
+    let fetcher = async move {
+        stream::poll_fn(|cx| rx_fetch.poll_recv(cx))
+            .for_each_concurrent(None, |(url, tx): FetchRequest| {
+                let throttler = &fetcher_throttler;
+                let host_robots = &fetcher_robots;
+                async move {
+                    let page =
+                        fetch_auxiliary(crawler_conf, client, throttler, host_robots, &url).await;
+                    tx.send(page).ok();
+                }
+            })
+            .await;
+        Ok::<_, Error>(())
     };
 
     // Setup downloader task
 
     let pages_in_c = pages_in.clone();
+    let requests: Pin<Box<dyn Stream<Item = PageRequest>>> = match shared {
+        Some(backend) => {
+            let pages_in = pages_in.clone();
+            let last_pop = last_pop.clone();
+            let on_pop = move || {
+                pages_in.fetch_add(1, Ordering::SeqCst);
+                *last_pop.lock().unwrap() = Instant::now();
+            };
+            frontier::shared_stream(backend, rx_url, on_pop).boxed_local()
+        }
+        None => {
+            let release = release_url;
+            Frontier::new(rx_url)
+                .inspect(move |_| release())
+                .boxed_local()
+        }
+    };
+    let idle_timeout = match crawler_conf.frontier {
+        FrontierConfig {
+            backend: None,
+            redis_url: None,
+            ..
+        } => None,
+        FrontierConfig { idle_timeout, .. } => Some(idle_timeout.unwrap_or(10.)),
+    };
+
+    // Errors that must stop the crawl but are found where they can't be propagated
+    let fatal = Arc::new(Mutex::new(None::<Error>));
+    let fatal_c = fatal.clone();
+
+    let downloader_state = state.clone();
+    // The pages being saved, awaited before returning
+    let page_saves = Arc::new(Mutex::new(Vec::<JoinHandle<()>>::new()));
+    let page_saves_c = page_saves.clone();
 
     let downloader = async move {
-        let stream = UnboundedReceiverStream::new(rx_url)
+        let stream = requests
             .zip(stream::repeat_with(move || pages_in_c.clone()))
-            .map(|(url, pages_in)| async move {
-                download(crawler_conf, &url).await.map_err(|e| {
-                    pages_in.fetch_sub(1, Ordering::SeqCst);
-                    e
-                })
+            .filter_map(|(request, pages_in)| {
+                let host_robots = host_robots.clone();
+                let fatal = fatal_c.clone();
+                let state = downloader_state.clone();
+                async move {
+                    // Dropped requests are no longer pending
+                    let drop_request = |request: &PageRequest| {
+                        if let Some(state) = &state {
+                            state.remove_pending(request);
+                        }
+                        pages_in.fetch_sub(1, Ordering::SeqCst);
+                    };
+                    if let (Some(since), Some(state)) = (resumed, &state) {
+                        let scraped = state.get(&request.url);
+                        if scraped.is_some_and(|page| page.scraped_at >= since) {
+                            log::debug!(
+                                "Skipping URL scraped by the resumed crawl {}",
+                                request.url
+                            );
+                            drop_request(&request);
+                            return None;
+                        }
+                    }
+                    let robot = match host_robots {
+                        Some(host_robots) => {
+                            match host_robots.get(crawler_conf, client, &request.url).await {
+                                Ok(robot) => robot,
+                                Err(e) => {
+                                    pages_in.fetch_sub(1, Ordering::SeqCst);
+                                    fatal.lock().unwrap().get_or_insert(e);
+                                    return None;
+                                }
+                            }
+                        }
+                        None => None,
+                    };
+                    if robot.as_ref().is_some_and(|r| !r.allowed(&request.url)) {
+                        log::debug!("Skipping URL disallowed by robots.txt {}", request.url);
+                        drop_request(&request);
+                        return None;
+                    }
+                    // Planned pages stay in flight so that each URL is only reported once
+                    if crawler_conf.dry_run {
+                        event::emit(crawler_conf, CrawlEvent::Planned(&request.url));
+                        drop_request(&request);
+                        return None;
+                    }
+                    Some((request, robot, pages_in))
+                }
+            })
+            .map(|(request, robot, pages_in)| {
+                let in_flight = in_flight.clone();
+                let state = downloader_state.clone();
+                let page_saves = page_saves_c.clone();
+                let url = request.url.clone();
+                let fut = async move {
+                    let page = download(crawler_conf, client, &request).await;
+                    in_flight.lock().unwrap().remove(&request);
+                    let mut page = page.inspect_err(|e| {
+                        error::report(
+                            crawler_conf,
+                            &request.url,
+                            CrawlPhase::Download,
+                            e,
+                            request.attempt(),
+                        );
+                        if let (Some(state), OnError::SkipAndLog) =
+                            (&state, crawler_conf.on_dl_error)
+                        {
+                            state.remove_pending(&request);
+                        }
+                        pages_in.fetch_sub(1, Ordering::SeqCst);
+                    })?;
+                    page.robot = robot;
+                    event::emit(crawler_conf, CrawlEvent::Downloaded(&request.url));
+                    let save = crawler_conf
+                        .save_pages_dir
+                        .as_ref()
+                        .and_then(|dir| save_page(dir, &request, &page));
+                    if let Some(save) = save {
+                        let mut saves = page_saves.lock().unwrap();
+                        saves.retain(|save| !save.is_finished());
+                        saves.push(save);
+                    }
+                    Ok(page)
+                };
+                (url, fut)
             });
         let stream = throttler.throttle(stream);
 
@@ -412,15 +1071,60 @@ where
         }
     };
 
+    // Once the crawl is stopped, URLs are no longer gathered nor downloaded
+    let crawler = until_stopped(crawler, &crawl_stop);
+    let downloader = until_stopped(downloader, &crawl_stop);
+
     // Run all tasks
 
+    let crawl_stop_c = crawl_stop.clone();
+    let state_c = state.clone();
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupted_c = interrupted.clone();
     let done = Box::pin(async move {
+        let started = Instant::now();
+        let mut ticks = 0u32;
         loop {
             match timeout(Duration::from_secs(1), tokio::signal::ctrl_c()).await {
-                Ok(_) => return Err(anyhow!("Interrupted")),
+                Ok(_) => {
+                    // The pages being scraped are finished, as for a stopped crawl
+                    log::warn!("Interrupted, finishing the pages being scraped");
+                    interrupted_c.store(true, Ordering::SeqCst);
+                    crawl_stop_c.stop("Interrupted");
+                    for _ in 0..crawler_conf.num_workers {
+                        tx_stop.send(()).ok();
+                    }
+                    return Ok(());
+                }
                 Err(_) => {
+                    // The state is saved regularly so that a killed crawl can be resumed
+                    ticks = ticks.wrapping_add(1);
+                    if let (Some(state), 0) = (&state_c, ticks % STATE_SAVE_PERIOD) {
+                        if let Err(e) = state.save() {
+                            log::error!("Couldn't save crawl state: {e}");
+                        }
+                    }
+                    if let Some(e) = fatal.lock().unwrap().take() {
+                        return Err(e);
+                    }
+                    if let Some(max_duration) = crawler_conf.max_duration {
+                        if started.elapsed().as_secs_f32() >= max_duration {
+                            crawl_stop_c.stop(&format!("Reached max_duration ({max_duration}s)"));
+                        }
+                    }
+                    if let Some(reason) = crawl_stop_c.reason() {
+                        log::info!("Crawl stopped: {reason}");
+                        for _ in 0..crawler_conf.num_workers {
+                            tx_stop.send(()).ok();
+                        }
+                        return Ok(());
+                    }
+                    let idle = idle_timeout.is_none_or(|idle_timeout| {
+                        last_pop.lock().unwrap().elapsed().as_secs_f32() >= idle_timeout
+                    });
                     if pages_out.load(Ordering::SeqCst) == pages_in.load(Ordering::SeqCst)
                         && crawler_done.load(Ordering::SeqCst)
+                        && idle
                     {
                         for _ in 0..crawler_conf.num_workers {
                             tx_stop.send(()).ok();
@@ -433,9 +1137,29 @@ where
     });
 
     let mut scraper = <T as Scrapable>::new(scraper_conf)?;
-    let res = try_join!(workers, downloader, crawler, done);
+    let res = try_join!(workers, downloader, crawler, done, fetcher).and_then(|(results, ..)| {
+        let ctx = ScrapingContext {
+            scratch_dir: Some(scratch_dir.clone()),
+            ..Default::default()
+        };
+        scraper.finalize(results, ctx)
+    });
     scraper.finalizer();
+    let saves = std::mem::take(&mut *page_saves.lock().unwrap());
+    future::join_all(saves).await;
+    // An interrupted crawl stays unfinished, so that it can be resumed
+    let interrupted = interrupted.load(Ordering::SeqCst);
+    if let Some(state) = state {
+        if res.is_ok() && !interrupted {
+            state.finish_crawl();
+        }
+        state.save()?;
+    }
+    scratch.close()?;
     res?;
 
+    if interrupted {
+        return Err(Interrupted.into());
+    }
     Ok(())
 }
@@ -0,0 +1,165 @@
+use std::io::prelude::*;
+
+use anyhow::{bail, Result};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use reqwest::header::{HeaderMap, CONTENT_ENCODING, CONTENT_TYPE};
+
+/// The `Accept-Encoding` header value matching what [`decompress_body`] supports.
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, deflate, br, zstd";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decompresses a response body accordingly to its `Content-Encoding`.
+pub(crate) fn decompress_body(headers: &HeaderMap, mut body: Vec<u8>) -> Result<Vec<u8>> {
+    if let Some(encodings) = headers
+        .get(CONTENT_ENCODING)
+        .and_then(|enc| enc.to_str().ok())
+    {
+        for encoding in encodings.rsplit(',').map(str::trim) {
+            body = decompress(encoding, &body)?;
+        }
+    }
+    Ok(body)
+}
+
+/// Decompresses a sitemap served without `Content-Encoding` that is still compressed. This
+/// happens for compressed files such as `.xml.gz` sitemaps, that are often served with a
+/// wrong (or without) `Content-Type`.
+///
+/// Returns `None` when the body isn't compressed, or when it can't be decompressed (it is
+/// then kept as is).
+pub(crate) fn decompress_sitemap(headers: &HeaderMap, body: &[u8]) -> Option<Vec<u8>> {
+    if headers.contains_key(CONTENT_ENCODING) {
+        return None;
+    }
+    let encoding = if body.starts_with(GZIP_MAGIC) {
+        "gzip"
+    } else if body.starts_with(ZSTD_MAGIC) {
+        "zstd"
+    } else {
+        return None;
+    };
+    decompress(encoding, body)
+        .inspect_err(|e| log::debug!("Couldn't decompress {encoding} sitemap got: {e}"))
+        .ok()
+}
+
+/// The charset of the `Content-Type` header, if any.
+pub(crate) fn charset(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .and_then(|ct| {
+            ct.split(';')
+                .find_map(|p| p.trim().strip_prefix("charset="))
+        })
+        .map(|charset| charset.trim_matches('"'))
+}
+
+/// Decodes `bytes` with the given `charset` label, falling back to UTF-8 for unknown
+/// labels. Malformed sequences are replaced by `U+FFFD`.
+pub(crate) fn decode_text(bytes: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, _, _) = encoding.decode(bytes);
+    text.into_owned()
+}
+
+fn decompress(encoding: &str, body: &[u8]) -> Result<Vec<u8>> {
+    let mut out = vec![];
+    match encoding.to_ascii_lowercase().as_str() {
+        "" | "identity" => return Ok(body.to_vec()),
+        "gzip" | "x-gzip" => {
+            GzDecoder::new(body).read_to_end(&mut out)?;
+        }
+        "deflate" => {
+            // Some servers send raw deflate streams instead of zlib wrapped ones
+            if ZlibDecoder::new(body).read_to_end(&mut out).is_err() {
+                out.clear();
+                flate2::read::DeflateDecoder::new(body).read_to_end(&mut out)?;
+            }
+        }
+        "br" => {
+            brotli_decompressor::Decompressor::new(body, 4096).read_to_end(&mut out)?;
+        }
+        "zstd" => {
+            zstd::stream::read::Decoder::new(body)?.read_to_end(&mut out)?;
+        }
+        other => bail!("Unsupported content encoding: {other}"),
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::prelude::*;
+
+    use flate2::write::GzEncoder;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+
+    use super::{charset, decode_text, decompress_body, decompress_sitemap};
+
+    /// Decompresses then decodes `body` as the crawler does.
+    fn decode_body(headers: &HeaderMap, body: Vec<u8>) -> anyhow::Result<String> {
+        let body = decompress_body(headers, body)?;
+        Ok(decode_text(&body, charset(headers)))
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut enc = GzEncoder::new(vec![], flate2::Compression::default());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    #[test]
+    fn decompress_compressed_sitemap() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        let body = decompress_sitemap(&headers, &gzip(b"<urlset></urlset>")).unwrap();
+        assert_eq!(body, b"<urlset></urlset>");
+
+        // Bodies that only look compressed are kept as is
+        assert_eq!(
+            decompress_sitemap(&headers, b"\x1f\x8b<urlset></urlset>"),
+            None
+        );
+        assert_eq!(decompress_sitemap(&headers, b"<urlset></urlset>"), None);
+
+        // Bodies whose Content-Encoding is handled aren't decompressed again
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        let body = decompress_body(&headers, gzip(&gzip(b"<urlset></urlset>"))).unwrap();
+        assert_eq!(decompress_sitemap(&headers, &body), None);
+    }
+
+    #[test]
+    fn decode_content_encodings() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("zstd"));
+        let zstd = zstd::stream::encode_all(&b"<p>zstd</p>"[..], 0).unwrap();
+        assert_eq!(decode_body(&headers, zstd).unwrap(), "<p>zstd</p>");
+
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip, identity"));
+        assert_eq!(
+            decode_body(&headers, gzip(b"<p>gz</p>")).unwrap(),
+            "<p>gz</p>"
+        );
+    }
+
+    #[test]
+    fn decode_charset() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=ISO-8859-1"),
+        );
+        assert_eq!(
+            decode_body(&headers, vec![0x63, 0x61, 0x66, 0xe9]).unwrap(),
+            "café"
+        );
+    }
+}
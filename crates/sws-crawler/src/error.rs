@@ -0,0 +1,69 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::CrawlerConfig;
+
+/// An error that occurred while crawling, reported to [`CrawlerConfig::on_error`].
+#[derive(Debug)]
+pub struct CrawlError<'a> {
+    /// The URL of the page, sitemap or robots.txt being processed
+    pub url: &'a str,
+    pub phase: CrawlPhase,
+    pub source: &'a anyhow::Error,
+    /// The attempt number (starting from 1) of the failed operation
+    pub attempt: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlPhase {
+    /// Downloading a page or a sitemap
+    Download,
+    /// Processing a XML sitemap
+    Sitemap,
+    /// Retrieving a robots.txt
+    Robots,
+    /// Scraping a page
+    Scrap,
+}
+
+/// The error of a crawl interrupted with Ctrl-C, returned once the pages being scraped
+/// were finished and the crawl state saved.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// A callback invoked for each crawling error, whether it is skipped or not.
+#[derive(Clone)]
+pub struct ErrorObserver(pub Arc<dyn Fn(&CrawlError) + Send + Sync>);
+
+impl fmt::Debug for ErrorObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ErrorObserver").finish()
+    }
+}
+
+/// Reports an error to the observer, `attempt` starting from 1 (pages are fetched and
+/// scraped again when their scraper asks for a retry).
+pub(crate) fn report(
+    config: &CrawlerConfig,
+    url: &str,
+    phase: CrawlPhase,
+    source: &anyhow::Error,
+    attempt: u32,
+) {
+    if let Some(observer) = &config.on_error {
+        (observer.0)(&CrawlError {
+            url,
+            phase,
+            source,
+            attempt,
+        });
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::CrawlerConfig;
+
+/// A step of the crawl of a page, reported to [`CrawlerConfig::on_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlEvent<'a> {
+    /// The URL of a page was queued to be downloaded
+    Discovered(&'a str),
+    /// A page was downloaded
+    Downloaded(&'a str),
+    /// A page was successfully scraped
+    Scraped(&'a str),
+    /// A page would have been downloaded and scraped, when crawling with `dry_run`
+    Planned(&'a str),
+}
+
+/// A callback invoked for each step of the crawl, for instance to display its progress.
+#[derive(Clone)]
+pub struct EventObserver(pub Arc<dyn Fn(CrawlEvent) + Send + Sync>);
+
+impl fmt::Debug for EventObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EventObserver").finish()
+    }
+}
+
+impl EventObserver {
+    pub(crate) fn emit(&self, event: CrawlEvent) {
+        (self.0)(event)
+    }
+}
+
+pub(crate) fn emit(config: &CrawlerConfig, event: CrawlEvent) {
+    if let Some(observer) = &config.on_event {
+        observer.emit(event);
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use regex::RegexSet;
+
+use crate::config::UrlFilters;
+
+/// Compiled [`UrlFilters`], evaluated before [`Scrapable::accept`](crate::Scrapable::accept).
+#[derive(Debug, Clone)]
+pub(crate) struct UrlFilter {
+    accept: Option<RegexSet>,
+    deny: Option<RegexSet>,
+}
+
+impl UrlFilter {
+    pub(crate) fn new(filters: &UrlFilters) -> Result<Self> {
+        let accept = patterns(&filters.accept, &filters.accept_globs);
+        let deny = patterns(&filters.deny, &filters.deny_globs);
+        Ok(Self {
+            accept: (!accept.is_empty())
+                .then(|| RegexSet::new(accept))
+                .transpose()?,
+            deny: (!deny.is_empty())
+                .then(|| RegexSet::new(deny))
+                .transpose()?,
+        })
+    }
+
+    /// Whether `url` isn't denied and, when accept patterns are set, matches one of them.
+    pub(crate) fn allows(&self, url: &str) -> bool {
+        !self.deny.as_ref().is_some_and(|deny| deny.is_match(url))
+            && self
+                .accept
+                .as_ref()
+                .is_none_or(|accept| accept.is_match(url))
+    }
+}
+
+fn patterns(regexes: &[String], globs: &[String]) -> Vec<String> {
+    regexes
+        .iter()
+        .cloned()
+        .chain(globs.iter().map(|glob| glob_to_regex(glob)))
+        .collect()
+}
+
+/// Translates a glob matching the whole URL to a regex, `*` matches any characters but
+/// `/`, `**` matches any characters and `?` matches a single character but `/`.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrlFilter;
+    use crate::config::UrlFilters;
+
+    #[test]
+    fn filter_urls() {
+        let filter = UrlFilter::new(&UrlFilters {
+            accept_globs: vec!["https://www.example.com/**".into()],
+            deny: vec!["/tag/".into()],
+            deny_globs: vec!["*://*/*.pdf".into()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(filter.allows("https://www.example.com/word/foo"));
+        assert!(!filter.allows("https://www.example.com/tag/foo"));
+        assert!(!filter.allows("https://www.example.com/doc.pdf"));
+        assert!(filter.allows("https://www.example.com/docs/doc.pdf"));
+        assert!(!filter.allows("https://www.example.org/word/foo"));
+    }
+
+    #[test]
+    fn filter_nothing() {
+        let filter = UrlFilter::new(&UrlFilters::default()).unwrap();
+        assert!(filter.allows("https://www.example.com/"));
+    }
+}
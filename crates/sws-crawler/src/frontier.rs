@@ -0,0 +1,443 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+
+use crate::config::FrontierConfig;
+use crate::scrapable::PageRequest;
+
+/// A URL frontier that can be shared by multiple crawlers, possibly running on different
+/// machines, so that they cooperatively crawl a site.
+pub trait FrontierBackend: Send + Sync {
+    /// Adds a request to the frontier, requests that were already added may be ignored.
+    fn push(&self, request: PageRequest, priority: i32) -> anyhow::Result<()>;
+
+    /// Takes the highest priority request, `None` when the frontier is currently empty.
+    fn pop(&self) -> anyhow::Result<Option<PageRequest>>;
+}
+
+/// A page request along with its crawling priority, higher priorities are fetched first.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Prioritized {
+    pub(crate) request: PageRequest,
+    pub(crate) priority: i32,
+}
+
+#[derive(Debug)]
+struct Entry {
+    priority: i32,
+    seq: u64,
+    request: PageRequest,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Same priorities are fetched in the order they were received
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// A stream of page requests ordered by priority.
+///
+/// Every ready request of the underlying channel is pulled on each poll, so that the
+/// highest priority one among those received so far is always yielded first.
+pub(crate) struct Frontier {
+    rx: mpsc::UnboundedReceiver<Prioritized>,
+    heap: BinaryHeap<Entry>,
+    seq: u64,
+    closed: bool,
+}
+
+impl Frontier {
+    pub(crate) fn new(rx: mpsc::UnboundedReceiver<Prioritized>) -> Self {
+        Self {
+            rx,
+            heap: BinaryHeap::new(),
+            seq: 0,
+            closed: false,
+        }
+    }
+}
+
+impl Stream for Frontier {
+    type Item = PageRequest;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        while !self.closed {
+            match self.rx.poll_recv(cx) {
+                Poll::Ready(Some(Prioritized { request, priority })) => {
+                    let seq = self.seq;
+                    self.seq += 1;
+                    self.heap.push(Entry {
+                        priority,
+                        seq,
+                        request,
+                    });
+                }
+                Poll::Ready(None) => self.closed = true,
+                Poll::Pending => break,
+            }
+        }
+
+        match self.heap.pop() {
+            Some(entry) => Poll::Ready(Some(entry.request)),
+            None if self.closed => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// The sending half of the frontier, holding at most `limit` queued requests (taken
+/// from the frontier once their download starts).
+///
+/// Senders that can wait for room are given a permit once there is some, requests sent
+/// while it is full by the others (such as scrapers) are spilled to a file, and queued
+/// back in the order they were sent as soon as there is room again.
+#[derive(Debug, Clone)]
+pub(crate) struct UrlQueue {
+    tx: mpsc::UnboundedSender<Prioritized>,
+    permits: Option<Arc<Semaphore>>,
+    spill: Arc<Mutex<Spill>>,
+}
+
+impl UrlQueue {
+    /// A queue of at most `limit` requests (unbounded if `None`), spilling requests to a
+    /// file in `spill_dir`.
+    pub(crate) fn new(
+        tx: mpsc::UnboundedSender<Prioritized>,
+        limit: Option<usize>,
+        spill_dir: &Path,
+    ) -> Self {
+        Self {
+            tx,
+            permits: limit.map(|limit| Arc::new(Semaphore::new(limit))),
+            spill: Arc::new(Mutex::new(Spill::new(spill_dir.join("spilled-urls.jsonl")))),
+        }
+    }
+
+    /// Waits until there is room for one more request, the returned permit is then used
+    /// by [`push`](Self::push).
+    pub(crate) async fn reserve(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.permits {
+            Some(permits) => permits.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+
+    /// Queues a request with a permit from [`reserve`](Self::reserve), or with one taken
+    /// right away if there is room, otherwise it is spilled. The request is returned if
+    /// the frontier is closed.
+    pub(crate) fn push(
+        &self,
+        item: Prioritized,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> Result<(), Box<Prioritized>> {
+        let Some(permits) = &self.permits else {
+            return self.tx.send(item).map_err(|e| Box::new(e.0));
+        };
+        match permit.or_else(|| permits.clone().try_acquire_owned().ok()) {
+            Some(permit) => {
+                // Given back when the request is taken from the frontier
+                permit.forget();
+                self.tx.send(item).map_err(|e| Box::new(e.0))
+            }
+            None => match self.spill.lock().unwrap().push(&item) {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    log::error!(
+                        "Couldn't spill request {}, queuing it: {e}",
+                        item.request.url
+                    );
+                    self.tx.send(item).map_err(|e| Box::new(e.0))
+                }
+            },
+        }
+    }
+
+    /// A function making room for one more request once one was taken from the frontier,
+    /// taken by the oldest spilled one if any. It doesn't keep the frontier open.
+    pub(crate) fn releaser(&self) -> impl Fn() {
+        let tx = self.tx.downgrade();
+        let permits = self.permits.clone();
+        let spill = self.spill.clone();
+        move || {
+            let Some(permits) = &permits else {
+                return;
+            };
+            let mut spill = spill.lock().unwrap();
+            match spill.pop() {
+                Ok(Some(item)) => {
+                    if let Some(tx) = tx.upgrade() {
+                        tx.send(item).ok();
+                    }
+                }
+                Ok(None) => permits.add_permits(1),
+                Err(e) => {
+                    log::error!("Couldn't read spilled request: {e}");
+                    permits.add_permits(1);
+                }
+            }
+        }
+    }
+}
+
+/// Requests written to a file as JSON lines, read back in the same order.
+#[derive(Debug)]
+struct Spill {
+    path: PathBuf,
+    writer: Option<File>,
+    reader: Option<BufReader<File>>,
+    len: usize,
+}
+
+impl Spill {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            writer: None,
+            reader: None,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, item: &Prioritized) -> io::Result<()> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => self
+                .writer
+                .insert(File::options().create(true).append(true).open(&self.path)?),
+        };
+        let mut line = serde_json::to_vec(item)?;
+        line.push(b'\n');
+        writer.write_all(&line)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> io::Result<Option<Prioritized>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        let reader = match &mut self.reader {
+            Some(reader) => reader,
+            None => self.reader.insert(BufReader::new(File::open(&self.path)?)),
+        };
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        self.len -= 1;
+        if self.len == 0 {
+            // Everything was read back, the file is reused from its start
+            if let Some(writer) = &self.writer {
+                writer.set_len(0)?;
+            }
+            reader.rewind()?;
+        }
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+}
+
+/// The shared frontier to use accordingly to `config`, if any.
+pub(crate) fn shared_backend(
+    config: &FrontierConfig,
+) -> anyhow::Result<Option<Arc<dyn FrontierBackend>>> {
+    match (&config.backend, &config.redis_url) {
+        (Some(backend), _) => Ok(Some(backend.0.clone())),
+        #[cfg(feature = "redis")]
+        (None, Some(url)) => {
+            let key = config.redis_key.as_deref().unwrap_or("sws");
+            Ok(Some(Arc::new(RedisFrontier::open(url, key)?)))
+        }
+        #[cfg(not(feature = "redis"))]
+        (None, Some(_)) => anyhow::bail!(
+            "Invalid frontier config, `redis_url` requires sws-crawler's `redis` feature"
+        ),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Forwards local requests to the shared `backend`, and streams the requests popped from
+/// it (calling `on_pop` for each of them) until local requests are closed.
+pub(crate) fn shared_stream<F>(
+    backend: Arc<dyn FrontierBackend>,
+    rx: mpsc::UnboundedReceiver<Prioritized>,
+    on_pop: F,
+) -> impl Stream<Item = PageRequest>
+where
+    F: Fn() + Clone + 'static,
+{
+    stream::unfold((rx, false), move |(mut rx, mut closed)| {
+        let backend = backend.clone();
+        let on_pop = on_pop.clone();
+        async move {
+            loop {
+                let mut local = vec![];
+                while !closed {
+                    match rx.try_recv() {
+                        Ok(prioritized) => local.push(prioritized),
+                        Err(mpsc::error::TryRecvError::Empty) => break,
+                        Err(mpsc::error::TryRecvError::Disconnected) => closed = true,
+                    }
+                }
+
+                let backend = backend.clone();
+                let popped = tokio::task::spawn_blocking(move || {
+                    for Prioritized { request, priority } in local {
+                        if let Err(e) = backend.push(request, priority) {
+                            log::error!("Couldn't push to shared frontier: {e}");
+                        }
+                    }
+                    backend.pop()
+                })
+                .await;
+                match popped {
+                    Ok(Ok(Some(request))) => {
+                        on_pop();
+                        return Some((request, (rx, closed)));
+                    }
+                    Ok(Ok(None)) => (),
+                    Ok(Err(e)) => log::error!("Couldn't pop from shared frontier: {e}"),
+                    Err(e) => log::error!("Couldn't pop from shared frontier: {e}"),
+                }
+
+                if closed {
+                    return None;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    })
+}
+
+/// A frontier stored in Redis, requests are kept in a sorted set scored by priority and
+/// requests already seen by any crawler are not added again.
+#[cfg(feature = "redis")]
+pub(crate) struct RedisFrontier {
+    conn: std::sync::Mutex<redis::Connection>,
+    queue_key: String,
+    seen_key: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisFrontier {
+    pub(crate) fn open(url: &str, key: &str) -> anyhow::Result<Self> {
+        let conn = redis::Client::open(url)?.get_connection()?;
+        Ok(Self {
+            conn: std::sync::Mutex::new(conn),
+            queue_key: format!("{key}:queue"),
+            seen_key: format!("{key}:seen"),
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+impl FrontierBackend for RedisFrontier {
+    fn push(&self, request: PageRequest, priority: i32) -> anyhow::Result<()> {
+        let member = serde_json::to_string(&request)?;
+        let identity = serde_json::to_string(&request.identity())?;
+        let mut conn = self.conn.lock().unwrap();
+        let added: bool = redis::cmd("SADD")
+            .arg(&self.seen_key)
+            .arg(&identity)
+            .query(&mut *conn)?;
+        if added {
+            redis::cmd("ZADD")
+                .arg(&self.queue_key)
+                .arg(priority)
+                .arg(&member)
+                .query::<()>(&mut *conn)?;
+        }
+        Ok(())
+    }
+
+    fn pop(&self) -> anyhow::Result<Option<PageRequest>> {
+        let mut conn = self.conn.lock().unwrap();
+        let popped: Vec<(String, f64)> = redis::cmd("ZPOPMAX")
+            .arg(&self.queue_key)
+            .query(&mut *conn)?;
+        match popped.into_iter().next() {
+            Some((member, _)) => Ok(Some(serde_json::from_str(&member)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tokio::sync::mpsc;
+
+    use super::{Frontier, Prioritized, UrlQueue};
+
+    #[tokio::test]
+    async fn frontier_order() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for (url, priority) in [("a", 0), ("b", 5), ("c", 0), ("d", 10)] {
+            let request = url.into();
+            tx.send(Prioritized { request, priority }).unwrap();
+        }
+        drop(tx);
+        let urls = Frontier::new(rx)
+            .map(|request| request.url)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(urls, ["d", "b", "a", "c"]);
+    }
+
+    #[tokio::test]
+    async fn spill_when_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let queue = UrlQueue::new(tx, Some(2), dir.path());
+        let release = queue.releaser();
+        for url in ["a", "b", "c", "d"] {
+            let request = url.into();
+            queue
+                .push(
+                    Prioritized {
+                        request,
+                        priority: 0,
+                    },
+                    None,
+                )
+                .unwrap();
+        }
+        assert_eq!(rx.recv().await.unwrap().request.url, "a");
+        assert_eq!(rx.recv().await.unwrap().request.url, "b");
+        assert!(rx.try_recv().is_err());
+
+        release();
+        assert_eq!(rx.recv().await.unwrap().request.url, "c");
+        release();
+        assert_eq!(rx.recv().await.unwrap().request.url, "d");
+        release();
+        assert!(rx.try_recv().is_err());
+
+        // Room is made once the spilled requests were queued back
+        assert!(queue.reserve().await.is_some());
+    }
+}
@@ -10,16 +10,36 @@
 //! [robots-txt]: https://en.wikipedia.org/wiki/Robots.txt
 //! [robots]: https://docs.rs/texting_robots/latest/texting_robots/struct.Robot.html
 
+mod cache;
+mod client;
 mod config;
 mod crawler;
+mod decode;
+mod error;
+mod event;
+mod filter;
+mod frontier;
 mod limiter;
+mod robots;
 mod scrapable;
+mod state;
 
-pub use config::{CrawlerConfig, OnError, Throttle};
-pub use crawler::crawl_site;
+pub use config::{
+    ClientCertificate, CrawlerConfig, DnsConfig, DnsResolver, FrontierConfig, HttpConfig, OnError,
+    Revisit, RobotsFallback, SharedFrontier, Throttle, TlsConfig, UrlFilters, UserAgentPreset,
+};
+pub use crawler::{crawl_site, fetch_page};
+pub use error::{CrawlError, CrawlPhase, ErrorObserver, Interrupted};
+pub use event::{CrawlEvent, EventObserver};
+pub use frontier::FrontierBackend;
 pub use scrapable::{
-    CountedTx, CrawlingContext, PageLocation, Scrapable, ScrapingContext, Seed, Sitemap,
+    CountedTx, CrawlStop, CrawlingContext, FetchInfo, FetchedPage, Fetcher, OutcomeReporter,
+    PageLocation, PageOutcome, PageRequest, Scrapable, ScrapingContext, Seed, SeedGenerator,
+    Sitemap,
 };
+pub use state::{PageState, StateStore};
 
 pub use anyhow;
+pub use reqwest;
+pub use serde_json;
 pub use texting_robots;
@@ -1,90 +1,106 @@
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Duration;
 
 use futures::stream::{Fuse, FusedStream, FuturesUnordered};
-use futures::{future, Future, Stream, StreamExt};
+use futures::{Future, Stream, StreamExt};
 use pin_project_lite::pin_project;
-use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
-use tokio::time::timeout;
+use tokio::time::{Instant, Sleep};
 
+/// Token buckets, refilled continuously at a given rate and holding at most `burst`
+/// tokens. A single bucket is shared by all URLs, or one is created for each host when
+/// the limiter is per host.
 #[derive(Debug, Clone)]
 pub struct RateLimiter {
-    permits: Arc<Semaphore>,
+    /// Tokens per second
+    rate: f64,
+    burst: f64,
+    per_host: bool,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
 }
 
-impl RateLimiter {
-    pub fn with_limit(per_second: usize) -> Self {
-        let permits = Arc::new(Semaphore::new(0));
-
-        let permits_c = permits.clone();
-        tokio::spawn(async move {
-            loop {
-                match timeout(Duration::from_secs(1), future::pending::<()>()).await {
-                    Ok(_) => unreachable!(),
-                    Err(_) => {
-                        let available = permits_c.available_permits();
-                        permits_c.add_permits(per_second - available);
-                    }
-                }
-            }
-        });
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
 
-        Self { permits }
+impl RateLimiter {
+    pub fn new(rate: f64, burst: usize) -> Self {
+        Self {
+            rate,
+            burst: burst.max(1) as f64,
+            per_host: false,
+            buckets: Arc::default(),
+        }
     }
 
-    pub fn with_delay(delay: f32) -> Self {
-        let permits = Arc::new(Semaphore::new(0));
+    pub fn with_limit(per_second: usize, burst: usize) -> Self {
+        Self::new(per_second as f64, burst)
+    }
 
-        let permits_c = permits.clone();
-        tokio::spawn(async move {
-            loop {
-                match timeout(Duration::from_secs_f32(delay), future::pending::<()>()).await {
-                    Ok(_) => unreachable!(),
-                    Err(_) => {
-                        let available = permits_c.available_permits();
-                        if available == 0 {
-                            permits_c.add_permits(1);
-                        }
-                    }
-                }
-            }
-        });
+    pub fn with_delay(delay: f32, burst: usize) -> Self {
+        Self::new(1.0 / delay as f64, burst)
+    }
 
-        Self { permits }
+    /// Makes the limit apply to each host separately.
+    pub fn per_host(mut self) -> Self {
+        self.per_host = true;
+        self
     }
 
-    pub fn try_acquire_owned(&self) -> Result<OwnedSemaphorePermit, TryAcquireError> {
-        self.permits.clone().try_acquire_owned()
+    /// Takes a token if one is available, otherwise returns how long to wait for one.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_key("")
     }
-}
 
-pin_project! {
-    pub struct PermittedFuture<F> {
-        #[pin]
-        fut: F,
-        permit: Option<OwnedSemaphorePermit>,
+    /// Takes a token for `url` (from its host's bucket when per host) if one is available,
+    /// otherwise returns how long to wait for one.
+    pub fn try_acquire_for(&self, url: &str) -> Result<(), Duration> {
+        self.try_acquire_key(&self.key_for(url))
     }
 
-    impl<F> PinnedDrop for PermittedFuture<F> {
-        fn drop(this: Pin<&mut Self>) {
-            let this = this.project();
-            if let Some(p) = this.permit.take() { p.forget() }
+    /// The bucket of `url`, its host when per host.
+    fn key_for(&self, url: &str) -> String {
+        if self.per_host {
+            // URLs without host share a bucket
+            reqwest::Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_default()
+        } else {
+            String::new()
         }
     }
-}
 
-impl<F> Future for PermittedFuture<F>
-where
-    F: Future,
-{
-    type Output = F::Output;
+    fn try_acquire_key(&self, key: &str) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            refilled_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        let this = self.project();
-        this.fut.poll(cx)
+    /// Waits until a token is available for `url`, then takes it.
+    pub async fn acquire_for(&self, url: &str) {
+        while let Err(wait) = self.try_acquire_for(url) {
+            tokio::time::sleep(wait).await;
+        }
     }
 }
 
@@ -95,7 +111,9 @@ pin_project! {
     {
         #[pin]
         stream: Fuse<St>,
-        in_progress_queue: FuturesUnordered<PermittedFuture<St::Item>>,
+        next: Option<St::Item>,
+        sleep: Option<Pin<Box<Sleep>>>,
+        in_progress_queue: FuturesUnordered<St::Item>,
         limiter: RateLimiter,
     }
 }
@@ -103,10 +121,12 @@ pin_project! {
 impl<St> fmt::Debug for RateLimited<St>
 where
     St: Stream + fmt::Debug,
+    St::Item: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RateLimited")
             .field("stream", &self.stream)
+            .field("next", &self.next)
             .field("in_progress_queue", &self.in_progress_queue)
             .field("limiter", &self.limiter)
             .finish()
@@ -125,26 +145,12 @@ where
     {
         Self {
             stream: stream.fuse(),
+            next: None,
+            sleep: None,
             in_progress_queue: FuturesUnordered::new(),
             limiter,
         }
     }
-
-    // pub fn get_ref(&self) -> &St {
-    //     (&self.stream).get_ref()
-    // }
-
-    // pub fn get_mut(&mut self) -> &mut St {
-    //     (&mut self.stream).get_mut()
-    // }
-
-    // pub fn get_pin_mut(self: core::pin::Pin<&mut Self>) -> core::pin::Pin<&mut St> {
-    //     self.project().stream.get_pin_mut()
-    // }
-
-    // pub fn into_inner(self) -> St {
-    //     self.stream.into_inner()
-    // }
 }
 
 impl<St> Stream for RateLimited<St>
@@ -157,14 +163,29 @@ where
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let mut this = self.project();
 
-        // First up, try to spawn off as many futures as possible
-        while let Ok(permit) = this.limiter.try_acquire_owned() {
-            match this.stream.as_mut().poll_next(cx) {
-                Poll::Ready(Some(fut)) => this.in_progress_queue.push(PermittedFuture {
-                    permit: Some(permit),
-                    fut,
-                }),
-                Poll::Ready(None) | Poll::Pending => break,
+        // First up, try to spawn off as many futures as tokens allow
+        loop {
+            if let Some(sleep) = this.sleep.as_mut() {
+                match sleep.as_mut().poll(cx) {
+                    Poll::Ready(()) => *this.sleep = None,
+                    Poll::Pending => break,
+                }
+            }
+
+            if this.next.is_none() {
+                match this.stream.as_mut().poll_next(cx) {
+                    Poll::Ready(Some(fut)) => *this.next = Some(fut),
+                    Poll::Ready(None) | Poll::Pending => break,
+                }
+            }
+
+            match this.limiter.try_acquire() {
+                Ok(()) => {
+                    if let Some(fut) = this.next.take() {
+                        this.in_progress_queue.push(fut);
+                    }
+                }
+                Err(wait) => *this.sleep = Some(Box::pin(tokio::time::sleep(wait))),
             }
         }
 
@@ -175,7 +196,7 @@ where
         }
 
         // If more values are still coming from the stream, we're not done yet
-        if this.stream.is_done() {
+        if this.stream.is_done() && this.next.is_none() {
             Poll::Ready(None)
         } else {
             Poll::Pending
@@ -183,7 +204,7 @@ where
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let queue_len = self.in_progress_queue.len();
+        let queue_len = self.in_progress_queue.len() + usize::from(self.next.is_some());
         let (lower, upper) = self.stream.size_hint();
         let lower = lower.saturating_add(queue_len);
         let upper = match upper {
@@ -200,7 +221,119 @@ where
     St::Item: Future,
 {
     fn is_terminated(&self) -> bool {
-        self.in_progress_queue.is_terminated() && self.stream.is_terminated()
+        self.next.is_none() && self.in_progress_queue.is_terminated() && self.stream.is_terminated()
+    }
+}
+
+pin_project! {
+    /// Rate limits futures paired with their URL, each one waiting in the queue of its
+    /// bucket (i.e. its host) so that the throttled ones don't hold the others.
+    pub struct PerHostRateLimited<St, F>
+    where
+        St: Stream<Item = (String, F)>,
+        F: Future,
+    {
+        #[pin]
+        stream: Fuse<St>,
+        queues: HashMap<String, VecDeque<F>>,
+        queued: usize,
+        max_queued: usize,
+        sleep: Option<Pin<Box<Sleep>>>,
+        in_progress_queue: FuturesUnordered<F>,
+        max_in_progress: usize,
+        limiter: RateLimiter,
+    }
+}
+
+impl<St, F> PerHostRateLimited<St, F>
+where
+    St: Stream<Item = (String, F)>,
+    F: Future,
+{
+    /// At most `max_queued` futures wait for a token, and `max_in_progress` run at once.
+    pub fn new(
+        stream: St,
+        limiter: RateLimiter,
+        max_queued: usize,
+        max_in_progress: usize,
+    ) -> Self {
+        Self {
+            stream: stream.fuse(),
+            queues: HashMap::new(),
+            queued: 0,
+            max_queued: max_queued.max(1),
+            sleep: None,
+            in_progress_queue: FuturesUnordered::new(),
+            max_in_progress: max_in_progress.max(1),
+            limiter,
+        }
+    }
+}
+
+impl<St, F> Stream for PerHostRateLimited<St, F>
+where
+    St: Stream<Item = (String, F)>,
+    F: Future,
+{
+    type Item = F::Output;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            // Futures are pulled ahead of the tokens, queued by bucket
+            while *this.queued < *this.max_queued {
+                match this.stream.as_mut().poll_next(cx) {
+                    Poll::Ready(Some((url, fut))) => {
+                        let key = this.limiter.key_for(&url);
+                        this.queues.entry(key).or_default().push_back(fut);
+                        *this.queued += 1;
+                    }
+                    Poll::Ready(None) | Poll::Pending => break,
+                }
+            }
+
+            // Then each bucket spawns off as many futures as its tokens allow
+            let mut wait = None::<Duration>;
+            this.queues.retain(|key, queue| {
+                while !queue.is_empty() && this.in_progress_queue.len() < *this.max_in_progress {
+                    match this.limiter.try_acquire_key(key) {
+                        Ok(()) => {
+                            this.in_progress_queue.extend(queue.pop_front());
+                            *this.queued -= 1;
+                        }
+                        Err(next) => {
+                            wait = Some(wait.map_or(next, |wait| wait.min(next)));
+                            break;
+                        }
+                    }
+                }
+                !queue.is_empty()
+            });
+
+            // Woken up once the first bucket has a token, or once a future completes
+            *this.sleep = None;
+            if let (Some(wait), true) = (wait, this.in_progress_queue.len() < *this.max_in_progress)
+            {
+                let mut sleep = Box::pin(tokio::time::sleep(wait));
+                if sleep.as_mut().poll(cx).is_ready() {
+                    continue;
+                }
+                *this.sleep = Some(sleep);
+            }
+            break;
+        }
+
+        match this.in_progress_queue.poll_next_unpin(cx) {
+            x @ Poll::Pending | x @ Poll::Ready(Some(_)) => return x,
+            Poll::Ready(None) => {}
+        }
+
+        if this.stream.is_done() && *this.queued == 0 {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
     }
 }
 
@@ -212,6 +345,25 @@ pub trait RateLimitedExt: Stream {
     {
         assert_stream::<<Self::Item as Future>::Output, _>(RateLimited::new(self, limiter))
     }
+
+    /// See [`PerHostRateLimited`]
+    fn rate_limited_per_host<F>(
+        self,
+        limiter: RateLimiter,
+        max_queued: usize,
+        max_in_progress: usize,
+    ) -> PerHostRateLimited<Self, F>
+    where
+        Self: Stream<Item = (String, F)> + Sized,
+        F: Future,
+    {
+        assert_stream::<F::Output, _>(PerHostRateLimited::new(
+            self,
+            limiter,
+            max_queued,
+            max_in_progress,
+        ))
+    }
 }
 
 impl<T: ?Sized> RateLimitedExt for T where T: Stream {}
@@ -222,3 +374,85 @@ where
 {
     stream
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{stream, StreamExt};
+    use tokio::time::Instant;
+
+    use super::{RateLimitedExt, RateLimiter};
+
+    async fn start_times(limiter: RateLimiter, n: usize) -> Vec<Duration> {
+        let start = Instant::now();
+        stream::iter(0..n)
+            .map(|_| async move { start.elapsed() })
+            .rate_limited(limiter)
+            .collect()
+            .await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn smooth_per_second() {
+        let times = start_times(RateLimiter::with_limit(10, 1), 20).await;
+        for (i, t) in times.iter().enumerate() {
+            let expected = Duration::from_millis(100 * i as u64);
+            assert!(
+                t.abs_diff(expected) < Duration::from_millis(5),
+                "{i}: {t:?}"
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_then_smooth() {
+        let times = start_times(RateLimiter::with_delay(0.5, 3), 5).await;
+        let millis = times.iter().map(|t| t.as_millis()).collect::<Vec<_>>();
+        assert_eq!(&millis[..3], [0, 0, 0]);
+        assert!((499..=501).contains(&millis[3]), "{millis:?}");
+        assert!((999..=1001).contains(&millis[4]), "{millis:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn per_host_queues() {
+        // The throttled host's URLs come first, but don't take the slots of the other host's
+        let limiter = RateLimiter::with_limit(1, 1).per_host();
+        let start = Instant::now();
+        let urls = ["a", "a", "a", "a", "b", "b"]
+            .map(|host| format!("https://{host}.com/"))
+            .to_vec();
+        let times = stream::iter(urls)
+            .map(|url| {
+                let fut = {
+                    let url = url.clone();
+                    async move { (url, start.elapsed().as_millis()) }
+                };
+                (url, fut)
+            })
+            .rate_limited_per_host(limiter, 100, 2)
+            .collect::<Vec<_>>()
+            .await;
+        let millis = |host: &str| {
+            times
+                .iter()
+                .filter(|(url, _)| url.contains(host))
+                .map(|(_, t)| *t)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(millis("a.com"), [0, 1000, 2000, 3000]);
+        assert_eq!(millis("b.com"), [0, 1000]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn per_host_buckets() {
+        let limiter = RateLimiter::with_limit(1, 1).per_host();
+        let start = Instant::now();
+        for url in ["https://a.com/1", "https://b.com/1", "https://a.com/2"] {
+            limiter.acquire_for(url).await;
+        }
+        assert_eq!(start.elapsed().as_millis(), 1000);
+        assert!(limiter.try_acquire_for("https://b.com/2").is_ok());
+        assert!(limiter.try_acquire_for("https://a.com/3").is_err());
+    }
+}
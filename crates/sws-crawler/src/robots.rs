@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Error, Result};
+use texting_robots::Robot;
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::config::{CrawlerConfig, OnError, RobotsFallback};
+use crate::crawler::download;
+use crate::error::{self, CrawlPhase};
+
+type HostRobot = Arc<OnceCell<Arc<Robot>>>;
+
+/// The robots.txt of each crawled host, fetched once when one of its URLs is first seen.
+#[derive(Debug, Default)]
+pub(crate) struct HostRobots {
+    robots: Mutex<HashMap<String, HostRobot>>,
+}
+
+impl HostRobots {
+    /// The robot of `url`'s host, `None` for URLs without a host.
+    pub(crate) async fn get(
+        &self,
+        config: &CrawlerConfig,
+        client: &reqwest::Client,
+        url: &str,
+    ) -> Result<Option<Arc<Robot>>> {
+        let origin = match reqwest::Url::parse(url) {
+            Ok(url) if url.origin().is_tuple() => url.origin().ascii_serialization(),
+            _ => return Ok(None),
+        };
+
+        let cell = self
+            .robots
+            .lock()
+            .await
+            .entry(origin.clone())
+            .or_default()
+            .clone();
+        let robot = cell
+            .get_or_try_init(|| async {
+                let robot = fetch_robot(config, client, &format!("{origin}/robots.txt")).await?;
+                Ok::<_, Error>(Arc::new(robot))
+            })
+            .await?;
+        Ok(Some(robot.clone()))
+    }
+}
+
+/// Fetches the robots.txt at `url`, falling back to `config.robots_fallback` when it is
+/// unreachable or unparsable and `config.on_robots_error` is [`OnError::SkipAndLog`].
+///
+/// As per [RFC 9309](https://www.rfc-editor.org/rfc/rfc9309#section-2.3.1.3), a robots.txt
+/// answered with a client error status (such as `404`) allows everything.
+pub(crate) async fn fetch_robot(
+    config: &CrawlerConfig,
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<Robot> {
+    let robot = async {
+        let page = download(config, client, &url.into()).await?;
+        if page.fetch_info.status.is_client_error() {
+            return Robot::new(&config.user_agent, b"");
+        }
+        anyhow::ensure!(
+            page.fetch_info.status.is_success(),
+            "Unexpected status {}",
+            page.fetch_info.status
+        );
+        Robot::new(&config.user_agent, page.page.as_bytes())
+    };
+    let robot = robot
+        .await
+        .inspect_err(|e| error::report(config, url, CrawlPhase::Robots, e, 1));
+    match (robot, config.on_robots_error) {
+        (Ok(robot), _) => Ok(robot),
+        (Err(e), OnError::Fail) => Err(anyhow!("Couldn't retrieve {url} got: {e}")),
+        (Err(e), OnError::SkipAndLog) => {
+            log::warn!(
+                "Couldn't retrieve {url}, using {:?} fallback, got: {e}",
+                config.robots_fallback
+            );
+            let txt: &[u8] = match config.robots_fallback {
+                RobotsFallback::Allow => b"",
+                RobotsFallback::Deny => b"User-agent: *\nDisallow: /",
+            };
+            Robot::new(&config.user_agent, txt)
+        }
+    }
+}
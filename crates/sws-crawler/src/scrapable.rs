@@ -1,12 +1,26 @@
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail};
+use crossbeam_channel::Sender;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
 use sxd_document::dom;
 use texting_robots::Robot;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch, OwnedSemaphorePermit};
+
+use crate::config::CrawlerConfig;
+use crate::event::{CrawlEvent, EventObserver};
+use crate::frontier::{Prioritized, UrlQueue};
+use crate::state::StateStore;
 
 pub trait Scrapable {
     type Config: Clone + Send + 'static;
@@ -19,8 +33,42 @@ pub trait Scrapable {
 
     fn accept(&self, url: &str, crawling_ctx: CrawlingContext) -> bool;
 
+    /// The priority of an accepted URL, higher priorities are fetched first.
+    fn priority(&self, _url: &str, _crawling_ctx: CrawlingContext) -> i32 {
+        0
+    }
+
     fn scrap(&mut self, page: String, scraping_ctx: ScrapingContext) -> anyhow::Result<()>;
 
+    /// Called once by each worker, before it scraps its first page. The context has no
+    /// page location.
+    fn init(&mut self, _scraping_ctx: ScrapingContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called once by each worker when it stops, after it scraped its last page. The
+    /// context has no page location.
+    ///
+    /// The returned value (the state accumulated by the worker) is passed to
+    /// [`Scrapable::finalize`].
+    fn finish(
+        &mut self,
+        _scraping_ctx: ScrapingContext,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        Ok(None)
+    }
+
+    /// Called once at the end of the crawl, after all the workers stopped, with the values
+    /// returned by their [`Scrapable::finish`]. It runs before the [`Scrapable::finalizer`]
+    /// so it can still send records.
+    fn finalize(
+        &mut self,
+        _results: Vec<serde_json::Value>,
+        _scraping_ctx: ScrapingContext,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     fn finalizer(&mut self) {}
 }
 
@@ -29,17 +77,181 @@ pub enum Seed {
     Sitemaps(Vec<String>),
     Pages(Vec<String>),
     RobotsTxt(String),
+    Requests(Vec<PageRequest>),
+    /// Page URLs produced lazily while crawling, until the generator returns `None`
+    Generator(SeedGenerator),
+    /// Combines several seeds in a single crawl
+    Many(Vec<Seed>),
+}
+
+/// A generator of seed page URLs, for instance date-based archive URLs that would be too
+/// many to be precomputed.
+#[derive(Clone)]
+pub struct SeedGenerator(Rc<RefCell<dyn FnMut() -> Option<String>>>);
+
+impl SeedGenerator {
+    pub fn new<F>(generator: F) -> Self
+    where
+        F: FnMut() -> Option<String> + 'static,
+    {
+        Self(Rc::new(RefCell::new(generator)))
+    }
+}
+
+impl Iterator for SeedGenerator {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        (self.0.borrow_mut())()
+    }
+}
+
+impl fmt::Debug for SeedGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SeedGenerator").finish()
+    }
+}
+
+impl Seed {
+    /// Flattens nested [`Seed::Many`] into the seeds they contain.
+    pub(crate) fn flatten(self) -> Vec<Seed> {
+        match self {
+            Seed::Many(seeds) => seeds.into_iter().flat_map(Seed::flatten).collect(),
+            seed => vec![seed],
+        }
+    }
+}
+
+/// An HTTP request for a page, a simple `GET` of its URL by default.
+///
+/// Requests are compared without their crawl metadata (`meta`, `depth` and `referrer`), so
+/// that a page sent twice from different places is still only fetched once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRequest {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Metadata attached to the request, available when its page is scraped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<serde_json::Value>,
+    /// The number of links followed from a seed page to reach this page
+    #[serde(default, skip_serializing_if = "is_seed")]
+    pub depth: usize,
+    /// The URL of the page this request was sent from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referrer: Option<String>,
+    /// The number of times the page was requeued by its scraper, see
+    /// [`ScrapingContext::retry`]
+    #[serde(default, skip_serializing_if = "is_first_attempt")]
+    pub retries: usize,
+}
+
+fn is_seed(depth: &usize) -> bool {
+    *depth == 0
+}
+
+fn is_first_attempt(retries: &usize) -> bool {
+    *retries == 0
+}
+
+impl PageRequest {
+    /// The request without its crawl metadata, retries are kept so that they are queued
+    /// again.
+    #[cfg(feature = "redis")]
+    pub(crate) fn identity(&self) -> Self {
+        Self {
+            meta: None,
+            depth: 0,
+            referrer: None,
+            ..self.clone()
+        }
+    }
+
+    /// The attempt number (starting from 1) of the fetch and scrap of the page
+    pub(crate) fn attempt(&self) -> u32 {
+        self.retries as u32 + 1
+    }
+
+    fn key(&self) -> (&str, &str, Option<&str>, Option<&str>) {
+        (
+            &self.url,
+            &self.method,
+            self.body.as_deref(),
+            self.content_type.as_deref(),
+        )
+    }
+}
+
+impl PartialEq for PageRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for PageRequest {}
+
+impl Hash for PageRequest {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+fn default_method() -> String {
+    String::from("GET")
+}
+
+impl From<String> for PageRequest {
+    fn from(url: String) -> Self {
+        Self {
+            url,
+            method: default_method(),
+            body: None,
+            content_type: None,
+            meta: None,
+            depth: 0,
+            referrer: None,
+            retries: 0,
+        }
+    }
+}
+
+impl From<&str> for PageRequest {
+    fn from(url: &str) -> Self {
+        Self::from(url.to_string())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct CrawlingContext {
     sitemap: Sitemap,
     robot: Option<Arc<Robot>>,
+    parent: Option<String>,
+    depth: usize,
+    lastmod: Option<String>,
 }
 
 impl CrawlingContext {
-    pub(crate) fn new(sm: Sitemap, robot: Option<Arc<Robot>>) -> Self {
-        Self { sitemap: sm, robot }
+    /// Creates the context of a URL found in a sitemap, for instance to test the
+    /// [`Scrapable::accept`] of a scraper.
+    pub fn new(
+        sm: Sitemap,
+        robot: Option<Arc<Robot>>,
+        parent: Option<String>,
+        depth: usize,
+        lastmod: Option<String>,
+    ) -> Self {
+        Self {
+            sitemap: sm,
+            robot,
+            parent,
+            depth,
+            lastmod,
+        }
     }
 
     pub fn sitemap(&self) -> Sitemap {
@@ -49,6 +261,22 @@ impl CrawlingContext {
     pub fn robot(&self) -> Option<Arc<Robot>> {
         self.robot.clone()
     }
+
+    /// The URL of the sitemap (or robots.txt) where the URL was found.
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+
+    /// The nesting depth of the parent sitemap, `0` for seed sitemaps and the ones
+    /// listed in a robots.txt.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The `<lastmod>` of the URL's sitemap entry, if any.
+    pub fn lastmod(&self) -> Option<&str> {
+        self.lastmod.as_deref()
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -77,31 +305,43 @@ impl<'a> TryFrom<dom::Root<'a>> for Sitemap {
     }
 }
 
-#[derive(Debug, Clone)]
+/// The context of a scraped page, the crawler builds it from its fields (the ones that
+/// don't apply staying `None`).
+#[derive(Debug, Clone, Default)]
 pub struct ScrapingContext {
-    location: Rc<PageLocation>,
-    tx_url: Option<CountedTx>,
-    robot: Option<Arc<Robot>>,
+    pub(crate) location: Option<Rc<PageLocation>>,
+    pub(crate) tx_url: Option<CountedTx>,
+    pub(crate) robot: Option<Arc<Robot>>,
+    pub(crate) fetch_info: Option<Arc<FetchInfo>>,
+    pub(crate) request: Option<Arc<PageRequest>>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) scratch_dir: Option<Arc<Path>>,
+    pub(crate) worker_scratch_dir: Option<Arc<Path>>,
+    pub(crate) crawl_stop: Option<CrawlStop>,
+    pub(crate) fetcher: Option<Fetcher>,
+    pub(crate) outcome: OutcomeReporter,
 }
 
 impl ScrapingContext {
     pub fn with_location(location: PageLocation) -> Self {
-        Self::new(Rc::new(location), None, None)
+        Self {
+            location: Some(Rc::new(location)),
+            ..Self::default()
+        }
     }
 
-    pub(crate) fn new(
-        location: Rc<PageLocation>,
-        tx_url: Option<CountedTx>,
-        robot: Option<Arc<Robot>>,
-    ) -> Self {
+    /// A context for the page of `request`, as if it was fetched by the crawler.
+    pub fn with_request(request: PageRequest) -> Self {
         Self {
-            location,
-            tx_url,
-            robot,
+            location: Some(Rc::new(PageLocation::Url(request.url.clone()))),
+            request: Some(Arc::new(request)),
+            ..Self::default()
         }
     }
 
-    pub fn location(&self) -> Rc<PageLocation> {
+    /// The location of the scraped page, `None` in the [`Scrapable::init`],
+    /// [`Scrapable::finish`] and [`Scrapable::finalize`] hooks.
+    pub fn location(&self) -> Option<Rc<PageLocation>> {
         self.location.clone()
     }
 
@@ -112,6 +352,194 @@ impl ScrapingContext {
     pub fn robot(&self) -> Option<Arc<Robot>> {
         self.robot.clone()
     }
+
+    /// The metadata of the HTTP response, `None` for local pages.
+    pub fn fetch_info(&self) -> Option<Arc<FetchInfo>> {
+        self.fetch_info.clone()
+    }
+
+    /// The request the page was fetched with, `None` for local pages.
+    pub fn request(&self) -> Option<Arc<PageRequest>> {
+        self.request.clone()
+    }
+
+    /// The metadata attached to the page's request, if any.
+    pub fn meta(&self) -> Option<&serde_json::Value> {
+        self.request.as_deref()?.meta.as_ref()
+    }
+
+    /// The number of links followed from a seed page to reach this page, `0` for seeds and
+    /// local pages.
+    pub fn depth(&self) -> usize {
+        self.request.as_ref().map(|r| r.depth).unwrap_or_default()
+    }
+
+    /// The URL of the page that sent this page's URL, `None` for seeds and local pages.
+    pub fn referrer(&self) -> Option<&str> {
+        self.request.as_deref()?.referrer.as_deref()
+    }
+
+    /// When the scrap of this page times out, `None` if there is no `scrap_timeout`.
+    ///
+    /// Long running scrapers should abort once it is reached. A scrap that still completes
+    /// after its deadline is handled as a scrap error, but the crawler only checks it once
+    /// `scrap` returned: a scraper that never returns is never interrupted.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// A directory shared by all the workers of the crawl, removed once it is over.
+    pub fn scratch_dir(&self) -> Option<&Path> {
+        self.scratch_dir.as_deref()
+    }
+
+    /// A directory dedicated to the current worker, inside [`Self::scratch_dir`].
+    pub fn worker_scratch_dir(&self) -> Option<&Path> {
+        self.worker_scratch_dir.as_deref()
+    }
+
+    /// The handle stopping the crawl, `None` for local pages.
+    pub fn crawl_stop(&self) -> Option<CrawlStop> {
+        self.crawl_stop.clone()
+    }
+
+    /// The handle fetching auxiliary URLs, `None` for local pages and in the
+    /// [`Scrapable::finalize`] hook.
+    pub fn fetcher(&self) -> Option<Fetcher> {
+        self.fetcher.clone()
+    }
+
+    /// Where the scraper reports what to do with the page, shared by the clones of this
+    /// context.
+    pub fn outcome(&self) -> OutcomeReporter {
+        self.outcome.clone()
+    }
+
+    /// A copy of this context with its own [`OutcomeReporter`], for scrapers dispatching
+    /// the page to several others.
+    pub fn with_own_outcome(&self) -> Self {
+        Self {
+            outcome: OutcomeReporter::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Requeues the page to be fetched and scraped again, for instance when it was
+    /// rate-limited. Ignored for local pages.
+    pub fn retry(&self, reason: &str) {
+        self.outcome.retry(reason)
+    }
+
+    /// Marks the page as intentionally skipped, for instance when it is not a product page.
+    pub fn skip(&self, reason: &str) {
+        self.outcome.skip(reason)
+    }
+}
+
+/// What to do with a scraped page, instead of considering it done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageOutcome {
+    /// The page is fetched and scraped again, up to `max_scrap_retries` times
+    Retry(String),
+    /// The page was intentionally not scraped
+    Skip(String),
+}
+
+/// Reports the [`PageOutcome`] of a scrap, the last one reported wins.
+#[derive(Debug, Clone, Default)]
+pub struct OutcomeReporter(Rc<RefCell<Option<PageOutcome>>>);
+
+impl OutcomeReporter {
+    pub fn retry(&self, reason: &str) {
+        self.0.replace(Some(PageOutcome::Retry(reason.to_string())));
+    }
+
+    pub fn skip(&self, reason: &str) {
+        self.0.replace(Some(PageOutcome::Skip(reason.to_string())));
+    }
+
+    pub fn take(&self) -> Option<PageOutcome> {
+        self.0.take()
+    }
+}
+
+/// Stops the crawl: no more URLs are queued nor downloaded, the workers stop after their
+/// current page and the outputs are finalized.
+#[derive(Debug, Clone)]
+pub struct CrawlStop(Arc<watch::Sender<Option<String>>>);
+
+impl CrawlStop {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(watch::channel(None).0))
+    }
+
+    /// Requests the crawl to stop, only the first `reason` is kept.
+    pub fn stop(&self, reason: &str) {
+        self.0.send_if_modified(|stopped| {
+            stopped.get_or_insert_with(|| reason.to_string());
+            true
+        });
+    }
+
+    /// The reason the crawl was stopped for, `None` while it is running.
+    pub fn reason(&self) -> Option<String> {
+        self.0.borrow().clone()
+    }
+
+    /// Resolves once the crawl is stopped.
+    pub(crate) async fn stopped(&self) {
+        self.0.subscribe().wait_for(Option::is_some).await.ok();
+    }
+}
+
+/// An auxiliary URL to fetch, along with where to send its page.
+pub(crate) type FetchRequest = (String, Sender<anyhow::Result<FetchedPage>>);
+
+/// Fetches auxiliary URLs (such as a JSON endpoint completing a page's data) through the
+/// crawler's HTTP client, honoring its throttle and the robots.txt of their hosts.
+///
+/// Fetched pages are returned to the scraper, they are neither scraped nor followed.
+#[derive(Debug, Clone)]
+pub struct Fetcher(mpsc::UnboundedSender<FetchRequest>);
+
+impl Fetcher {
+    pub(crate) fn new(tx: mpsc::UnboundedSender<FetchRequest>) -> Self {
+        Self(tx)
+    }
+
+    /// Blocks until `url` is fetched with a `GET`, responses with an error status are
+    /// returned as well.
+    pub fn fetch(&self, url: &str) -> anyhow::Result<FetchedPage> {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.0
+            .send((url.to_string(), tx))
+            .map_err(|_| anyhow!("Couldn't fetch {url}, the crawl is over"))?;
+        rx.recv()
+            .map_err(|_| anyhow!("Couldn't fetch {url}, the crawl is over"))?
+    }
+}
+
+/// A page fetched with [`Fetcher::fetch`].
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    /// The response body, decoded accordingly to its `Content-Type` charset
+    pub page: String,
+    pub fetch_info: FetchInfo,
+}
+
+/// The metadata of the HTTP response of a page.
+#[derive(Debug, Clone)]
+pub struct FetchInfo {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// The `Content-Length` of the (possibly compressed) response body
+    pub content_length: Option<u64>,
+    /// The duration between sending the request and receiving the whole response body
+    pub latency: Duration,
+    /// The URL of the response, which differs from the requested one after redirects
+    pub final_url: String,
+    /// The decompressed response body, before it is decoded to the scraped page
+    pub body: Arc<[u8]>,
 }
 
 #[derive(Debug, Clone)]
@@ -120,24 +548,131 @@ pub enum PageLocation {
     Path(PathBuf),
 }
 
+fn host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(str::to_string)
+}
+
+/// Requests sent for crawling and not downloaded yet.
+pub(crate) type InFlight = Arc<Mutex<HashSet<PageRequest>>>;
+
 #[derive(Debug, Clone)]
 pub struct CountedTx {
-    tx: mpsc::UnboundedSender<String>,
+    queue: UrlQueue,
     counter: Arc<AtomicUsize>,
+    in_flight: InFlight,
+    /// The URL and depth of the page requests are sent from
+    referrer: Option<(String, usize)>,
+    on_event: Option<EventObserver>,
+    /// Links followed from further than this depth are dropped
+    max_depth: Option<usize>,
+    /// Whether links to another host than the one of their referrer are dropped
+    same_host_only: bool,
+    /// Where queued requests are tracked until they are scraped, for the crawl to be resumed
+    state: Option<Arc<StateStore>>,
 }
 
 impl CountedTx {
-    pub fn new(tx: mpsc::UnboundedSender<String>, counter: Arc<AtomicUsize>) -> Self {
-        Self { tx, counter }
+    pub(crate) fn new(
+        queue: UrlQueue,
+        counter: Arc<AtomicUsize>,
+        in_flight: InFlight,
+        state: Option<Arc<StateStore>>,
+        config: &CrawlerConfig,
+    ) -> Self {
+        Self {
+            queue,
+            counter,
+            in_flight,
+            referrer: None,
+            on_event: config.on_event.clone(),
+            max_depth: config.max_depth,
+            same_host_only: config.same_host_only,
+            state,
+        }
+    }
+
+    /// A sender for the URLs found in the page fetched with `request`.
+    pub(crate) fn referred_by(&self, request: &PageRequest) -> Self {
+        Self {
+            referrer: Some((request.url.clone(), request.depth)),
+            ..self.clone()
+        }
+    }
+
+    pub fn send<R: Into<PageRequest>>(&self, request: R) {
+        self.send_with_priority(request, 0)
+    }
+
+    /// Sends a request that will be fetched before the ones with a lower priority.
+    ///
+    /// Requests that are already in flight are coalesced, so that they are only fetched
+    /// (and scraped) once. Links beyond the crawler's `max_depth`, or to another host with
+    /// `same_host_only`, are dropped. When the queue is full (see the crawler's
+    /// `url_buffer`) the request is spilled to disk until there is room for it.
+    pub fn send_with_priority<R: Into<PageRequest>>(&self, request: R, priority: i32) {
+        self.enqueue(request.into(), priority, None)
     }
 
-    pub fn send(&self, s: String) {
-        match self.tx.send(s) {
+    /// See [`UrlQueue::releaser`]
+    pub(crate) fn releaser(&self) -> impl Fn() {
+        self.queue.releaser()
+    }
+
+    /// Same as [`send_with_priority`](Self::send_with_priority), but waits until there
+    /// is room in the queue.
+    pub(crate) async fn send_when_ready<R: Into<PageRequest>>(&self, request: R, priority: i32) {
+        let permit = self.queue.reserve().await;
+        self.enqueue(request.into(), priority, permit)
+    }
+
+    fn enqueue(
+        &self,
+        mut request: PageRequest,
+        priority: i32,
+        permit: Option<OwnedSemaphorePermit>,
+    ) {
+        if let (Some((url, depth)), None) = (&self.referrer, &request.referrer) {
+            request.depth = depth + 1;
+            request.referrer = Some(url.clone());
+        }
+        if self
+            .max_depth
+            .is_some_and(|max_depth| request.depth > max_depth)
+        {
+            log::debug!("Skipping URL beyond max_depth {}", request.url);
+            return;
+        }
+        if let (true, Some(referrer)) = (self.same_host_only, &request.referrer) {
+            if host(referrer) != host(&request.url) {
+                log::debug!("Skipping URL of another host {}", request.url);
+                return;
+            }
+        }
+        if !self.in_flight.lock().unwrap().insert(request.clone()) {
+            log::debug!("Coalescing already in flight URL {}", request.url);
+            return;
+        }
+        if let Some(state) = &self.state {
+            state.add_pending(&request, priority);
+        }
+        let url = self.on_event.as_ref().map(|_| request.url.clone());
+        let prioritized = Prioritized { request, priority };
+        match self.queue.push(prioritized, permit) {
             Ok(()) => {
                 self.counter.fetch_add(1, Ordering::SeqCst);
+                if let (Some(observer), Some(url)) = (&self.on_event, url) {
+                    observer.emit(CrawlEvent::Discovered(&url));
+                }
             }
-            Err(e) => {
-                log::error!("Couldn't send data: {e}");
+            Err(prioritized) => {
+                self.in_flight.lock().unwrap().remove(&prioritized.request);
+                if let Some(state) = &self.state {
+                    state.remove_pending(&prioritized.request);
+                }
+                log::error!("Couldn't send data: channel closed");
             }
         }
     }
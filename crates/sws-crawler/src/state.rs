@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::scrapable::PageRequest;
+
+/// The state of a page as of its last successful scrap.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageState {
+    /// Hex encoded SHA-256 of the page content
+    pub hash: String,
+    /// Unix timestamp (in seconds) of the last successful scrap
+    pub scraped_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CrawlState {
+    pages: HashMap<String, PageState>,
+    /// Unix timestamp (in seconds) of the start of the crawl, while it is unfinished
+    #[serde(default)]
+    crawl_started_at: Option<u64>,
+    /// The requests queued but not scraped yet (with their priority), while the crawl is
+    /// unfinished
+    #[serde(default, with = "pending_requests")]
+    pending: HashMap<PageRequest, i32>,
+}
+
+/// Pending requests are persisted as a list, JSON keys being strings only
+mod pending_requests {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::scrapable::PageRequest;
+
+    pub fn serialize<S: Serializer>(
+        pending: &HashMap<PageRequest, i32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(pending)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<PageRequest, i32>, D::Error> {
+        Ok(Vec::<(PageRequest, i32)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+/// A crawl state persisted across runs as a JSON file.
+#[derive(Debug)]
+pub struct StateStore {
+    path: PathBuf,
+    state: Mutex<CrawlState>,
+}
+
+impl StateStore {
+    /// Loads the state from `path`, an empty state is used if the file doesn't exist.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let state = match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => CrawlState::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn get(&self, url: &str) -> Option<PageState> {
+        self.state.lock().unwrap().pages.get(url).cloned()
+    }
+
+    pub fn record(&self, url: &str, hash: String) {
+        let page = PageState {
+            hash,
+            scraped_at: unix_now(),
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .pages
+            .insert(url.to_string(), page);
+    }
+
+    /// The start of the last crawl if it didn't finish (for instance if it was
+    /// interrupted), the pages scraped since then don't need to be scraped again.
+    pub fn unfinished_crawl(&self) -> Option<u64> {
+        self.state.lock().unwrap().crawl_started_at
+    }
+
+    /// Marks a crawl as started, either now or at the start of the crawl it resumes (whose
+    /// pending requests are kept).
+    pub fn start_crawl(&self, resumed: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        state.crawl_started_at = Some(resumed.unwrap_or_else(unix_now));
+        if resumed.is_none() {
+            state.pending.clear();
+        }
+    }
+
+    pub fn finish_crawl(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.crawl_started_at = None;
+        state.pending.clear();
+    }
+
+    /// The requests that were queued but not scraped yet, along with their priority.
+    pub fn pending(&self) -> Vec<(PageRequest, i32)> {
+        let state = self.state.lock().unwrap();
+        state
+            .pending
+            .iter()
+            .map(|(request, priority)| (request.clone(), *priority))
+            .collect()
+    }
+
+    /// Tracks a queued request until [`Self::remove_pending`] is called once it is done.
+    pub fn add_pending(&self, request: &PageRequest, priority: i32) {
+        let mut state = self.state.lock().unwrap();
+        // A retried request replaces the previous attempt, along with its retries
+        state.pending.remove(request);
+        state.pending.insert(request.clone(), priority);
+    }
+
+    pub fn remove_pending(&self, request: &PageRequest) {
+        self.state.lock().unwrap().pending.remove(request);
+    }
+
+    /// Writes the state to disk, going through a temporary file so that an
+    /// interruption doesn't leave a truncated state behind.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let bytes = serde_json::to_vec(&*self.state.lock().unwrap())?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,112 @@
+//! Test server and scrapers shared by the integration tests, each of them using only
+//! some of them.
+#![allow(dead_code)]
+
+use sws_crawler::{CrawlingContext, Scrapable, ScrapingContext, Seed};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Serves the content type and body given by `handler` for the base URL of the server
+/// and the requested path, returns the base URL
+pub async fn serve<H>(handler: H) -> String
+where
+    H: Fn(&str, &str) -> (&'static str, String) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let base_c = base.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let mut n = 0;
+            let header_end = loop {
+                if let Some(end) = buf[..n].windows(4).position(|w| w == b"\r\n\r\n") {
+                    break end + 4;
+                }
+                match stream.read(&mut buf[n..]).await.unwrap() {
+                    0 => break n,
+                    read => n += read,
+                }
+            };
+            let request = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+            // The body is read as well, so that the connection is closed cleanly
+            let content_length = request
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or_default();
+            while n < header_end + content_length {
+                match stream.read(&mut buf[n..]).await.unwrap() {
+                    0 => break,
+                    read => n += read,
+                }
+            }
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+            let (content_type, body) = handler(&base_c, path);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    base
+}
+
+/// The seed of an [`UnreachableScraper`], nothing listening on its port
+#[derive(Debug, Clone)]
+pub enum Unreachable {
+    RobotsTxt,
+    Page,
+}
+
+pub struct UnreachableScraper(Unreachable);
+
+impl Scrapable for UnreachableScraper {
+    type Config = Unreachable;
+    fn new(config: &Self::Config) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(config.clone()))
+    }
+    fn seed(&self) -> Seed {
+        match self.0 {
+            Unreachable::RobotsTxt => Seed::RobotsTxt("http://127.0.0.1:1/robots.txt".into()),
+            Unreachable::Page => Seed::Pages(vec!["http://127.0.0.1:1/page".into()]),
+        }
+    }
+    fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
+        true
+    }
+    fn scrap(&mut self, _page: String, _ctx: ScrapingContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Asks for the page of the base URL to be retried, up to the given number of times
+pub struct RetryingScraper(String, usize);
+
+impl Scrapable for RetryingScraper {
+    type Config = (String, usize);
+    fn new((base, retries): &Self::Config) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(base.clone(), *retries))
+    }
+    fn seed(&self) -> Seed {
+        Seed::Pages(vec![format!("{}/page", self.0)])
+    }
+    fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
+        true
+    }
+    fn scrap(&mut self, _page: String, ctx: ScrapingContext) -> anyhow::Result<()> {
+        if ctx.request().unwrap().retries < self.1 {
+            ctx.retry("again");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{RetryingScraper, Unreachable, UnreachableScraper};
+use sws_crawler::{crawl_site, CrawlPhase, CrawlerConfig, ErrorObserver};
+
+#[tokio::test]
+async fn observe_robots_error() {
+    let observed = Arc::new(Mutex::new(vec![]));
+    let observed_c = observed.clone();
+    let crawler_conf = CrawlerConfig {
+        on_error: Some(ErrorObserver(Arc::new(move |e| {
+            observed_c
+                .lock()
+                .unwrap()
+                .push((e.url.to_string(), e.phase, e.attempt));
+        }))),
+        ..Default::default()
+    };
+
+    let res = crawl_site::<UnreachableScraper>(&crawler_conf, &Unreachable::RobotsTxt).await;
+    assert!(res.is_err());
+    assert_eq!(
+        *observed.lock().unwrap(),
+        [(
+            "http://127.0.0.1:1/robots.txt".to_string(),
+            CrawlPhase::Robots,
+            1
+        )]
+    );
+}
+
+#[tokio::test]
+async fn observe_scrap_error_attempt() {
+    let base = common::serve(|_, _| ("text/html", "<html></html>".into())).await;
+    let observed = Arc::new(Mutex::new(vec![]));
+    let observed_c = observed.clone();
+    let crawler_conf = CrawlerConfig {
+        max_scrap_retries: 2,
+        on_error: Some(ErrorObserver(Arc::new(move |e| {
+            observed_c
+                .lock()
+                .unwrap()
+                .push((e.url.to_string(), e.phase, e.attempt));
+        }))),
+        ..Default::default()
+    };
+
+    crawl_site::<RetryingScraper>(&crawler_conf, &(base.clone(), usize::MAX))
+        .await
+        .unwrap();
+    assert_eq!(
+        *observed.lock().unwrap(),
+        [(format!("{base}/page"), CrawlPhase::Scrap, 3)]
+    );
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{RetryingScraper, Unreachable, UnreachableScraper};
+use sws_crawler::{crawl_site, CrawlEvent, CrawlerConfig, EventObserver, OnError};
+
+#[tokio::test]
+async fn observe_discovered_page() {
+    let observed = Arc::new(Mutex::new(vec![]));
+    let observed_c = observed.clone();
+    let crawler_conf = CrawlerConfig {
+        on_dl_error: OnError::SkipAndLog,
+        on_event: Some(EventObserver(Arc::new(move |e| {
+            let e = match e {
+                CrawlEvent::Discovered(url) => ("discovered", url.to_string()),
+                CrawlEvent::Downloaded(url) => ("downloaded", url.to_string()),
+                CrawlEvent::Scraped(url) => ("scraped", url.to_string()),
+                CrawlEvent::Planned(url) => ("planned", url.to_string()),
+            };
+            observed_c.lock().unwrap().push(e);
+        }))),
+        ..Default::default()
+    };
+
+    crawl_site::<UnreachableScraper>(&crawler_conf, &Unreachable::Page)
+        .await
+        .unwrap();
+    assert_eq!(
+        *observed.lock().unwrap(),
+        [("discovered", "http://127.0.0.1:1/page".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn dry_run_plans_pages() {
+    let observed = Arc::new(Mutex::new(vec![]));
+    let observed_c = observed.clone();
+    let crawler_conf = CrawlerConfig {
+        on_dl_error: OnError::Fail,
+        on_event: Some(EventObserver(Arc::new(move |e| {
+            if let CrawlEvent::Planned(url) = e {
+                observed_c.lock().unwrap().push(url.to_string());
+            }
+        }))),
+        dry_run: true,
+        ..Default::default()
+    };
+
+    crawl_site::<UnreachableScraper>(&crawler_conf, &Unreachable::Page)
+        .await
+        .unwrap();
+    assert_eq!(*observed.lock().unwrap(), ["http://127.0.0.1:1/page"]);
+}
+
+#[tokio::test]
+async fn scraped_once_when_retried() {
+    let base = common::serve(|_, _| ("text/html", "<html></html>".into())).await;
+    let observed = Arc::new(Mutex::new(vec![]));
+    let observed_c = observed.clone();
+    let crawler_conf = CrawlerConfig {
+        on_event: Some(EventObserver(Arc::new(move |e| {
+            if let CrawlEvent::Scraped(url) = e {
+                observed_c.lock().unwrap().push(url.to_string());
+            }
+        }))),
+        ..Default::default()
+    };
+
+    crawl_site::<RetryingScraper>(&crawler_conf, &(base.clone(), 1))
+        .await
+        .unwrap();
+    assert_eq!(*observed.lock().unwrap(), [format!("{base}/page")]);
+}
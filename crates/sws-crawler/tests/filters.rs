@@ -0,0 +1,73 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use sws_crawler::{
+    crawl_site, CrawlEvent, CrawlerConfig, CrawlingContext, EventObserver, Scrapable,
+    ScrapingContext, Seed, UrlFilters,
+};
+
+/// A sitemap index whose child sitemap lists a product page
+fn sitemaps(base: &str, path: &str) -> (&'static str, String) {
+    let body = match path {
+        "/sitemap.xml" => format!(
+            r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sitemap><loc>{base}/sitemap-1.xml</loc></sitemap>
+            </sitemapindex>"#
+        ),
+        "/sitemap-1.xml" => format!(
+            r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url><loc>{base}/products/1</loc></url>
+                <url><loc>{base}/about</loc></url>
+            </urlset>"#
+        ),
+        _ => String::new(),
+    };
+    ("application/xml", body)
+}
+
+struct SitemapScraper(String);
+
+impl Scrapable for SitemapScraper {
+    type Config = String;
+    fn new(config: &Self::Config) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(config.clone()))
+    }
+    fn seed(&self) -> Seed {
+        Seed::Sitemaps(vec![format!("{}/sitemap.xml", self.0)])
+    }
+    fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
+        true
+    }
+    fn scrap(&mut self, _page: String, _ctx: ScrapingContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn filters_only_apply_to_pages() {
+    let base = common::serve(sitemaps).await;
+    let planned = Arc::new(Mutex::new(vec![]));
+    let planned_c = planned.clone();
+    let crawler_conf = CrawlerConfig {
+        url_filters: UrlFilters {
+            accept_globs: vec![format!("{base}/products/**")],
+            ..Default::default()
+        },
+        on_event: Some(EventObserver(Arc::new(move |e| {
+            if let CrawlEvent::Planned(url) = e {
+                planned_c.lock().unwrap().push(url.to_string());
+            }
+        }))),
+        dry_run: true,
+        ..Default::default()
+    };
+
+    crawl_site::<SitemapScraper>(&crawler_conf, &base)
+        .await
+        .unwrap();
+    assert_eq!(*planned.lock().unwrap(), [format!("{base}/products/1")]);
+}
@@ -0,0 +1,61 @@
+use std::sync::{Arc, Mutex};
+
+use sws_crawler::serde_json::{json, Value};
+use sws_crawler::{crawl_site, CrawlerConfig, CrawlingContext, Scrapable, ScrapingContext, Seed};
+
+/// Records the calls to its hooks
+struct HookScraper(Arc<Mutex<Vec<&'static str>>>);
+
+impl Scrapable for HookScraper {
+    type Config = Arc<Mutex<Vec<&'static str>>>;
+    fn new(config: &Self::Config) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(config.clone()))
+    }
+    fn seed(&self) -> Seed {
+        Seed::Pages(vec![])
+    }
+    fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
+        true
+    }
+    fn scrap(&mut self, _page: String, _ctx: ScrapingContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn init(&mut self, ctx: ScrapingContext) -> anyhow::Result<()> {
+        assert!(ctx.location().is_none());
+        assert!(ctx.worker_scratch_dir().is_some());
+        self.0.lock().unwrap().push("init");
+        Ok(())
+    }
+    fn finish(&mut self, _ctx: ScrapingContext) -> anyhow::Result<Option<Value>> {
+        self.0.lock().unwrap().push("finish");
+        Ok(Some(json!({ "pages": 0 })))
+    }
+    fn finalize(&mut self, results: Vec<Value>, _ctx: ScrapingContext) -> anyhow::Result<()> {
+        assert_eq!(results, vec![json!({ "pages": 0 }); 3]);
+        self.0.lock().unwrap().push("finalize");
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn hooks_once_per_worker() {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let crawler_conf = CrawlerConfig {
+        num_workers: 3,
+        ..Default::default()
+    };
+
+    crawl_site::<HookScraper>(&crawler_conf, &calls)
+        .await
+        .unwrap();
+    let mut calls = calls.lock().unwrap().clone();
+    assert_eq!(calls.pop(), Some("finalize"));
+    calls.sort();
+    assert_eq!(
+        calls,
+        ["finish", "finish", "finish", "init", "init", "init"]
+    );
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use sws_crawler::{
+    crawl_site, CrawlerConfig, CrawlingContext, PageRequest, Scrapable, ScrapingContext, Seed,
+};
+
+/// Seeds the same URL twice, queried with different bodies
+struct SearchScraper(String);
+
+impl Scrapable for SearchScraper {
+    type Config = String;
+    fn new(config: &Self::Config) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(config.clone()))
+    }
+    fn seed(&self) -> Seed {
+        let search = |query: &str| PageRequest {
+            method: "POST".to_string(),
+            body: Some(format!("q={query}")),
+            ..PageRequest::from(format!("{}/search", self.0))
+        };
+        Seed::Requests(vec![search("books"), search("games")])
+    }
+    fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
+        true
+    }
+    fn scrap(&mut self, _page: String, _ctx: ScrapingContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn saved_pages_of_requests() {
+    let base = common::serve(|_, _| ("text/html", "<html></html>".to_string())).await;
+    let dir = tempfile::tempdir().unwrap();
+    let crawler_conf = CrawlerConfig {
+        save_pages_dir: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    crawl_site::<SearchScraper>(&crawler_conf, &base)
+        .await
+        .unwrap();
+    // Pages are all written once the crawl returns, each request having its own file
+    let saved = std::fs::read_dir(dir.path()).unwrap().count();
+    assert_eq!(saved, 2);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use sws_crawler::{
+    crawl_site, CrawlerConfig, CrawlingContext, OnError, Scrapable, ScrapingContext, Seed,
+    StateStore,
+};
+
+#[test]
+fn state_roundtrip() {
+    let path = std::env::temp_dir().join(format!("sws-state-{}.json", std::process::id()));
+
+    let store = StateStore::open(&path).unwrap();
+    assert!(store.get("https://dummy-url.com").is_none());
+    store.record("https://dummy-url.com", "abc".into());
+    store.save().unwrap();
+
+    let store = StateStore::open(&path).unwrap();
+    assert_eq!(store.get("https://dummy-url.com").unwrap().hash, "abc");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn state_unfinished_crawl() {
+    let path = std::env::temp_dir().join(format!("sws-crawl-{}.json", std::process::id()));
+
+    let store = StateStore::open(&path).unwrap();
+    assert!(store.unfinished_crawl().is_none());
+    store.start_crawl(None);
+    store.save().unwrap();
+
+    let store = StateStore::open(&path).unwrap();
+    let started_at = store.unfinished_crawl().unwrap();
+    store.start_crawl(Some(started_at));
+    assert_eq!(store.unfinished_crawl(), Some(started_at));
+    store.finish_crawl();
+    store.save().unwrap();
+
+    let store = StateStore::open(&path).unwrap();
+    assert!(store.unfinished_crawl().is_none());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Follows the link of each page (its body), failing on the page `fail_on` if any
+#[derive(Clone)]
+struct FollowConfig {
+    base: String,
+    fail_on: Option<String>,
+    scraped: Arc<Mutex<Vec<String>>>,
+}
+
+struct FollowingScraper(FollowConfig);
+
+impl Scrapable for FollowingScraper {
+    type Config = FollowConfig;
+    fn new(config: &Self::Config) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self(config.clone()))
+    }
+    fn seed(&self) -> Seed {
+        Seed::Pages(vec![format!("{}/1", self.0.base)])
+    }
+    fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
+        true
+    }
+    fn scrap(&mut self, page: String, ctx: ScrapingContext) -> anyhow::Result<()> {
+        let url = ctx.request().unwrap().url.clone();
+        if self.0.fail_on.as_ref() == Some(&url) {
+            anyhow::bail!("Failing on {url}");
+        }
+        self.0.scraped.lock().unwrap().push(url);
+        if !page.is_empty() {
+            ctx.tx_url().unwrap().send(format!("{}{page}", self.0.base));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn resume_followed_links() {
+    let path = std::env::temp_dir().join(format!("sws-resume-{}.json", std::process::id()));
+    let base = common::serve(|_, path| {
+        let next = match path {
+            "/1" => "/2",
+            "/2" => "/3",
+            _ => "",
+        };
+        ("text/html", next.into())
+    })
+    .await;
+    let crawler_conf = CrawlerConfig {
+        on_scrap_error: OnError::Fail,
+        state_file: Some(path.clone()),
+        resume: true,
+        ..Default::default()
+    };
+
+    // The crawl fails once the first page is scraped, its link being still pending
+    let config = FollowConfig {
+        base: base.clone(),
+        fail_on: Some(format!("{base}/2")),
+        scraped: Arc::default(),
+    };
+    let res = crawl_site::<FollowingScraper>(&crawler_conf, &config).await;
+    assert!(res.is_err());
+    assert_eq!(*config.scraped.lock().unwrap(), [format!("{base}/1")]);
+
+    // The seed is not scraped again, but the pages it led to are
+    let config = FollowConfig {
+        fail_on: None,
+        scraped: Arc::default(),
+        ..config
+    };
+    crawl_site::<FollowingScraper>(&crawler_conf, &config)
+        .await
+        .unwrap();
+    assert_eq!(
+        *config.scraped.lock().unwrap(),
+        [format!("{base}/2"), format!("{base}/3")]
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
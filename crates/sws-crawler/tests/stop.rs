@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use sws_crawler::{
+    crawl_site, CrawlerConfig, CrawlingContext, Scrapable, ScrapingContext, Seed, SeedGenerator,
+};
+
+/// Seeds unreachable pages forever, and stops the crawl right away
+struct StoppingScraper;
+
+impl Scrapable for StoppingScraper {
+    type Config = ();
+    fn new(_config: &Self::Config) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self)
+    }
+    fn seed(&self) -> Seed {
+        let mut i = 0;
+        Seed::Generator(SeedGenerator::new(move || {
+            i += 1;
+            Some(format!("http://127.0.0.1:1/{i}"))
+        }))
+    }
+    fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
+        true
+    }
+    fn scrap(&mut self, _page: String, _ctx: ScrapingContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn init(&mut self, ctx: ScrapingContext) -> anyhow::Result<()> {
+        ctx.crawl_stop().unwrap().stop("done");
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn stop_endless_crawl() {
+    let crawler_conf = CrawlerConfig::default();
+    let crawl = crawl_site::<StoppingScraper>(&crawler_conf, &());
+    tokio::time::timeout(Duration::from_secs(10), crawl)
+        .await
+        .expect("Crawl was not stopped")
+        .unwrap();
+}
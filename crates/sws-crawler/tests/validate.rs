@@ -1,31 +1,33 @@
-use sws_crawler::{crawl_site, CrawlerConfig, CrawlingContext, Scrapable, ScrapingContext, Seed};
+use sws_crawler::{
+    crawl_site, CrawlerConfig, CrawlingContext, Revisit, Scrapable, ScrapingContext, Seed,
+};
+
+struct DummyScraper;
+
+impl Scrapable for DummyScraper {
+    type Config = ();
+    fn new(_config: &Self::Config) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self)
+    }
+    fn seed(&self) -> Seed {
+        sws_crawler::Seed::RobotsTxt("https://dummy-url.com/robots.txt".into())
+    }
+    fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
+        true
+    }
+    fn scrap(&mut self, _page: String, _ctx: ScrapingContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
 
 #[tokio::test]
 #[should_panic(
     expected = "Invalid seed config, cannot use Seed::RobotsTxt when `crawler_conf.robot` is defined"
 )]
 async fn validate_robot_config() {
-    struct DummyScraper;
-
-    impl Scrapable for DummyScraper {
-        type Config = ();
-        fn new(_config: &Self::Config) -> anyhow::Result<Self>
-        where
-            Self: Sized,
-        {
-            Ok(Self)
-        }
-        fn seed(&self) -> Seed {
-            sws_crawler::Seed::RobotsTxt("https://dummy-url.com/robots.txt".into())
-        }
-        fn accept(&self, _url: &str, _ctx: CrawlingContext) -> bool {
-            true
-        }
-        fn scrap(&mut self, _page: String, _ctx: ScrapingContext) -> anyhow::Result<()> {
-            Ok(())
-        }
-    }
-
     let scraper_conf = ();
     let crawler_conf = CrawlerConfig {
         robot: Some("https://dummy-url.com/robots.txt".into()),
@@ -36,3 +38,47 @@ async fn validate_robot_config() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+#[should_panic(
+    expected = "Invalid revisit config, cannot use Revisit::IfModified when `crawler_conf.state_file` is not defined"
+)]
+async fn validate_revisit_config() {
+    let scraper_conf = ();
+    let crawler_conf = CrawlerConfig {
+        revisit: Revisit::IfModified,
+        ..Default::default()
+    };
+
+    crawl_site::<DummyScraper>(&crawler_conf, &scraper_conf)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[should_panic(expected = "Crawler max_pages must be > 0")]
+async fn validate_max_pages_config() {
+    let scraper_conf = ();
+    let crawler_conf = CrawlerConfig {
+        max_pages: Some(0),
+        ..Default::default()
+    };
+
+    crawl_site::<DummyScraper>(&crawler_conf, &scraper_conf)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[should_panic(expected = "Crawler max_duration must be > 0.0")]
+async fn validate_max_duration_config() {
+    let scraper_conf = ();
+    let crawler_conf = CrawlerConfig {
+        max_duration: Some(0.0),
+        ..Default::default()
+    };
+
+    crawl_site::<DummyScraper>(&crawler_conf, &scraper_conf)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,134 @@
+//! Pages read from local files, possibly gzipped or stored in WARC archives.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_8};
+use flate2::read::MultiGzDecoder;
+use sws_crawler::PageLocation;
+
+/// Calls `scrap` with the page(s) of a local file:
+///
+/// * `.gz` files are decompressed
+/// * `.warc` (or `.warc.gz`) archives yield their successful HTML responses, located by
+///   their target URI
+/// * other files are a single page, located by their path
+pub(crate) fn for_each_page<F>(path: &Path, mut scrap: F) -> anyhow::Result<()>
+where
+    F: FnMut(String, PageLocation) -> anyhow::Result<()>,
+{
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let file = fs_err::File::open(path)?;
+    let mut reader: Box<dyn BufRead> = if name.ends_with(".gz") {
+        Box::new(BufReader::new(MultiGzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    if name.trim_end_matches(".gz").ends_with(".warc") {
+        while let Some(record) = read_record(&mut reader)? {
+            if let Some((url, page)) = record.html_response() {
+                scrap(page, PageLocation::Url(url))?;
+            }
+        }
+        Ok(())
+    } else {
+        let mut page = String::new();
+        reader.read_to_string(&mut page)?;
+        scrap(page, PageLocation::Path(path.to_path_buf()))
+    }
+}
+
+/// A WARC record, its header names are lowercased.
+struct Record {
+    headers: HashMap<String, String>,
+    block: Vec<u8>,
+}
+
+impl Record {
+    /// The target URI and the decoded body of a successful HTML response record.
+    fn html_response(&self) -> Option<(String, String)> {
+        if self.headers.get("warc-type")? != "response" {
+            return None;
+        }
+        let url = self.headers.get("warc-target-uri")?;
+        // WARC 1.0 writers commonly enclose the URI in angle brackets
+        let url = url
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_string();
+
+        let head_len = self.block.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let head = String::from_utf8_lossy(&self.block[..head_len]);
+        let body = &self.block[head_len + 4..];
+
+        let mut lines = head.lines();
+        let status = lines.next()?.split_whitespace().nth(1)?;
+        if !status.starts_with('2') {
+            return None;
+        }
+        let content_type = lines
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.trim().to_ascii_lowercase());
+        if content_type.as_ref().is_some_and(|ct| !ct.contains("html")) {
+            return None;
+        }
+
+        let encoding = content_type
+            .as_deref()
+            .and_then(|ct| {
+                ct.split(';')
+                    .find_map(|param| param.trim().strip_prefix("charset="))
+            })
+            .and_then(|charset| Encoding::for_label(charset.trim_matches('"').as_bytes()))
+            .unwrap_or(UTF_8);
+        let (page, _, _) = encoding.decode(body);
+        Some((url, page.into_owned()))
+    }
+}
+
+/// Reads the next WARC record, `None` once the archive is exhausted.
+fn read_record(reader: &mut impl BufRead) -> anyhow::Result<Option<Record>> {
+    // Records are separated by blank lines
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.trim().is_empty() {
+            break;
+        }
+    }
+    if !line.starts_with("WARC/") {
+        anyhow::bail!("Invalid WARC record start {:?}", line.trim_end());
+    }
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("Truncated WARC record header");
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let len = headers
+        .get("content-length")
+        .ok_or_else(|| anyhow::anyhow!("WARC record without Content-Length"))?
+        .parse::<usize>()?;
+    let mut block = vec![0; len];
+    reader.read_exact(&mut block)?;
+    Ok(Some(Record { headers, block }))
+}
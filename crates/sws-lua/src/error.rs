@@ -0,0 +1,60 @@
+use std::fmt;
+
+const TRACEBACK_HEADER: &str = "stack traceback:";
+
+/// An error raised by a function of the Lua script, with the Lua traceback (if any) kept
+/// apart from the message.
+#[derive(Debug)]
+pub struct ScriptError {
+    function: String,
+    message: String,
+    traceback: Option<String>,
+}
+
+impl ScriptError {
+    pub(crate) fn new(function: &str, error: mlua::Error) -> Self {
+        let error = error.to_string();
+        let (message, traceback) = match error.split_once(TRACEBACK_HEADER) {
+            Some((message, traceback)) => (
+                message.trim_end(),
+                Some(format!("{TRACEBACK_HEADER}{}", traceback.trim_end())),
+            ),
+            None => (error.trim_end(), None),
+        };
+        Self {
+            function: function.to_string(),
+            message: message.to_string(),
+            traceback,
+        }
+    }
+
+    /// The name of the Lua function that failed
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The Lua stack traceback, with the script's line numbers
+    pub fn traceback(&self) -> Option<&str> {
+        self.traceback.as_deref()
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Couldn't run function {}: {}",
+            self.function, self.message
+        )?;
+        if let Some(traceback) = &self.traceback {
+            write!(f, "\n{traceback}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ScriptError {}
@@ -0,0 +1,57 @@
+//! Interactive exploration of a page with CSS selectors and Lua snippets, to shorten the
+//! loop of writing a scraping script.
+
+use anyhow::anyhow;
+use mlua::{Function, Lua};
+use sws_scraper::{Html, Selector};
+
+use crate::interop::LuaHtml;
+use crate::scraper::{setup_lua, LuaScraperConfig};
+
+/// The global name of the inspected page in Lua snippets
+pub const PAGE: &str = "page";
+
+pub struct PageInspector {
+    lua: Lua,
+    html: Html,
+}
+
+impl PageInspector {
+    /// Parses `page`, which Lua snippets (evaluated with the `sws` namespace) can access
+    /// as the global `page`.
+    pub fn new(config: &LuaScraperConfig, page: &str) -> anyhow::Result<Self> {
+        let lua = Lua::new();
+        setup_lua(&lua, config)?;
+        let html = Html::parse_document(page);
+        lua.globals().set(PAGE, LuaHtml(html.clone()))?;
+        Ok(Self { lua, html })
+    }
+
+    /// The HTML of the elements matching `css_selector`.
+    pub fn select(&self, css_selector: &str) -> anyhow::Result<Vec<String>> {
+        let selector = Selector::parse(css_selector)
+            .map_err(|e| anyhow!("Invalid CSS selector {css_selector:?}: {e:?}"))?;
+        Ok(self.html.select(selector).map(|elem| elem.html()).collect())
+    }
+
+    /// Runs a Lua snippet, either an expression or statements, and returns the string
+    /// representations of the values it evaluates to.
+    pub fn eval(&self, code: &str) -> anyhow::Result<Vec<String>> {
+        let chunk = match self
+            .lua
+            .load(format!("return {code}"))
+            .set_name("=shell")
+            .into_function()
+        {
+            Ok(chunk) => chunk,
+            Err(_) => self.lua.load(code).set_name("=shell").into_function()?,
+        };
+        let tostring: Function = self.lua.globals().get("tostring")?;
+        let values = chunk
+            .call::<_, mlua::MultiValue>(())?
+            .into_iter()
+            .map(|value| tostring.call::<_, String>(value))
+            .collect::<mlua::Result<_>>()?;
+        Ok(values)
+    }
+}
@@ -1,15 +1,24 @@
+use std::collections::HashSet;
+use std::path::Path;
 use std::rc::{Rc, Weak};
 use std::sync::Arc;
 use std::{fs, thread};
 
 use crossbeam_channel::Sender;
-use mlua::{FromLua, MetaMethod, UserData, UserDataMethods};
-use sws_crawler::{CountedTx, CrawlingContext, PageLocation, ScrapingContext, Sitemap};
+use mlua::{FromLua, LuaSerdeExt, MetaMethod, UserData, UserDataMethods};
+use sws_crawler::reqwest::header::HeaderMap;
+use sws_crawler::{
+    CountedTx, CrawlStop, CrawlingContext, FetchInfo, Fetcher, OnError, OutcomeReporter,
+    PageLocation, PageRequest, ScrapingContext, Sitemap,
+};
 use sws_scraper::CaseSensitivity;
 use sws_scraper::{element_ref::Select, ElementRef, Html, Selector};
 use texting_robots::Robot;
 
 use crate::ns::{globals, sws};
+use crate::profile::{timed, timed_resumed, SELECTOR};
+use crate::scraper::SharedKv;
+use crate::writer::{OutputRecord, Record, Schema};
 
 pub struct LuaHtml(pub(crate) Html);
 
@@ -19,35 +28,74 @@ impl UserData for LuaHtml {
             Ok(format!("{:?}", html.0))
         });
 
-        methods.add_method(sws::html::SELECT, |_, html, css_selector: String| {
-            let select = html.0.select(Selector::parse(&css_selector).map_err(|e| {
-                mlua::Error::RuntimeError(format!(
-                    "Invalid CSS selector {:?}: {:?}",
-                    css_selector, e
-                ))
-            })?);
-            Ok(LuaSelect(select))
+        methods.add_method(sws::html::SELECT, |lua, html, css_selector: String| {
+            let select = timed(lua, SELECTOR, &css_selector, || {
+                Ok::<_, mlua::Error>(html.0.select(parse_selector(&css_selector)?))
+            })?;
+            Ok(LuaSelect {
+                select,
+                css_selector: css_selector.into(),
+            })
         });
 
+        methods.add_method(
+            sws::html::SELECT_FIRST,
+            |lua, html, css_selector: String| {
+                timed(lua, SELECTOR, &css_selector, || {
+                    let mut select = html.0.select(parse_selector(&css_selector)?);
+                    Ok(select.next().map(LuaElementRef))
+                })
+            },
+        );
+
         methods.add_method(sws::html::ROOT, |_, html, ()| {
             Ok(LuaElementRef(html.0.root_element()))
         });
+
+        methods.add_method(sws::html::META, |lua, html, ()| {
+            let meta = lua.create_table()?;
+            for elem in html.0.select(parse_selector("meta[content]")?) {
+                let entry = elem.map_value(|el| {
+                    let key = el.attr("property").or_else(|| el.attr("name"))?;
+                    Some((key.to_string(), el.attr("content")?.to_string()))
+                });
+                if let Some(Some((key, content))) = entry {
+                    // The first occurrence wins, as for og:image which can be repeated
+                    if !meta.contains_key(key.as_str())? {
+                        meta.set(key, content)?;
+                    }
+                }
+            }
+            Ok(meta)
+        });
     }
 }
 
+fn parse_selector(css_selector: &str) -> mlua::Result<Selector> {
+    Selector::parse(css_selector).map_err(|e| {
+        mlua::Error::RuntimeError(format!("Invalid CSS selector {:?}: {:?}", css_selector, e))
+    })
+}
+
 #[derive(Clone)]
-pub struct LuaSelect(pub(crate) Select);
+pub struct LuaSelect {
+    select: Select,
+    css_selector: Rc<str>,
+}
 
 impl UserData for LuaSelect {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_meta_method(MetaMethod::ToString, |_, sel, ()| {
-            Ok(format!("{:?}", sel.0))
+            Ok(format!("{:?}", sel.select))
         });
 
         methods.add_method(sws::select::ITER, |lua, select, ()| {
             let mut select = select.clone();
-            let iterator =
-                lua.create_function_mut(move |_, ()| Ok(select.0.next().map(LuaElementRef)));
+            let iterator = lua.create_function_mut(move |lua, ()| {
+                let next =
+                    timed_resumed(lua, SELECTOR, &select.css_selector, || select.select.next());
+                Ok(next.map(LuaElementRef))
+            });
 
             Ok(iterator)
         });
@@ -55,9 +103,11 @@ impl UserData for LuaSelect {
         methods.add_method(sws::select::ENUMERATE, |lua, select, ()| {
             let mut select = select.clone();
             let mut i = 0;
-            let iterator = lua.create_function_mut(move |_, ()| {
+            let iterator = lua.create_function_mut(move |lua, ()| {
                 i += 1;
-                let next = select.0.next().map(LuaElementRef);
+                let next =
+                    timed_resumed(lua, SELECTOR, &select.css_selector, || select.select.next())
+                        .map(LuaElementRef);
                 if next.is_some() {
                     Ok((Some(i), next))
                 } else {
@@ -77,16 +127,41 @@ impl UserData for LuaElementRef {
             Ok(format!("{:?}", elem.0))
         });
 
-        methods.add_method(sws::elem_ref::SELECT, |_, elem, css_selector: String| {
-            let select = elem.0.select(Selector::parse(&css_selector).map_err(|e| {
-                mlua::Error::RuntimeError(format!(
-                    "Invalid CSS selector {:?}: {:?}",
-                    css_selector, e
-                ))
-            })?);
-            Ok(LuaSelect(select))
+        methods.add_method(sws::elem_ref::SELECT, |lua, elem, css_selector: String| {
+            let select = timed(lua, SELECTOR, &css_selector, || {
+                Ok::<_, mlua::Error>(elem.0.select(parse_selector(&css_selector)?))
+            })?;
+            Ok(LuaSelect {
+                select,
+                css_selector: css_selector.into(),
+            })
         });
 
+        methods.add_method(
+            sws::elem_ref::SELECT_FIRST,
+            |lua, elem, css_selector: String| {
+                timed(lua, SELECTOR, &css_selector, || {
+                    let mut select = elem.0.select(parse_selector(&css_selector)?);
+                    Ok(select.next().map(LuaElementRef))
+                })
+            },
+        );
+
+        methods.add_method(sws::elem_ref::MATCHES, |lua, elem, css_selector: String| {
+            timed(lua, SELECTOR, &css_selector, || {
+                Ok(parse_selector(&css_selector)?.matches(&elem.0))
+            })
+        });
+
+        methods.add_method(sws::elem_ref::CLOSEST, |lua, elem, css_selector: String| {
+            timed(lua, SELECTOR, &css_selector, || {
+                let closest = elem.0.closest(&parse_selector(&css_selector)?);
+                Ok(closest.map(LuaElementRef))
+            })
+        });
+
+        methods.add_method(sws::elem_ref::HTML, |_, elem, ()| Ok(elem.0.html()));
+
         methods.add_method(sws::elem_ref::INNER_HTML, |_, elem, ()| {
             Ok(elem.0.inner_html())
         });
@@ -95,6 +170,13 @@ impl UserData for LuaElementRef {
             Ok(elem.0.inner_text())
         });
 
+        methods.add_method(sws::elem_ref::TEXTS, |lua, elem, ()| {
+            let mut texts = elem.0.text();
+            lua.create_function_mut(move |_, ()| Ok(texts.next()))
+        });
+
+        methods.add_method(sws::elem_ref::OWN_TEXT, |_, elem, ()| Ok(elem.0.own_text()));
+
         methods.add_method(sws::elem_ref::NAME, |_, elem, ()| {
             Ok(elem.0.map_value(|el| el.name().to_string()))
         });
@@ -142,14 +224,30 @@ impl UserData for LuaElementRef {
 }
 
 #[derive(Debug)]
-pub struct LuaPageLocation(pub(crate) Weak<PageLocation>);
+pub struct LuaPageLocation {
+    location: Weak<PageLocation>,
+    /// The directory of the glob pattern the page's file was found with
+    glob_base: Option<Rc<Path>>,
+}
+
+impl LuaPageLocation {
+    /// The metadata of the page's file, `None` for a `Location.URL`
+    fn metadata(&self) -> mlua::Result<Option<fs::Metadata>> {
+        match self.location.upgrade().as_deref() {
+            Some(PageLocation::Path(p)) => Ok(Some(fs::metadata(p)?)),
+            _ => Ok(None),
+        }
+    }
+}
 
 impl UserData for LuaPageLocation {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_meta_method(MetaMethod::ToString, |_, pl, ()| Ok(format!("{:?}", pl.0)));
+        methods.add_meta_method(MetaMethod::ToString, |_, pl, ()| {
+            Ok(format!("{:?}", pl.location))
+        });
 
         methods.add_method(sws::page_location::KIND, |lua, pl, ()| {
-            if let Some(loc) = pl.0.upgrade() {
+            if let Some(loc) = pl.location.upgrade() {
                 let location = match loc.as_ref() {
                     PageLocation::Path(_) => sws::location::PATH,
                     PageLocation::Url(_) => sws::location::URL,
@@ -165,7 +263,7 @@ impl UserData for LuaPageLocation {
         });
 
         methods.add_method(sws::page_location::GET, |_, pl, ()| {
-            if let Some(loc) = pl.0.upgrade() {
+            if let Some(loc) = pl.location.upgrade() {
                 let loc = match loc.as_ref() {
                     PageLocation::Path(p) => format!("{}", fs::canonicalize(p)?.display()),
                     PageLocation::Url(url) => url.to_string(),
@@ -175,11 +273,33 @@ impl UserData for LuaPageLocation {
                 Ok(None)
             }
         });
+
+        methods.add_method(sws::page_location::RELATIVE_PATH, |_, pl, ()| {
+            let (Some(loc), Some(base)) = (pl.location.upgrade(), &pl.glob_base) else {
+                return Ok(None);
+            };
+            let PageLocation::Path(p) = loc.as_ref() else {
+                return Ok(None);
+            };
+            Ok(p.strip_prefix(base).ok().map(|p| p.display().to_string()))
+        });
+
+        methods.add_method(sws::page_location::SIZE, |_, pl, ()| {
+            Ok(pl.metadata()?.map(|m| m.len()))
+        });
+
+        methods.add_method(sws::page_location::MODIFIED, |_, pl, ()| {
+            let Some(metadata) = pl.metadata()? else {
+                return Ok(None);
+            };
+            let modified = chrono::DateTime::<chrono::Utc>::from(metadata.modified()?);
+            Ok(Some(LuaDateTime(modified.fixed_offset())))
+        });
     }
 }
 
 #[derive(Clone, Default)]
-pub struct LuaStringRecord(pub(crate) csv::StringRecord);
+pub struct LuaStringRecord(pub(crate) Record);
 
 impl<'lua> FromLua<'lua> for LuaStringRecord {
     fn from_lua(value: mlua::Value<'lua>, _: &'lua mlua::Lua) -> mlua::Result<Self> {
@@ -195,12 +315,70 @@ impl UserData for LuaStringRecord {
         methods.add_meta_method(MetaMethod::ToString, |_, r, ()| Ok(format!("{:?}", r.0)));
 
         methods.add_method_mut(sws::record::PUSH_FIELD, |_, record, field: String| {
-            record.0.push_field(&field);
+            match &mut record.0 {
+                Record::Positional(record) => record.push_field(&field),
+                Record::Named(_) => {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "Cannot push field {field} to a record with named fields"
+                    )))
+                }
+            }
             Ok(())
         });
+
+        methods.add_method_mut(
+            sws::record::SET,
+            |_, record, (name, value): (String, String)| {
+                if let Record::Positional(positional) = &record.0 {
+                    if !positional.is_empty() {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            "Cannot set field {name} of a record with positional fields"
+                        )));
+                    }
+                    record.0 = Record::Named(vec![]);
+                }
+                if let Record::Named(fields) = &mut record.0 {
+                    match fields.iter_mut().find(|(field, _)| *field == name) {
+                        Some((_, v)) => *v = value,
+                        None => fields.push((name, value)),
+                    }
+                }
+                Ok(())
+            },
+        );
     }
 }
 
+/// Converts a table of field names to values (strings, numbers or booleans) into a record
+/// with named fields, sorted by name as tables are unordered.
+fn map_record<'lua>(lua: &'lua mlua::Lua, fields: mlua::Table<'lua>) -> mlua::Result<Record> {
+    let mut record = fields
+        .pairs::<String, mlua::Value>()
+        .map(|pair| {
+            let (name, value) = pair?;
+            let value = match value {
+                mlua::Value::Boolean(b) => b.to_string(),
+                value @ (mlua::Value::String(_)
+                | mlua::Value::Integer(_)
+                | mlua::Value::Number(_)) => lua
+                    .coerce_string(value)?
+                    .map(|s| s.to_str().map(String::from))
+                    .transpose()?
+                    .unwrap_or_default(),
+                value => {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "Invalid field {name}, expected a string, number or boolean got: {}",
+                        value.type_name()
+                    )))
+                }
+            };
+            Ok((name, value))
+        })
+        .collect::<mlua::Result<Vec<_>>>()?;
+    record.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(Record::Named(record))
+}
+
 pub struct LuaDate(pub(crate) chrono::NaiveDate);
 
 impl LuaDate {
@@ -221,6 +399,288 @@ impl UserData for LuaDate {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct LuaDateTime(pub(crate) chrono::DateTime<chrono::FixedOffset>);
+
+impl LuaDateTime {
+    /// Parses `dt` with the format `fmt` if any, otherwise as RFC 3339 (ISO 8601), RFC
+    /// 2822 or their variants without offset (in UTC).
+    pub fn parse(dt: &str, fmt: Option<&str>) -> mlua::Result<Self> {
+        use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+        let parsed = match fmt {
+            Some(fmt) => DateTime::parse_from_str(dt, fmt)
+                .or_else(|e| {
+                    NaiveDateTime::parse_from_str(dt, fmt)
+                        .or_else(|_| {
+                            NaiveDate::parse_from_str(dt, fmt)
+                                .map(|d| d.and_time(Default::default()))
+                        })
+                        .map(|dt| dt.and_utc().fixed_offset())
+                        .map_err(|_| e)
+                })
+                .map_err(|e| e.to_string()),
+            None => DateTime::parse_from_rfc3339(dt)
+                .or_else(|_| DateTime::parse_from_rfc2822(dt))
+                .or_else(|_| {
+                    dt.parse::<NaiveDateTime>()
+                        .or_else(|_| NaiveDateTime::parse_from_str(dt, "%Y-%m-%d %H:%M:%S%.f"))
+                        .map(|dt| dt.and_utc().fixed_offset())
+                })
+                .or_else(|_| {
+                    dt.parse::<NaiveDate>()
+                        .map(|d| d.and_time(Default::default()).and_utc().fixed_offset())
+                })
+                .map_err(|_| String::from("unknown format")),
+        };
+        let parsed = parsed.map_err(|e| {
+            mlua::Error::RuntimeError(format!("Couldn't parse date time {dt} got: {e}"))
+        })?;
+        Ok(Self(parsed))
+    }
+
+    /// The date time of a unix `timestamp` (in seconds), in UTC.
+    pub fn from_timestamp(timestamp: f64) -> mlua::Result<Self> {
+        let secs = timestamp.floor();
+        let nanos = ((timestamp - secs) * 1e9) as u32;
+        let dt = chrono::DateTime::from_timestamp(secs as i64, nanos)
+            .ok_or_else(|| mlua::Error::RuntimeError(format!("Invalid timestamp {timestamp}")))?;
+        Ok(Self(dt.fixed_offset()))
+    }
+}
+
+/// An offset such as `Z`, `+02:00`, `-0530` or a number of seconds east of UTC.
+fn parse_offset(offset: mlua::Value) -> mlua::Result<chrono::FixedOffset> {
+    let invalid = || {
+        let offset = offset.to_string().unwrap_or_default();
+        mlua::Error::RuntimeError(format!("Invalid offset {offset}"))
+    };
+    let secs = match &offset {
+        mlua::Value::Integer(secs) => *secs as i32,
+        mlua::Value::Number(secs) => *secs as i32,
+        mlua::Value::String(s) => match s.to_str()? {
+            "Z" | "UTC" => 0,
+            s => {
+                let (sign, hhmm) = match s.split_at_checked(1) {
+                    Some(("+", hhmm)) => (1, hhmm),
+                    Some(("-", hhmm)) => (-1, hhmm),
+                    _ => return Err(invalid()),
+                };
+                let hhmm = hhmm.replace(':', "");
+                let (hh, mm) = hhmm.split_at_checked(2).ok_or_else(invalid)?;
+                let hh: i32 = hh.parse().map_err(|_| invalid())?;
+                let mm: i32 = if mm.is_empty() {
+                    0
+                } else {
+                    mm.parse().map_err(|_| invalid())?
+                };
+                sign * (hh * 3600 + mm * 60)
+            }
+        },
+        _ => return Err(invalid()),
+    };
+    chrono::FixedOffset::east_opt(secs).ok_or_else(invalid)
+}
+
+impl UserData for LuaDateTime {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::ToString, |_, dt, ()| Ok(dt.0.to_rfc3339()));
+
+        methods.add_meta_method(MetaMethod::Eq, |_, dt, other: mlua::UserDataRef<Self>| {
+            Ok(dt.0 == other.0)
+        });
+
+        methods.add_meta_method(MetaMethod::Lt, |_, dt, other: mlua::UserDataRef<Self>| {
+            Ok(dt.0 < other.0)
+        });
+
+        methods.add_meta_method(MetaMethod::Le, |_, dt, other: mlua::UserDataRef<Self>| {
+            Ok(dt.0 <= other.0)
+        });
+
+        methods.add_method(sws::date_time::FORMAT, |_, dt, fmt: String| {
+            Ok(dt.0.format(&fmt).to_string())
+        });
+
+        methods.add_method(sws::date_time::TIMESTAMP, |_, dt, ()| Ok(dt.0.timestamp()));
+
+        methods.add_method(sws::date_time::OFFSET, |_, dt, ()| {
+            Ok(dt.0.offset().local_minus_utc())
+        });
+
+        methods.add_method(sws::date_time::WITH_OFFSET, |_, dt, offset: mlua::Value| {
+            Ok(Self(dt.0.with_timezone(&parse_offset(offset)?)))
+        });
+
+        methods.add_method(sws::date_time::ADD, |_, dt, secs: f64| {
+            let duration = chrono::Duration::nanoseconds((secs * 1e9) as i64);
+            let added = dt.0.checked_add_signed(duration).ok_or_else(|| {
+                mlua::Error::RuntimeError(format!("Couldn't add {secs} seconds to {}", dt.0))
+            })?;
+            Ok(Self(added))
+        });
+
+        methods.add_method(
+            sws::date_time::DIFF,
+            |_, dt, other: mlua::UserDataRef<Self>| {
+                let diff = dt.0.signed_duration_since(other.0);
+                Ok(diff.num_milliseconds() as f64 / 1e3)
+            },
+        );
+
+        methods.add_method(sws::date_time::DATE, |_, dt, ()| {
+            Ok(LuaDate(dt.0.date_naive()))
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LuaUrl(pub(crate) url::Url);
+
+impl LuaUrl {
+    pub fn new(url: &str) -> mlua::Result<Self> {
+        Ok(Self(url::Url::parse(url).map_err(|e| {
+            mlua::Error::RuntimeError(format!("Couldn't parse URL {url} got: {e}"))
+        })?))
+    }
+}
+
+impl UserData for LuaUrl {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::ToString, |_, u, ()| Ok(u.0.to_string()));
+
+        methods.add_method(sws::url::JOIN, |_, u, other: String| {
+            let url = u.0.join(&other).map_err(|e| {
+                mlua::Error::RuntimeError(format!("Couldn't join URL {other} got: {e}"))
+            })?;
+            Ok(Self(url))
+        });
+
+        methods.add_method(sws::url::HOST, |_, u, ()| {
+            Ok(u.0.host_str().map(String::from))
+        });
+
+        methods.add_method(sws::url::PATH, |_, u, ()| Ok(u.0.path().to_string()));
+
+        methods.add_method(sws::url::PARAM, |_, u, name: String| {
+            let value = u.0.query_pairs().find(|(k, _)| *k == name);
+            Ok(value.map(|(_, v)| v.into_owned()))
+        });
+
+        methods.add_method_mut(
+            sws::url::SET_PARAM,
+            |_, u, (name, value): (String, Option<String>)| {
+                let mut value = value;
+                let mut pairs = vec![];
+                for (k, v) in u.0.query_pairs() {
+                    if k != name {
+                        pairs.push((k.into_owned(), v.into_owned()));
+                    } else if let Some(value) = value.take() {
+                        pairs.push((name.clone(), value));
+                    }
+                }
+                if let Some(value) = value {
+                    pairs.push((name, value));
+                }
+                if pairs.is_empty() {
+                    u.0.set_query(None);
+                } else {
+                    u.0.query_pairs_mut().clear().extend_pairs(pairs);
+                }
+                Ok(())
+            },
+        );
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LuaRegex(pub(crate) regex::Regex);
+
+impl LuaRegex {
+    pub fn new(re: &str) -> mlua::Result<Self> {
+        Ok(Self(regex::Regex::new(re).map_err(|e| {
+            mlua::Error::RuntimeError(format!("Couldn't compile regex {re:?} got: {e}"))
+        })?))
+    }
+}
+
+impl UserData for LuaRegex {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::ToString, |_, re, ()| Ok(re.0.to_string()));
+
+        methods.add_method(sws::regex::MATCH, |_, re, text: String| {
+            Ok(re.0.find(&text).map(|m| m.as_str().to_string()))
+        });
+
+        methods.add_method(sws::regex::CAPTURES, |lua, re, text: String| {
+            let Some(caps) = re.0.captures(&text) else {
+                return Ok(None);
+            };
+            let table = lua.create_table()?;
+            for (i, name) in re.0.capture_names().enumerate() {
+                let Some(m) = caps.get(i) else {
+                    continue;
+                };
+                table.set(i, m.as_str())?;
+                if let Some(name) = name {
+                    table.set(name, m.as_str())?;
+                }
+            }
+            Ok(Some(table))
+        });
+
+        methods.add_method(
+            sws::regex::REPLACE,
+            |_, re, (text, replacement): (String, String)| {
+                Ok(re.0.replace_all(&text, replacement.as_str()).into_owned())
+            },
+        );
+    }
+}
+
+pub struct LuaKv(pub(crate) SharedKv);
+
+impl UserData for LuaKv {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::ToString, |_, kv, ()| Ok(format!("{:?}", kv.0)));
+
+        methods.add_method(sws::kv::GET, |lua, kv, key: String| {
+            match kv.0 .0.lock().unwrap().get(&key) {
+                Some(value) => lua.to_value(value),
+                None => Ok(mlua::Value::Nil),
+            }
+        });
+
+        methods.add_method(
+            sws::kv::SET,
+            |lua, kv, (key, value): (String, mlua::Value)| {
+                let mut store = kv.0 .0.lock().unwrap();
+                if value.is_nil() {
+                    store.remove(&key);
+                } else {
+                    store.insert(key, lua.from_value(value)?);
+                }
+                Ok(())
+            },
+        );
+
+        methods.add_method(sws::kv::INCR, |_, kv, (key, by): (String, Option<f64>)| {
+            let mut store = kv.0 .0.lock().unwrap();
+            let value = match store.get(&key) {
+                None => 0.,
+                Some(value) => value.as_f64().ok_or_else(|| {
+                    mlua::Error::RuntimeError(format!(
+                        "Couldn't increment key {key}, its value is not a number"
+                    ))
+                })?,
+            };
+            let value = value + by.unwrap_or(1.);
+            store.insert(key, value.into());
+            Ok(value)
+        });
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LuaRobot(pub(crate) Arc<Robot>);
 
@@ -231,13 +691,76 @@ impl UserData for LuaRobot {
         methods.add_method(sws::robot::ALLOWED, |_, r, url: String| {
             Ok(r.0.allowed(&url))
         });
+
+        methods.add_method(sws::robot::SITEMAPS, |_, r, ()| Ok(r.0.sitemaps.clone()));
+
+        methods.add_method(sws::robot::DELAY, |_, r, ()| {
+            // Converted through its shortest representation, so that 0.1 stays 0.1
+            Ok(r.0.delay.and_then(|d| d.to_string().parse::<f64>().ok()))
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LuaFetchInfo(pub(crate) Arc<FetchInfo>);
+
+impl UserData for LuaFetchInfo {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::ToString, |_, f, ()| Ok(format!("{:?}", f.0)));
+
+        methods.add_method(sws::fetch_info::STATUS, |_, f, ()| Ok(f.0.status.as_u16()));
+
+        methods.add_method(sws::fetch_info::HEADER, |_, f, name: String| {
+            Ok(header_value(&f.0.headers, &name))
+        });
+
+        methods.add_method(sws::fetch_info::HEADERS, |lua, f, ()| {
+            let table = lua.create_table()?;
+            for name in f.0.headers.keys() {
+                table.set(name.as_str(), header_value(&f.0.headers, name.as_str()))?;
+            }
+            Ok(table)
+        });
+
+        methods.add_method(sws::fetch_info::CONTENT_TYPE, |_, f, ()| {
+            Ok(header_value(&f.0.headers, "content-type"))
+        });
+
+        methods.add_method(sws::fetch_info::CONTENT_LENGTH, |_, f, ()| {
+            Ok(f.0.content_length)
+        });
+
+        methods.add_method(sws::fetch_info::LATENCY, |_, f, ()| {
+            Ok(f.0.latency.as_secs_f64())
+        });
+
+        methods.add_method(sws::fetch_info::FINAL_URL, |_, f, ()| {
+            Ok(f.0.final_url.clone())
+        });
+
+        methods.add_method(sws::fetch_info::BODY, |lua, f, ()| {
+            lua.create_string(&f.0.body)
+        });
     }
 }
 
+/// The values of the header `name`, joined by commas when it is repeated.
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
 #[derive(Clone, Debug)]
 pub struct LuaCrawlingContext {
     sm: &'static str,
     robot: Option<LuaRobot>,
+    parent: Option<String>,
+    depth: usize,
+    lastmod: Option<String>,
 }
 
 impl<'lua> FromLua<'lua> for LuaCrawlingContext {
@@ -258,6 +781,16 @@ impl UserData for LuaCrawlingContext {
         });
 
         methods.add_method(sws::crawling_context::SITEMAP, |_, ctx, ()| Ok(ctx.sm));
+
+        methods.add_method(sws::crawling_context::PARENT, |_, ctx, ()| {
+            Ok(ctx.parent.clone())
+        });
+
+        methods.add_method(sws::crawling_context::DEPTH, |_, ctx, ()| Ok(ctx.depth));
+
+        methods.add_method(sws::crawling_context::LASTMOD, |_, ctx, ()| {
+            Ok(ctx.lastmod.clone())
+        });
     }
 }
 
@@ -269,40 +802,113 @@ impl From<CrawlingContext> for LuaCrawlingContext {
                 Sitemap::Urlset => sws::sitemap::URL_SET,
             },
             robot: ctx.robot().map(LuaRobot),
+            parent: ctx.parent().map(String::from),
+            depth: ctx.depth(),
+            lastmod: ctx.lastmod().map(String::from),
         }
     }
 }
 
 #[derive(Clone)]
 pub struct LuaScrapingContext {
-    tx_writer: Sender<csv::StringRecord>,
+    tx_writer: Sender<OutputRecord>,
+    schema: Option<Arc<Schema>>,
     page_location: Weak<PageLocation>,
     tx_url: Option<CountedTx>,
     robot: Option<Arc<Robot>>,
+    fetch_info: Option<Arc<FetchInfo>>,
+    request: Option<Arc<PageRequest>>,
+    scratch_dir: Option<String>,
+    worker_scratch_dir: Option<String>,
+    crawl_stop: Option<CrawlStop>,
+    fetcher: Option<Fetcher>,
+    outcome: OutcomeReporter,
+    glob_base: Option<Rc<Path>>,
 }
 
 impl LuaScrapingContext {
-    pub fn new(tx_writer: Sender<csv::StringRecord>, ctx: ScrapingContext) -> Self {
+    pub fn new(
+        tx_writer: Sender<OutputRecord>,
+        schema: Option<Arc<Schema>>,
+        ctx: ScrapingContext,
+    ) -> Self {
         Self {
             tx_writer,
-            page_location: Rc::downgrade(&ctx.location()),
+            schema,
+            page_location: ctx
+                .location()
+                .map(|location| Rc::downgrade(&location))
+                .unwrap_or_default(),
             tx_url: ctx.tx_url(),
             robot: ctx.robot(),
+            fetch_info: ctx.fetch_info(),
+            request: ctx.request(),
+            scratch_dir: ctx.scratch_dir().map(|p| p.display().to_string()),
+            worker_scratch_dir: ctx.worker_scratch_dir().map(|p| p.display().to_string()),
+            crawl_stop: ctx.crawl_stop(),
+            fetcher: ctx.fetcher(),
+            outcome: ctx.outcome(),
+            glob_base: None,
         }
     }
+
+    /// Sets the directory of the glob pattern the page's file was found with.
+    pub(crate) fn with_glob_base(mut self, glob_base: Option<Rc<Path>>) -> Self {
+        self.glob_base = glob_base;
+        self
+    }
+
+    /// Sends the record to the writer, once validated against the schema (if any).
+    fn send_record(&self, output: Option<String>, record: Record) -> mlua::Result<()> {
+        let record = match &self.schema {
+            Some(schema) => match schema.coerce(record) {
+                Ok(record) => record,
+                Err(e) => match schema.on_invalid {
+                    OnError::Fail => return Err(mlua::Error::RuntimeError(e.to_string())),
+                    OnError::SkipAndLog => {
+                        match self.page_location.upgrade() {
+                            Some(location) => {
+                                log::error!("Skipping record of page {location:?}: {e}")
+                            }
+                            None => log::error!("Skipping record: {e}"),
+                        }
+                        return Ok(());
+                    }
+                },
+            },
+            None => record,
+        };
+        self.tx_writer.send(OutputRecord { output, record }).ok();
+        Ok(())
+    }
 }
 
 impl UserData for LuaScrapingContext {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method(sws::scraping_context::PAGE_LOCATION, |_, ctx, ()| {
-            Ok(LuaPageLocation(ctx.page_location.clone()))
+            Ok(LuaPageLocation {
+                location: ctx.page_location.clone(),
+                glob_base: ctx.glob_base.clone(),
+            })
         });
 
         methods.add_method(
             sws::scraping_context::SEND_RECORD,
-            |_, ctx, record: LuaStringRecord| {
-                ctx.tx_writer.send(record.0).ok();
-                Ok(())
+            |_, ctx, record: LuaStringRecord| ctx.send_record(None, record.0),
+        );
+
+        methods.add_method(
+            sws::scraping_context::SEND_RECORD_TO,
+            |_, ctx, (output, record): (String, LuaStringRecord)| {
+                ctx.send_record(Some(output), record.0)
+            },
+        );
+
+        methods.add_method(
+            sws::scraping_context::SEND_MAP,
+            |lua, ctx, (fields, output): (mlua::Table, Option<String>)| {
+                let record = map_record(lua, fields)?;
+                ctx.send_record(output, record)
             },
         );
 
@@ -314,17 +920,234 @@ impl UserData for LuaScrapingContext {
             Ok(id)
         });
 
-        methods.add_method(sws::scraping_context::SEND_URL, |_, ctx, url: String| {
-            if let Some(tx_url) = &ctx.tx_url {
-                tx_url.send(url);
-            } else {
-                log::warn!("Context not initalized, coudln't send URL {url}")
-            }
-            Ok(())
+        methods.add_method(
+            sws::scraping_context::SEND_URL,
+            |lua, ctx, (url, arg, priority): (String, mlua::Value, Option<i32>)| {
+                // Either `sendUrl(url, priority)` or `sendUrl(url, meta, priority)`
+                let (meta, priority) = match arg {
+                    mlua::Value::Nil => (None, priority),
+                    mlua::Value::Table(_) => (Some(lua.from_value(arg)?), priority),
+                    arg => (None, Some(i32::from_lua(arg, lua)?)),
+                };
+                if let Some(tx_url) = &ctx.tx_url {
+                    let request = PageRequest {
+                        meta,
+                        ..PageRequest::from(url)
+                    };
+                    tx_url.send_with_priority(request, priority.unwrap_or_default());
+                } else {
+                    log::warn!("Context not initalized, coudln't send URL {url}")
+                }
+                Ok(())
+            },
+        );
+
+        methods.add_method(
+            sws::scraping_context::SEND_URLS,
+            |_, ctx, (urls, priority): (Vec<String>, Option<i32>)| {
+                let Some(tx_url) = &ctx.tx_url else {
+                    log::warn!("Context not initialized, couldn't send {} URLs", urls.len());
+                    return Ok(());
+                };
+                let mut seen = HashSet::new();
+                for url in urls.into_iter().filter(|url| seen.insert(url.clone())) {
+                    tx_url.send_with_priority(url, priority.unwrap_or_default());
+                }
+                Ok(())
+            },
+        );
+
+        methods.add_method(
+            sws::scraping_context::STOP_CRAWL,
+            |_, ctx, reason: Option<String>| {
+                let reason = reason.unwrap_or_else(|| {
+                    format!("Function {} called", sws::scraping_context::STOP_CRAWL)
+                });
+                if let Some(crawl_stop) = &ctx.crawl_stop {
+                    crawl_stop.stop(&reason);
+                } else {
+                    log::warn!("Context not initialized, couldn't stop crawl: {reason}")
+                }
+                Ok(())
+            },
+        );
+
+        methods.add_method(
+            sws::scraping_context::RETRY,
+            |_, ctx, reason: Option<String>| {
+                let reason = reason
+                    .unwrap_or_else(|| format!("Function {} called", sws::scraping_context::RETRY));
+                ctx.outcome.retry(&reason);
+                Ok(())
+            },
+        );
+
+        methods.add_method(
+            sws::scraping_context::SKIP,
+            |_, ctx, reason: Option<String>| {
+                let reason = reason
+                    .unwrap_or_else(|| format!("Function {} called", sws::scraping_context::SKIP));
+                ctx.outcome.skip(&reason);
+                Ok(())
+            },
+        );
+
+        methods.add_method(sws::scraping_context::FETCH, |_, ctx, url: String| {
+            let Some(fetcher) = &ctx.fetcher else {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "Context not initialized, couldn't fetch URL {url}"
+                )));
+            };
+            let fetched = fetcher
+                .fetch(&url)
+                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+            Ok((fetched.page, LuaFetchInfo(Arc::new(fetched.fetch_info))))
         });
 
         methods.add_method(sws::scraping_context::ROBOT, |_, ctx, ()| {
             Ok(ctx.robot.clone().map(LuaRobot))
         });
+
+        methods.add_method(sws::scraping_context::FETCH_INFO, |_, ctx, ()| {
+            Ok(ctx.fetch_info.clone().map(LuaFetchInfo))
+        });
+
+        methods.add_method(sws::scraping_context::RESPONSE, |_, ctx, ()| {
+            Ok(ctx.fetch_info.clone().map(LuaFetchInfo))
+        });
+
+        methods.add_method(sws::scraping_context::META, |lua, ctx, ()| {
+            let meta = ctx.request.as_ref().and_then(|r| r.meta.as_ref());
+            meta.map(|meta| lua.to_value(meta)).transpose()
+        });
+
+        methods.add_method(sws::scraping_context::DEPTH, |_, ctx, ()| {
+            Ok(ctx.request.as_ref().map(|r| r.depth).unwrap_or_default())
+        });
+
+        methods.add_method(sws::scraping_context::REFERRER, |_, ctx, ()| {
+            Ok(ctx.request.as_ref().and_then(|r| r.referrer.clone()))
+        });
+
+        methods.add_method(sws::scraping_context::SCRATCH_DIR, |_, ctx, ()| {
+            Ok(ctx.scratch_dir.clone())
+        });
+
+        methods.add_method(sws::scraping_context::WORKER_SCRATCH_DIR, |_, ctx, ()| {
+            Ok(ctx.worker_scratch_dir.clone())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+
+    use super::*;
+
+    fn rfc3339(dt: &str, fmt: Option<&str>) -> String {
+        LuaDateTime::parse(dt, fmt).unwrap().0.to_rfc3339()
+    }
+
+    #[test]
+    fn parse_date_times() {
+        assert_eq!(
+            rfc3339("2024-03-01T10:20:30+02:00", None),
+            "2024-03-01T10:20:30+02:00"
+        );
+        assert_eq!(
+            rfc3339("2024-03-01T10:20:30.250Z", None),
+            "2024-03-01T10:20:30.250+00:00"
+        );
+        assert_eq!(
+            rfc3339("Fri, 01 Mar 2024 10:20:30 -0500", None),
+            "2024-03-01T10:20:30-05:00"
+        );
+        assert_eq!(
+            rfc3339("2024-03-01 10:20:30", None),
+            "2024-03-01T10:20:30+00:00"
+        );
+        assert_eq!(rfc3339("2024-03-01", None), "2024-03-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn parse_date_times_with_format() {
+        assert_eq!(
+            rfc3339("01/03/2024 10:20 +0100", Some("%d/%m/%Y %H:%M %z")),
+            "2024-03-01T10:20:00+01:00"
+        );
+        assert_eq!(
+            rfc3339("01/03/2024 10:20", Some("%d/%m/%Y %H:%M")),
+            "2024-03-01T10:20:00+00:00"
+        );
+        assert_eq!(
+            rfc3339("March 1, 2024", Some("%B %d, %Y")),
+            "2024-03-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn parse_invalid_date_times() {
+        assert!(LuaDateTime::parse("yesterday", None).is_err());
+        assert!(LuaDateTime::parse("2024-13-01", None).is_err());
+        assert!(LuaDateTime::parse("2024-03-01", Some("%d/%m/%Y")).is_err());
+    }
+
+    #[test]
+    fn date_time_from_timestamp() {
+        let dt = LuaDateTime::from_timestamp(1709288430.5).unwrap();
+        assert_eq!(dt.0.to_rfc3339(), "2024-03-01T10:20:30.500+00:00");
+    }
+
+    #[test]
+    fn parse_offsets() {
+        let lua = Lua::new();
+        let offset = |s: &str| {
+            let s = lua.create_string(s).unwrap();
+            parse_offset(mlua::Value::String(s)).map(|o| o.local_minus_utc())
+        };
+        assert_eq!(offset("Z").unwrap(), 0);
+        assert_eq!(offset("UTC").unwrap(), 0);
+        assert_eq!(offset("+02:00").unwrap(), 7200);
+        assert_eq!(offset("-0530").unwrap(), -19800);
+        assert_eq!(offset("+09").unwrap(), 32400);
+        assert!(offset("02:00").is_err());
+        assert!(offset("+2h").is_err());
+        assert!(offset("+99:00").is_err());
+        assert_eq!(
+            parse_offset(mlua::Value::Integer(-3600))
+                .unwrap()
+                .local_minus_utc(),
+            -3600
+        );
+        assert!(parse_offset(mlua::Value::Boolean(true)).is_err());
+    }
+
+    #[test]
+    fn format_date_times() {
+        let lua = Lua::new();
+        let dt = LuaDateTime::parse("2024-03-01T23:30:00Z", None).unwrap();
+        lua.globals().set("dt", dt).unwrap();
+        let eval = |code: &str| lua.load(code).eval::<String>().unwrap();
+        assert_eq!(
+            eval("return dt:format('%Y-%m-%d %H:%M')"),
+            "2024-03-01 23:30"
+        );
+        assert_eq!(
+            eval("return dt:withOffset('+02:00'):format('%Y-%m-%d %H:%M %z')"),
+            "2024-03-02 01:30 +0200"
+        );
+        assert_eq!(
+            eval("return tostring(dt:withOffset(-3600):date())"),
+            "2024-03-01"
+        );
+        assert_eq!(
+            eval("return tostring(dt:withOffset('+02:00') == dt)"),
+            "true"
+        );
+        assert_eq!(
+            eval("return tostring(dt:add(90))"),
+            "2024-03-01T23:31:30+00:00"
+        );
     }
 }
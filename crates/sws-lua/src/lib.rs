@@ -4,11 +4,27 @@
 //! [sws_crawler]: https://crates.io/crates/sws-crawler
 //! [sws_scraper]: https://crates.io/crates/sws-scraper
 
+mod archive;
+mod error;
+pub mod inspect;
 pub mod interop;
+mod multi;
 pub mod ns;
+mod profile;
 mod scraper;
+mod stats;
+pub mod stubs;
+pub mod test;
+mod text;
+pub mod upload;
 pub mod writer;
 
-pub use scraper::{scrap_glob, scrap_page, LuaScraper, LuaScraperConfig};
+pub use error::ScriptError;
+pub use multi::{MultiLuaScraper, MultiLuaScraperConfig};
+pub use profile::SharedProfiler;
+pub use scraper::{
+    scrap_files, scrap_glob, scrap_page, scrap_urls, BaseConfig, LuaScraper, LuaScraperConfig,
+};
+pub use stats::SharedStats;
 
 pub use anyhow;
@@ -0,0 +1,289 @@
+//! Several Lua scripts sharing the pages of a single crawl.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use sws_crawler::{
+    serde_json, CrawlingContext, PageOutcome, PageRequest, Scrapable, ScrapingContext, Seed,
+};
+
+use crate::scraper::{LuaScraper, LuaScraperConfig};
+
+/// The scripts of a [`MultiLuaScraper`].
+#[derive(Debug, Clone, Default)]
+pub struct MultiLuaScraperConfig {
+    pub scripts: Vec<LuaScraperConfig>,
+    retries: PendingRetries,
+}
+
+/// The maximum number of retries whose scripts are remembered.
+const MAX_PENDING_RETRIES: usize = 10_000;
+
+/// The retries pending, shared by the workers as another one may scrap the requeued page.
+type PendingRetries = Arc<Mutex<Retries>>;
+
+/// The scripts that asked for a retry, by request and retries of the requeued page.
+///
+/// The crawler may give up on a requeued page (after `max_scrap_retries`, or when the
+/// crawl is stopped) without it being scraped again, so only the most recent retries are
+/// remembered. A page retried after being forgotten is scraped by all the scripts.
+#[derive(Debug, Default)]
+struct Retries {
+    scripts: HashMap<(PageRequest, usize), Vec<usize>>,
+    /// The retries by insertion order, including the ones already scraped
+    order: VecDeque<(PageRequest, usize)>,
+}
+
+impl Retries {
+    fn insert(&mut self, retry: (PageRequest, usize), scripts: Vec<usize>) {
+        if self.order.len() >= MAX_PENDING_RETRIES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.scripts.remove(&oldest);
+            }
+        }
+        self.order.push_back(retry.clone());
+        self.scripts.insert(retry, scripts);
+    }
+
+    fn remove(&mut self, retry: &(PageRequest, usize)) -> Option<Vec<usize>> {
+        self.scripts.remove(retry)
+    }
+}
+
+impl From<Vec<LuaScraperConfig>> for MultiLuaScraperConfig {
+    fn from(scripts: Vec<LuaScraperConfig>) -> Self {
+        Self {
+            scripts,
+            ..Default::default()
+        }
+    }
+}
+
+/// Scraps each downloaded page with several scripts, each of them writing to its own
+/// outputs, so that a site is only downloaded once.
+///
+/// The crawl is driven by the first script: its seed, `acceptUrl` and `priorityUrl` are
+/// the ones used, as well as its `sws.crawlerConfig`. The other scripts scrap all the
+/// pages it accepts.
+///
+/// As the pages scraped are the ones accepted by the first script, the outputs of the
+/// other scripts depend on its `acceptUrl`.
+///
+/// When some scripts ask for a page to be retried, the page is requeued and only these
+/// scripts scrap it again, so that the others don't write their records twice.
+pub struct MultiLuaScraper {
+    scrapers: Vec<LuaScraper>,
+    retries: PendingRetries,
+}
+
+impl Scrapable for MultiLuaScraper {
+    type Config = MultiLuaScraperConfig;
+
+    fn new(config: &Self::Config) -> anyhow::Result<Self> {
+        if config.scripts.is_empty() {
+            anyhow::bail!("At least one script is required");
+        }
+        let scrapers = config
+            .scripts
+            .iter()
+            .map(LuaScraper::new)
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            scrapers,
+            retries: config.retries.clone(),
+        })
+    }
+
+    fn seed(&self) -> Seed {
+        self.scrapers[0].seed()
+    }
+
+    fn accept(&self, url: &str, crawling_ctx: CrawlingContext) -> bool {
+        self.scrapers[0].accept(url, crawling_ctx)
+    }
+
+    fn priority(&self, url: &str, crawling_ctx: CrawlingContext) -> i32 {
+        self.scrapers[0].priority(url, crawling_ctx)
+    }
+
+    /// Each script scraps the page (only the ones that asked for it when it is retried),
+    /// even when a previous one failed. The first error is returned.
+    ///
+    /// The page is retried when any script asks for it, otherwise the last skip reported
+    /// wins.
+    fn scrap(&mut self, page: String, scraping_ctx: ScrapingContext) -> anyhow::Result<()> {
+        let request = scraping_ctx.request();
+        let retried = request.as_ref().and_then(|request| {
+            self.retries
+                .lock()
+                .unwrap()
+                .remove(&(PageRequest::clone(request), request.retries))
+        });
+
+        let mut res = Ok(());
+        let mut retrying = vec![];
+        let mut retry_reason = None;
+        let mut skip_reason = None;
+        for (i, scraper) in self.scrapers.iter_mut().enumerate() {
+            if retried
+                .as_ref()
+                .is_some_and(|retried| !retried.contains(&i))
+            {
+                continue;
+            }
+            let ctx = scraping_ctx.with_own_outcome();
+            let outcome = ctx.outcome();
+            let scraped = scraper.scrap(page.clone(), ctx);
+            match outcome.take() {
+                Some(PageOutcome::Retry(reason)) => {
+                    retrying.push(i);
+                    retry_reason.get_or_insert(reason);
+                }
+                Some(PageOutcome::Skip(reason)) => skip_reason = Some(reason),
+                None => (),
+            }
+            if res.is_ok() {
+                res = scraped;
+            }
+        }
+
+        match (retry_reason, skip_reason) {
+            (Some(reason), _) => {
+                if let Some(request) = request {
+                    self.retries.lock().unwrap().insert(
+                        (PageRequest::clone(&request), request.retries + 1),
+                        retrying,
+                    );
+                }
+                scraping_ctx.retry(&reason);
+            }
+            (None, Some(reason)) => scraping_ctx.skip(&reason),
+            (None, None) => (),
+        }
+        res
+    }
+
+    fn init(&mut self, scraping_ctx: ScrapingContext) -> anyhow::Result<()> {
+        for scraper in &mut self.scrapers {
+            scraper.init(scraping_ctx.clone())?;
+        }
+        Ok(())
+    }
+
+    /// The states of the scripts, in order (`null` for the ones without state).
+    fn finish(
+        &mut self,
+        scraping_ctx: ScrapingContext,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        let states = self
+            .scrapers
+            .iter_mut()
+            .map(|scraper| {
+                let state = scraper.finish(scraping_ctx.clone())?;
+                Ok(state.unwrap_or_default())
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Some(serde_json::Value::Array(states)))
+    }
+
+    fn finalize(
+        &mut self,
+        results: Vec<serde_json::Value>,
+        scraping_ctx: ScrapingContext,
+    ) -> anyhow::Result<()> {
+        for (i, scraper) in self.scrapers.iter_mut().enumerate() {
+            let states = results
+                .iter()
+                .filter_map(|states| states.get(i))
+                .filter(|state| !state.is_null())
+                .cloned()
+                .collect();
+            scraper.finalize(states, scraping_ctx.clone())?;
+        }
+        Ok(())
+    }
+
+    fn finalizer(&mut self) {
+        for scraper in &mut self.scrapers {
+            scraper.finalizer();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::{OutputRecord, Record, SharedSink};
+
+    #[test]
+    fn retry_only_asking_scripts() {
+        let records = Arc::new(Mutex::new(vec![]));
+        let scripts = [("once", "false"), ("retried", "true")]
+            .into_iter()
+            .map(|(name, retry)| {
+                let script = std::env::temp_dir()
+                    .join(format!("sws-multi-{name}-{}.lua", std::process::id()));
+                fs_err::write(
+                    &script,
+                    format!(
+                        r#"
+                        sws.seedPages = {{ "https://example.com" }}
+                        function scrapPage(page, context)
+                           local record = sws.Record()
+                           record:pushField("{name}")
+                           context:sendRecord(record)
+                           if {retry} then context:retry("again") end
+                        end
+                        "#
+                    ),
+                )
+                .unwrap();
+                let records = records.clone();
+                LuaScraperConfig {
+                    script,
+                    sink: Some(SharedSink(Arc::new(move |output: OutputRecord| {
+                        if let Record::Positional(record) = output.record {
+                            records.lock().unwrap().push(record[0].to_string());
+                        }
+                        Ok(())
+                    }))),
+                    ..Default::default()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let config = MultiLuaScraperConfig::from(scripts.clone());
+        let mut scraper = MultiLuaScraper::new(&config).unwrap();
+        for retries in 0..2 {
+            let request = PageRequest {
+                retries,
+                ..PageRequest::from("https://example.com")
+            };
+            let ctx = ScrapingContext::with_request(request);
+            let outcome = ctx.outcome();
+            scraper.scrap("<html></html>".into(), ctx).unwrap();
+            assert_eq!(outcome.take(), Some(PageOutcome::Retry("again".into())));
+        }
+        scraper.finalizer();
+        for script in scripts {
+            fs_err::remove_file(script.script).unwrap();
+        }
+
+        // Each script writes its records on its own thread
+        let mut records = records.lock().unwrap().clone();
+        records.sort();
+        assert_eq!(records, ["once", "retried", "retried"]);
+    }
+
+    #[test]
+    fn forget_oldest_retries() {
+        let retry = |i: usize| (PageRequest::from(format!("https://example.com/{i}")), 1);
+        let mut retries = Retries::default();
+        for i in 0..=MAX_PENDING_RETRIES {
+            retries.insert(retry(i), vec![0]);
+        }
+        assert_eq!(retries.scripts.len(), MAX_PENDING_RETRIES);
+        assert_eq!(retries.remove(&retry(0)), None);
+        assert_eq!(retries.remove(&retry(MAX_PENDING_RETRIES)), Some(vec![0]));
+    }
+}
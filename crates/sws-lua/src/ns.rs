@@ -3,6 +3,10 @@ pub mod globals {
 
     pub const SCRAP_PAGE: &str = "scrapPage"; // Function
     pub const ACCEPT_URL: &str = "acceptUrl"; // Function
+    pub const PRIORITY_URL: &str = "priorityUrl"; // Function
+    pub const INIT: &str = "init"; // Function
+    pub const FINISH: &str = "finish"; // Function
+    pub const FINALIZE: &str = "finalize"; // Function
 
     pub const SWS: &str = "sws"; // Table
 }
@@ -13,14 +17,50 @@ pub mod sws {
     pub const SEED_SITEMAPS: &str = "seedSitemaps"; // Table
     pub const SEED_PAGES: &str = "seedPages"; // Table
     pub const SEED_ROBOTS_TXT: &str = "seedRobotsTxt"; // String
+    pub const SEED_REQUESTS: &str = "seedRequests"; // Table
+    pub const SEED_GENERATOR: &str = "seedGenerator"; // Function
+
+    pub const ARGS: &str = "args"; // Table
+
+    pub const ENV: &str = "env"; // Function
+    pub const HTML_DECODE: &str = "htmlDecode"; // Function
+    pub const DECODE: &str = "decode"; // Function
+    pub const PARSE_NUMBER: &str = "parseNumber"; // Function
+
+    pub const STR: &str = "str"; // Table
+    pub mod string {
+        //! The `str` table
+        pub const TRIM: &str = "trim"; // Function
+        pub const SQUISH: &str = "squish"; // Function
+        pub const SLUGIFY: &str = "slugify"; // Function
+        pub const STRIP_TAGS: &str = "stripTags"; // Function
+    }
+
+    pub const KV: &str = "kv"; // UserData
+    pub mod kv {
+        //! The `kv` store
+        pub const GET: &str = "get"; // Function
+        pub const SET: &str = "set"; // Function
+        pub const INCR: &str = "incr"; // Function
+    }
+
+    pub const SCHEMA: &str = "schema"; // Table
+    pub const ON_INVALID_RECORD: &str = "onInvalidRecord"; // String
 
     pub const CSV_WRITER_CONFIG: &str = "csvWriterConfig"; // Table
+    pub const POSTGRES_WRITER_CONFIG: &str = "postgresWriterConfig"; // Table
+    pub const OUTPUTS: &str = "outputs"; // Table
+    pub const CSV_PARTITION_CONFIG: &str = "csvPartitionConfig"; // Table
+    pub const UPLOAD_CONFIG: &str = "uploadConfig"; // Table
     pub const CRAWLER_CONFIG: &str = "crawlerConfig"; // Table
 
+    pub const HTML: &str = "Html"; // Function
     pub mod html {
         //! The `Html` class
         pub const SELECT: &str = "select"; // Function
+        pub const SELECT_FIRST: &str = "selectFirst"; // Function
         pub const ROOT: &str = "root"; // Function
+        pub const META: &str = "meta"; // Function
     }
 
     pub mod select {
@@ -32,8 +72,14 @@ pub mod sws {
     pub mod elem_ref {
         //! The `ElemRef` class
         pub const SELECT: &str = "select"; // Function
+        pub const SELECT_FIRST: &str = "selectFirst"; // Function
+        pub const MATCHES: &str = "matches"; // Function
+        pub const CLOSEST: &str = "closest"; // Function
+        pub const HTML: &str = "html"; // Function
         pub const INNER_HTML: &str = "innerHtml"; // Function
         pub const INNER_TEXT: &str = "innerText"; // Function
+        pub const TEXTS: &str = "texts"; // Function
+        pub const OWN_TEXT: &str = "ownText"; // Function
         pub const NAME: &str = "name"; // Function
         pub const ID: &str = "id"; // Function
         pub const HAS_CLASS: &str = "hasClass"; // Function
@@ -48,19 +94,100 @@ pub mod sws {
         pub const FORMAT: &str = "format"; // Function
     }
 
+    pub const DATE_TIME: &str = "DateTime"; // Function
+    pub mod date_time {
+        //! The `DateTime` class
+        pub const FORMAT: &str = "format"; // Function
+        pub const TIMESTAMP: &str = "timestamp"; // Function
+        pub const OFFSET: &str = "offset"; // Function
+        pub const WITH_OFFSET: &str = "withOffset"; // Function
+        pub const ADD: &str = "add"; // Function
+        pub const DIFF: &str = "diff"; // Function
+        pub const DATE: &str = "date"; // Function
+    }
+
+    pub const URL: &str = "Url"; // Function
+    pub mod url {
+        //! The `Url` class
+        pub const JOIN: &str = "join"; // Function
+        pub const HOST: &str = "host"; // Function
+        pub const PATH: &str = "path"; // Function
+        pub const PARAM: &str = "param"; // Function
+        pub const SET_PARAM: &str = "setParam"; // Function
+    }
+
+    pub const HASH: &str = "hash"; // Table
+    pub mod hash {
+        //! The `hash` table
+        pub const SHA256: &str = "sha256"; // Function
+        pub const MD5: &str = "md5"; // Function
+    }
+
+    pub const BASE64: &str = "base64"; // Table
+    pub mod base64 {
+        //! The `base64` table
+        pub const ENCODE: &str = "encode"; // Function
+        pub const DECODE: &str = "decode"; // Function
+    }
+
+    pub const REGEX: &str = "Regex"; // Function
+    pub mod regex {
+        //! The `Regex` class
+        pub const MATCH: &str = "match"; // Function
+        pub const CAPTURES: &str = "captures"; // Function
+        pub const REPLACE: &str = "replace"; // Function
+    }
+
+    pub const JSON: &str = "Json"; // Table
+    pub mod json {
+        //! The `Json` table
+        pub const DECODE: &str = "decode"; // Function
+        pub const ENCODE: &str = "encode"; // Function
+        pub const NULL: &str = "null"; // LightUserData
+    }
+
     pub mod scraping_context {
         //! The `ScrapingContext` class
         pub const PAGE_LOCATION: &str = "pageLocation"; // Function
         pub const SEND_RECORD: &str = "sendRecord"; // Function
+        pub const SEND_RECORD_TO: &str = "sendRecordTo"; // Function
+        pub const SEND_MAP: &str = "sendMap"; // Function
         pub const SEND_URL: &str = "sendUrl"; // Function
+        pub const SEND_URLS: &str = "sendUrls"; // Function
+        pub const FETCH: &str = "fetch"; // Function
+        pub const STOP_CRAWL: &str = "stopCrawl"; // Function
+        pub const RETRY: &str = "retry"; // Function
+        pub const SKIP: &str = "skip"; // Function
+        pub const META: &str = "meta"; // Function
+        pub const DEPTH: &str = "depth"; // Function
+        pub const REFERRER: &str = "referrer"; // Function
         pub const WORKER_ID: &str = "workerId"; // Function
         pub const ROBOT: &str = "robot"; // Function
+        pub const FETCH_INFO: &str = "fetchInfo"; // Function
+        pub const RESPONSE: &str = "response"; // Function
+        pub const SCRATCH_DIR: &str = "scratchDir"; // Function
+        pub const WORKER_SCRATCH_DIR: &str = "workerScratchDir"; // Function
+    }
+
+    pub mod fetch_info {
+        //! The `FetchInfo` class
+        pub const STATUS: &str = "status"; // Function
+        pub const HEADER: &str = "header"; // Function
+        pub const HEADERS: &str = "headers"; // Function
+        pub const CONTENT_TYPE: &str = "contentType"; // Function
+        pub const CONTENT_LENGTH: &str = "contentLength"; // Function
+        pub const LATENCY: &str = "latency"; // Function
+        pub const FINAL_URL: &str = "finalUrl"; // Function
+        pub const BODY: &str = "body"; // Function
     }
 
     pub mod page_location {
         //! The `PageLocation` class
         pub const KIND: &str = "kind"; // Function
         pub const GET: &str = "get"; // Function
+        pub const RELATIVE_PATH: &str = "relativePath"; // Function
+        pub const SIZE: &str = "size"; // Function
+        pub const MODIFIED: &str = "modified"; // Function
     }
 
     pub const LOCATION: &str = "Location"; // Table
@@ -74,17 +201,23 @@ pub mod sws {
     pub mod record {
         //! The `Record` class
         pub const PUSH_FIELD: &str = "pushField"; // Function
+        pub const SET: &str = "set"; // Function
     }
 
     pub mod crawling_context {
         //! The `CrawlingContext` class
         pub const ROBOT: &str = "robot"; // Function
         pub const SITEMAP: &str = "sitemap"; // Function
+        pub const PARENT: &str = "parent"; // Function
+        pub const DEPTH: &str = "depth"; // Function
+        pub const LASTMOD: &str = "lastmod"; // Function
     }
 
     pub mod robot {
         //! The `Robot` class
         pub const ALLOWED: &str = "allowed"; // Function
+        pub const SITEMAPS: &str = "sitemaps"; // Function
+        pub const DELAY: &str = "delay"; // Function
     }
 
     pub const SITEMAP: &str = "Sitemap"; // Table
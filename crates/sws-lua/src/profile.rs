@@ -0,0 +1,87 @@
+//! Timings of the Lua functions and CSS selectors across a run.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mlua::Lua;
+
+/// The kinds of the timed calls
+pub(crate) const FUNCTION: &str = "function";
+pub(crate) const SELECTOR: &str = "selector";
+
+/// The timings shared by all the scrapers of a config (and its clones), collected when it
+/// is set in [`LuaScraperConfig::profiler`](crate::LuaScraperConfig::profiler).
+#[derive(Clone, Default)]
+pub struct SharedProfiler(Arc<Mutex<HashMap<String, Timing>>>);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Timing {
+    calls: u64,
+    total: Duration,
+}
+
+impl fmt::Debug for SharedProfiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedProfiler").finish()
+    }
+}
+
+/// The summary of the timings, from the slowest in total.
+impl fmt::Display for SharedProfiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut timings = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, timing)| (name.clone(), *timing))
+            .collect::<Vec<_>>();
+        timings.sort_by_key(|(_, timing)| Reverse(timing.total));
+
+        writeln!(f, "{:>12} {:>10} {:>12}  Name", "Total", "Calls", "Mean")?;
+        for (name, timing) in timings {
+            let mean = timing.total.div_f64(timing.calls.max(1) as f64);
+            writeln!(
+                f,
+                "{:>12} {:>10} {:>12}  {name}",
+                format!("{:.3?}", timing.total),
+                timing.calls,
+                format!("{mean:.3?}")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl SharedProfiler {
+    fn record(&self, name: String, elapsed: Duration, calls: u64) {
+        let mut timings = self.0.lock().unwrap();
+        let timing = timings.entry(name).or_default();
+        timing.calls += calls;
+        timing.total += elapsed;
+    }
+}
+
+/// Runs `f`, timed as a call of `{kind} {name}` when the Lua state has a profiler.
+pub(crate) fn timed<T>(lua: &Lua, kind: &str, name: &str, f: impl FnOnce() -> T) -> T {
+    time(lua, kind, name, 1, f)
+}
+
+/// Runs `f`, its time being added to the last call of `{kind} {name}`, as when lazily
+/// iterating over the elements of a selector.
+pub(crate) fn timed_resumed<T>(lua: &Lua, kind: &str, name: &str, f: impl FnOnce() -> T) -> T {
+    time(lua, kind, name, 0, f)
+}
+
+fn time<T>(lua: &Lua, kind: &str, name: &str, calls: u64, f: impl FnOnce() -> T) -> T {
+    let Some(profiler) = lua.app_data_ref::<SharedProfiler>().map(|p| p.clone()) else {
+        return f();
+    };
+    let started = Instant::now();
+    let res = f();
+    profiler.record(format!("{kind} {name}"), started.elapsed(), calls);
+    res
+}
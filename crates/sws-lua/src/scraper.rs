@@ -1,78 +1,169 @@
-use std::path::PathBuf;
-use std::{fs, thread};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Instant, SystemTime};
+use std::{fmt, thread};
 
+use base64::engine::general_purpose::{STANDARD as BASE64, STANDARD_NO_PAD as BASE64_NO_PAD};
+use base64::Engine;
 use crossbeam_channel::{bounded, select, unbounded, Receiver, Sender};
-use mlua::{Function, Lua, LuaSerdeExt};
-use once_cell::sync::OnceCell;
+use mlua::{Function, HookTriggers, Lua, LuaSerdeExt};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sws_crawler::{
-    CrawlerConfig, CrawlingContext, OnError, PageLocation, Scrapable, ScrapingContext, Seed,
+    serde_json, CrawlerConfig, CrawlingContext, OnError, PageLocation, PageRequest, Scrapable,
+    ScrapingContext, Seed, SeedGenerator,
 };
 use sws_scraper::Html;
 
-use crate::interop::{LuaCrawlingContext, LuaDate, LuaHtml, LuaScrapingContext, LuaStringRecord};
+use crate::archive;
+use crate::error::ScriptError;
+use crate::interop::{
+    LuaCrawlingContext, LuaDate, LuaDateTime, LuaHtml, LuaKv, LuaRegex, LuaScrapingContext,
+    LuaStringRecord, LuaUrl,
+};
 use crate::ns::{globals, sws};
-use crate::writer;
+use crate::profile::{timed, SharedProfiler, FUNCTION};
+use crate::stats::SharedStats;
+use crate::{text, upload, writer};
 
-static TX_CSV_WRITER: OnceCell<(Sender<csv::StringRecord>, Sender<()>, Receiver<()>)> =
-    OnceCell::new();
+/// The number of Lua instructions between two checks of the scrap deadline
+const SCRAP_TIMEOUT_CHECK_PERIOD: u32 = 10_000;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct LuaScraperConfig {
     pub script: PathBuf,
     pub csv_file: Option<PathBuf>,
     pub file_mode: Option<writer::FileMode>,
+    #[serde(default)]
+    pub compression: Option<writer::Compression>,
+    /// The format of the main output, the script's writer config is used when not set
+    #[serde(default)]
+    pub format: Option<writer::Format>,
+    /// Extra directories Lua modules are required from, after the script's one
+    #[serde(default)]
+    pub lua_paths: Vec<PathBuf>,
+    /// Variables passed to the script as `sws.args`
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+    /// Environment variables the script can read with `sws.env`
+    #[serde(default)]
+    pub allowed_env_vars: Vec<String>,
+    /// Reloads the script between pages when it is modified
+    #[serde(default)]
+    pub hot_reload: bool,
+    /// Receives the records instead of the configured outputs
+    #[serde(skip)]
+    pub sink: Option<writer::SharedSink>,
+    #[serde(skip)]
+    pub writer: SharedWriter,
+    #[serde(skip)]
+    pub kv: SharedKv,
+    /// Collects the timings of the script's functions and CSS selectors
+    #[serde(skip)]
+    pub profiler: Option<SharedProfiler>,
+    /// Counts the pages and records of the run
+    #[serde(skip)]
+    pub stats: SharedStats,
+    /// Parameters used unless the script sets them, typically read from a config file
+    #[serde(default)]
+    pub base_config: BaseConfig,
 }
 
-pub struct LuaScraper {
-    lua: Lua,
-    seed: Seed,
-    tx_record: Sender<csv::StringRecord>,
+/// Crawler and CSV writer parameters, each of them being overridden by the one set in
+/// `sws.crawlerConfig` or `sws.csvWriterConfig` (using the same camelCase names).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BaseConfig {
+    #[serde(default)]
+    pub crawler: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub csv_writer: serde_json::Map<String, serde_json::Value>,
 }
 
-impl Scrapable for LuaScraper {
-    type Config = LuaScraperConfig;
+/// Reads a config table of the script on top of base parameters
+fn merged_config<T>(
+    lua: &Lua,
+    base: &serde_json::Map<String, serde_json::Value>,
+    value: Option<mlua::Value>,
+) -> mlua::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let mut merged = base.clone();
+    match value.map(|v| lua.from_value(v)).transpose()? {
+        Some(serde_json::Value::Object(fields)) => merged.extend(fields),
+        // An empty Lua table reads as an array
+        Some(serde_json::Value::Array(items)) if items.is_empty() => (),
+        Some(other) => {
+            return Err(mlua::Error::RuntimeError(format!(
+                "Expected a table of parameters, got: {other}"
+            )))
+        }
+        None => (),
+    }
+    serde_json::from_value(serde_json::Value::Object(merged)).map_err(mlua::Error::external)
+}
 
-    fn new(config: &LuaScraperConfig) -> anyhow::Result<Self> {
-        let lua = Lua::new();
-        let globals = lua.globals();
+/// The records writer shared by all the scrapers of a config (and its clones), it is
+/// opened by the first of them and closed by the finalizer.
+#[derive(Clone, Default)]
+pub struct SharedWriter(Arc<Mutex<Option<WriterHandle>>>);
 
-        // Load and check script
+impl fmt::Debug for SharedWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedWriter").finish()
+    }
+}
 
-        let sws = lua.create_table()?;
-        globals.set(globals::SWS, sws)?;
-        lua.load(&fs_err::read_to_string(&config.script)?).exec()?;
-        let _: Function = globals.get(globals::SCRAP_PAGE)?;
-
-        if globals
-            .get::<_, Option<Function>>(globals::ACCEPT_URL)?
-            .is_none()
-        {
-            let accept_url =
-                lua.create_function(|_, (_url, _ctx): (String, LuaCrawlingContext)| Ok(true))?;
-            globals.set(globals::ACCEPT_URL, accept_url)?;
-        }
+/// The key-value store shared by all the scrapers of a config (and its clones), that is by
+/// all the workers of a crawl.
+#[derive(Clone, Default)]
+pub struct SharedKv(pub(crate) Arc<Mutex<HashMap<String, serde_json::Value>>>);
 
-        // Setup sws namespace
+impl fmt::Debug for SharedKv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedKv").finish()
+    }
+}
 
-        let sws = globals.get::<_, mlua::Table>(globals::SWS)?;
+struct WriterHandle {
+    tx_record: Sender<writer::OutputRecord>,
+    tx_stop: Sender<()>,
+    rx_done: Receiver<()>,
+}
+
+pub struct LuaScraper {
+    lua: Rc<Lua>,
+    seed: Seed,
+    tx_record: Sender<writer::OutputRecord>,
+    writer: SharedWriter,
+    interruptible: bool,
+    schema: Option<Arc<writer::Schema>>,
+    config: LuaScraperConfig,
+    /// The modification time of the loaded script, when hot reload is enabled
+    script_modified: Option<SystemTime>,
+    /// The context `init` was called with, for reloaded scripts to be initialized again
+    init_context: Option<ScrapingContext>,
+    /// The directory of the glob pattern scraped files are found with
+    glob_base: Option<Rc<Path>>,
+}
 
-        let new_record = lua.create_function(|_, ()| Ok(LuaStringRecord::default()))?;
-        sws.set(sws::RECORD, new_record)?;
+impl Scrapable for LuaScraper {
+    type Config = LuaScraperConfig;
 
-        let new_date =
-            lua.create_function(|_, (d, fmt): (String, String)| LuaDate::new(&d, &fmt))?;
-        sws.set(sws::DATE, new_date)?;
+    fn new(config: &LuaScraperConfig) -> anyhow::Result<Self> {
+        let script_modified = if config.hot_reload {
+            Some(fs_err::metadata(&config.script)?.modified()?)
+        } else {
+            None
+        };
 
-        let location = lua.create_table()?;
-        location.set(sws::location::PATH, sws::location::PATH)?;
-        location.set(sws::location::URL, sws::location::URL)?;
-        sws.set(sws::LOCATION, location)?;
+        let lua = Rc::new(load_script(config)?);
+        let globals = lua.globals();
 
-        let sitemap = lua.create_table()?;
-        sitemap.set(sws::sitemap::INDEX, sws::sitemap::INDEX)?;
-        sitemap.set(sws::sitemap::URL_SET, sws::sitemap::URL_SET)?;
-        sws.set(sws::SITEMAP, sitemap)?;
+        let sws = globals.get::<_, mlua::Table>(globals::SWS)?;
 
         // Retrieve custom values
 
@@ -103,23 +194,117 @@ impl Scrapable for LuaScraper {
             ))
         })?;
 
-        let seed = match (sitemap_urls, seed_urls, seed_robots) {
-            (Some(urls), None, None) => Seed::Sitemaps(urls),
-            (None, Some(urls), None) => Seed::Pages(urls),
-            (None, None, Some(url)) => Seed::RobotsTxt(url),
-            _ => anyhow::bail!(
-                "Invalid seed, requires exactly one of: {ns}.{s1}, {ns}.{s2}, {ns}.{s3}",
+        let seed_requests: Option<Vec<PageRequest>> = sws
+            .get::<_, Option<mlua::Value>>(sws::SEED_REQUESTS)?
+            .map(|r| lua.from_value(r))
+            .transpose()
+            .map_err(|e| {
+                mlua::Error::RuntimeError(format!(
+                    "Couldn't read {}.{} got: {}",
+                    globals::SWS,
+                    sws::SEED_REQUESTS,
+                    e
+                ))
+            })?;
+
+        let seed_generator = match sws.get::<_, Option<Function>>(sws::SEED_GENERATOR)? {
+            Some(generator) => {
+                let key = lua.create_registry_value(generator)?;
+                let lua = lua.clone();
+                Some(SeedGenerator::new(move || {
+                    let generator: Function = lua.registry_value(&key).ok()?;
+                    let url = timed(&lua, FUNCTION, sws::SEED_GENERATOR, || {
+                        generator.call::<_, Option<String>>(())
+                    });
+                    match url {
+                        Ok(url) => url,
+                        Err(e) => {
+                            log::error!(
+                                "Couldn't generate seed in function {}.{}: {}",
+                                globals::SWS,
+                                sws::SEED_GENERATOR,
+                                e.to_string().replace('\n', "")
+                            );
+                            None
+                        }
+                    }
+                }))
+            }
+            None => None,
+        };
+
+        let mut seeds = vec![];
+        seeds.extend(sitemap_urls.map(Seed::Sitemaps));
+        seeds.extend(seed_urls.map(Seed::Pages));
+        seeds.extend(seed_robots.map(Seed::RobotsTxt));
+        seeds.extend(seed_requests.map(Seed::Requests));
+        seeds.extend(seed_generator.map(Seed::Generator));
+        let seed = match seeds.len() {
+            0 => anyhow::bail!(
+                "Invalid seed, requires at least one of: {ns}.{s1}, {ns}.{s2}, {ns}.{s3}, {ns}.{s4}, {ns}.{s5}",
                 ns = globals::SWS,
                 s1 = sws::SEED_SITEMAPS,
                 s2 = sws::SEED_PAGES,
-                s3 = sws::SEED_ROBOTS_TXT
+                s3 = sws::SEED_ROBOTS_TXT,
+                s4 = sws::SEED_REQUESTS,
+                s5 = sws::SEED_GENERATOR
             ),
+            1 => seeds.remove(0),
+            _ => Seed::Many(seeds),
         };
 
-        let csv_config: writer::CsvWriterConfig = sws
-            .get::<_, Option<mlua::Value>>(sws::CSV_WRITER_CONFIG)?
-            .map(|h| lua.from_value(h))
-            .unwrap_or_else(|| Ok(writer::CsvWriterConfig::default()))?;
+        let csv_config: writer::CsvWriterConfig = merged_config(
+            &lua,
+            &config.base_config.csv_writer,
+            sws.get(sws::CSV_WRITER_CONFIG)?,
+        )?;
+
+        let named_outputs: HashMap<String, writer::NamedOutput> = sws
+            .get::<_, Option<mlua::Value>>(sws::OUTPUTS)?
+            .map(|o| lua.from_value(o))
+            .transpose()
+            .map_err(|e| {
+                mlua::Error::RuntimeError(format!(
+                    "Couldn't read {}.{} got: {}",
+                    globals::SWS,
+                    sws::OUTPUTS,
+                    e
+                ))
+            })?
+            .unwrap_or_default();
+
+        let schema_fields: Option<HashMap<String, writer::FieldType>> = sws
+            .get::<_, Option<mlua::Value>>(sws::SCHEMA)?
+            .map(|s| lua.from_value(s))
+            .transpose()
+            .map_err(|e| {
+                mlua::Error::RuntimeError(format!(
+                    "Couldn't read {}.{} got: {}",
+                    globals::SWS,
+                    sws::SCHEMA,
+                    e
+                ))
+            })?;
+        let on_invalid: OnError = sws
+            .get::<_, Option<mlua::Value>>(sws::ON_INVALID_RECORD)?
+            .map(|o| lua.from_value(o))
+            .unwrap_or(Ok(OnError::SkipAndLog))?;
+        let schema = schema_fields.map(|fields| Arc::new(writer::Schema { fields, on_invalid }));
+
+        let partition_config: Option<writer::PartitionConfig> = sws
+            .get::<_, Option<mlua::Value>>(sws::CSV_PARTITION_CONFIG)?
+            .map(|c| lua.from_value(c))
+            .transpose()?;
+
+        let postgres_config: Option<writer::PostgresWriterConfig> = sws
+            .get::<_, Option<mlua::Value>>(sws::POSTGRES_WRITER_CONFIG)?
+            .map(|c| lua.from_value(c))
+            .transpose()?;
+
+        let upload_config: Option<upload::UploadConfig> = sws
+            .get::<_, Option<mlua::Value>>(sws::UPLOAD_CONFIG)?
+            .map(|c| lua.from_value(c))
+            .transpose()?;
 
         // Register sws namespace
 
@@ -128,62 +313,45 @@ impl Scrapable for LuaScraper {
 
         // Setup csv writer
 
-        let (tx_record, _, _) = TX_CSV_WRITER.get_or_try_init::<_, anyhow::Error>(move || {
-            let (tx_record, rx_record) = unbounded::<csv::StringRecord>();
-            let (tx_stop, rx_stop) = bounded::<()>(1);
-            let (tx_done, rx_done) = bounded::<()>(1);
-
-            let mut wtr = match &config.csv_file {
-                Some(path) => {
-                    let opts: fs_err::OpenOptions = config
-                        .file_mode
-                        .as_ref()
-                        .map(Clone::clone)
-                        .unwrap_or_default()
-                        .into();
-                    let wtr = csv::WriterBuilder::from(&csv_config).from_writer(opts.open(path)?);
-                    writer::CsvWriter::File(wtr)
-                }
-                None => {
-                    let wtr = csv::WriterBuilder::from(&csv_config).from_writer(std::io::stdout());
-                    writer::CsvWriter::Stdout(wtr)
-                }
-            };
-
-            thread::spawn(move || loop {
-                select! {
-                    recv(rx_stop) -> _ => {
-                        wtr.flush().ok();
-                        tx_done.send(()).ok();
-                        break;
-                    },
-                    recv(rx_record) -> msg => {
-                        msg.map(|record| wtr.write_record(record.into_iter()))
-                            .map(|res| if let Err(e) = res {
-                                log::error!("Couldn't write record: {e}");
-                            })
-                            .ok();
-                    }
-                }
-            });
-
-            Ok((tx_record, tx_stop, rx_done))
-        })?;
+        let mut shared = config.writer.0.lock().unwrap();
+        let handle = match shared.take() {
+            Some(handle) => handle,
+            None => match &config.sink {
+                Some(sink) => spawn_sink(sink.clone()),
+                None => spawn_writer(
+                    config,
+                    csv_config,
+                    named_outputs,
+                    partition_config,
+                    postgres_config,
+                    upload_config,
+                )?,
+            },
+        };
+        let tx_record = shared.insert(handle).tx_record.clone();
+        drop(shared);
 
         // Setup context
 
         Ok(Self {
             lua,
             seed,
-            tx_record: tx_record.clone(),
+            tx_record,
+            writer: config.writer.clone(),
+            interruptible: false,
+            schema,
+            config: config.clone(),
+            script_modified,
+            init_context: None,
+            glob_base: None,
         })
     }
 
     fn finalizer(&mut self) {
-        TX_CSV_WRITER.get().map(|(_, tx_stop, rx_done)| {
-            tx_stop.send(()).ok();
-            rx_done.recv().ok()
-        });
+        if let Some(handle) = self.writer.0.lock().unwrap().take() {
+            handle.tx_stop.send(()).ok();
+            handle.rx_done.recv().ok();
+        }
     }
 
     fn seed(&self) -> Seed {
@@ -191,29 +359,128 @@ impl Scrapable for LuaScraper {
     }
 
     fn scrap(&mut self, page: String, scraping_context: ScrapingContext) -> anyhow::Result<()> {
+        self.reload_if_modified();
+
         let scrap_page: Function = self
             .lua
             .globals()
             .get(globals::SCRAP_PAGE)
             .unwrap_or_else(|_| panic!("Function {} not found", globals::SCRAP_PAGE)); // Ensured in constructor
 
+        // Interrupt the script once the scrap deadline is reached
+        if let Some(deadline) = scraping_context.deadline() {
+            // Hooks are never called from JIT-compiled code, so interrupting scripts
+            // requires the (slower) interpreter
+            if cfg!(feature = "luajit") && !self.interruptible {
+                static JIT_OFF: Once = Once::new();
+                JIT_OFF.call_once(|| {
+                    log::warn!("LuaJIT compiler turned off so that scraps can time out");
+                });
+                self.lua.load("jit.off()").exec()?;
+                self.interruptible = true;
+            }
+            self.lua.set_hook(
+                HookTriggers::new().every_nth_instruction(SCRAP_TIMEOUT_CHECK_PERIOD),
+                move |_, _| {
+                    if Instant::now() > deadline {
+                        Err(mlua::Error::RuntimeError(format!(
+                            "Function {} timed out",
+                            globals::SCRAP_PAGE
+                        )))
+                    } else {
+                        Ok(())
+                    }
+                },
+            );
+        }
+
         let page = LuaHtml(Html::parse_document(&page));
-        let ctx = LuaScrapingContext::new(self.tx_record.clone(), scraping_context);
+        // The context is kept until the end of the scrap, for the page location to live on
+        let ctx = LuaScrapingContext::new(
+            self.tx_record.clone(),
+            self.schema.clone(),
+            scraping_context.clone(),
+        )
+        .with_glob_base(self.glob_base.clone());
 
-        scrap_page
-            .call::<_, ()>((page, ctx))
-            .map_err(|e| anyhow::anyhow!(e.to_string().replace('\n', "")))
+        let res = timed(&self.lua, FUNCTION, globals::SCRAP_PAGE, || {
+            scrap_page.call::<_, ()>((page, ctx))
+        })
+        .map_err(|e| ScriptError::new(globals::SCRAP_PAGE, e).into());
+        self.lua.remove_hook();
+        res
     }
 
-    fn accept(&self, url: &str, crawling_ctx: CrawlingContext) -> bool {
-        let accept_url: Function = self
+    fn init(&mut self, scraping_context: ScrapingContext) -> anyhow::Result<()> {
+        if self.script_modified.is_some() {
+            self.init_context = Some(scraping_context.clone());
+        }
+        let ctx = LuaScrapingContext::new(
+            self.tx_record.clone(),
+            self.schema.clone(),
+            scraping_context,
+        );
+        self.call_hook(globals::INIT, ctx)?;
+        Ok(())
+    }
+
+    fn finish(
+        &mut self,
+        scraping_context: ScrapingContext,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        let ctx = LuaScrapingContext::new(
+            self.tx_record.clone(),
+            self.schema.clone(),
+            scraping_context,
+        );
+        self.call_hook(globals::FINISH, ctx)
+    }
+
+    fn finalize(
+        &mut self,
+        results: Vec<serde_json::Value>,
+        scraping_context: ScrapingContext,
+    ) -> anyhow::Result<()> {
+        let results = self.lua.to_value(&results)?;
+        let ctx = LuaScrapingContext::new(
+            self.tx_record.clone(),
+            self.schema.clone(),
+            scraping_context,
+        );
+        self.call_hook(globals::FINALIZE, (results, ctx))?;
+        Ok(())
+    }
+
+    fn priority(&self, url: &str, crawling_ctx: CrawlingContext) -> i32 {
+        let Some(priority_url) = self
             .lua
             .globals()
-            .get(globals::ACCEPT_URL)
-            .unwrap_or_else(|_| panic!("Function {} not found", globals::ACCEPT_URL)); // Ensured in constructor
+            .get::<_, Option<Function>>(globals::PRIORITY_URL)
+            .ok()
+            .flatten()
+        else {
+            return 0;
+        };
 
         let ctx: LuaCrawlingContext = crawling_ctx.clone().into();
-        match accept_url.call::<_, bool>((url.to_string(), ctx)) {
+        let priority = timed(&self.lua, FUNCTION, globals::PRIORITY_URL, || {
+            priority_url.call::<_, i32>((url.to_string(), ctx))
+        });
+        match priority {
+            Ok(priority) => priority,
+            Err(e) => {
+                log::error!(
+                    "Couldn't process URL {url} ({crawling_ctx:?}) in function {}: {}",
+                    globals::PRIORITY_URL,
+                    e.to_string().replace('\n', "")
+                );
+                0
+            }
+        }
+    }
+
+    fn accept(&self, url: &str, crawling_ctx: CrawlingContext) -> bool {
+        match self.try_accept(url, crawling_ctx.clone()) {
             Ok(accepted) => accepted,
             Err(e) => {
                 log::error!(
@@ -227,6 +494,272 @@ impl Scrapable for LuaScraper {
     }
 }
 
+impl LuaScraper {
+    pub(crate) fn try_accept(
+        &self,
+        url: &str,
+        crawling_ctx: CrawlingContext,
+    ) -> mlua::Result<bool> {
+        let accept_url: Function = self
+            .lua
+            .globals()
+            .get(globals::ACCEPT_URL)
+            .unwrap_or_else(|_| panic!("Function {} not found", globals::ACCEPT_URL)); // Ensured in constructor
+
+        let ctx: LuaCrawlingContext = crawling_ctx.into();
+        timed(&self.lua, FUNCTION, globals::ACCEPT_URL, || {
+            accept_url.call::<_, bool>((url.to_string(), ctx))
+        })
+    }
+
+    /// Reloads the script if it was modified since it was loaded, the current one is kept
+    /// when the new one fails to load. The reloaded script's `init` is called again (if
+    /// the crawl was initialized), as its global state starts over.
+    fn reload_if_modified(&mut self) {
+        let Some(loaded) = self.script_modified else {
+            return;
+        };
+        let modified = match fs_err::metadata(&self.config.script).and_then(|m| m.modified()) {
+            Ok(modified) if modified != loaded => modified,
+            Ok(_) => return,
+            Err(e) => {
+                log::warn!("Couldn't check script for changes: {e}");
+                return;
+            }
+        };
+        self.script_modified = Some(modified);
+
+        match load_script(&self.config) {
+            Ok(lua) => {
+                let previous = std::mem::replace(&mut self.lua, Rc::new(lua));
+                if let Some(ctx) = self.init_context.clone() {
+                    if let Err(e) = self.init(ctx) {
+                        self.lua = previous;
+                        log::error!(
+                            "Couldn't initialize reloaded script {}, keeping the previous one: {}",
+                            self.config.script.display(),
+                            e.to_string().replace('\n', "")
+                        );
+                        return;
+                    }
+                }
+                self.interruptible = false;
+                log::info!("Reloaded script {}", self.config.script.display());
+            }
+            Err(e) => log::error!(
+                "Couldn't reload script {}, keeping the previous one: {}",
+                self.config.script.display(),
+                e.to_string().replace('\n', "")
+            ),
+        }
+    }
+
+    /// Calls the optional global function `name`, its returned value (if not nil) must be
+    /// convertible to JSON to be passed across workers.
+    fn call_hook<'lua>(
+        &'lua self,
+        name: &str,
+        args: impl mlua::IntoLuaMulti<'lua>,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        let Some(hook) = self.lua.globals().get::<_, Option<Function>>(name)? else {
+            return Ok(None);
+        };
+        let value = timed(&self.lua, FUNCTION, name, || {
+            hook.call::<_, mlua::Value>(args)
+        })
+        .map_err(|e| ScriptError::new(name, e))?;
+        if value.is_nil() {
+            return Ok(None);
+        }
+        let value = self.lua.from_value(value).map_err(|e| {
+            anyhow::anyhow!("Couldn't convert the value returned by function {name}: {e}")
+        })?;
+        Ok(Some(value))
+    }
+}
+
+/// Loads the script in a new Lua state and checks its global functions.
+fn load_script(config: &LuaScraperConfig) -> anyhow::Result<Lua> {
+    let lua = Lua::new();
+    setup_lua(&lua, config)?;
+
+    let globals = lua.globals();
+    lua.load(&fs_err::read_to_string(&config.script)?)
+        .set_name(chunk_name(&config.script))
+        .exec()?;
+    let _: Function = globals.get(globals::SCRAP_PAGE)?;
+
+    if globals
+        .get::<_, Option<Function>>(globals::ACCEPT_URL)?
+        .is_none()
+    {
+        let accept_url =
+            lua.create_function(|_, (_url, _ctx): (String, LuaCrawlingContext)| Ok(true))?;
+        globals.set(globals::ACCEPT_URL, accept_url)?;
+    }
+
+    drop(globals);
+    Ok(lua)
+}
+
+/// The name of the script's chunk, so that Lua errors refer to its file and line numbers.
+fn chunk_name(script: &Path) -> String {
+    format!("@{}", script.display())
+}
+
+/// Sets up the `sws` namespace and the modules path, before the script is loaded.
+pub(crate) fn setup_lua(lua: &Lua, config: &LuaScraperConfig) -> anyhow::Result<()> {
+    let globals = lua.globals();
+
+    // Modules are required relative to the script, then to the configured paths
+
+    let script_dir = match config.script.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let package = globals.get::<_, mlua::Table>("package")?;
+    let mut path = std::iter::once(script_dir)
+        .chain(config.lua_paths.iter().map(PathBuf::as_path))
+        .map(|dir| format!("{0}/?.lua;{0}/?/init.lua;", dir.display()))
+        .collect::<String>();
+    path.push_str(&package.get::<_, String>("path")?);
+    package.set("path", path)?;
+
+    if let Some(profiler) = &config.profiler {
+        lua.set_app_data(profiler.clone());
+    }
+
+    // Setup sws namespace
+
+    let sws = lua.create_table()?;
+
+    sws.set(sws::ARGS, config.vars.clone())?;
+
+    let allowed_env_vars = config.allowed_env_vars.clone();
+    let env = lua.create_function(move |_, name: String| {
+        if !allowed_env_vars.contains(&name) {
+            return Err(mlua::Error::RuntimeError(format!(
+                "Environment variable {name} isn't allowed"
+            )));
+        }
+        Ok(std::env::var(name).ok())
+    })?;
+    sws.set(sws::ENV, env)?;
+
+    let new_record = lua.create_function(|_, ()| Ok(LuaStringRecord::default()))?;
+    sws.set(sws::RECORD, new_record)?;
+
+    let new_html =
+        lua.create_function(|_, html: String| Ok(LuaHtml(Html::parse_document(&html))))?;
+    sws.set(sws::HTML, new_html)?;
+
+    let new_date = lua.create_function(|_, (d, fmt): (String, String)| LuaDate::new(&d, &fmt))?;
+    sws.set(sws::DATE, new_date)?;
+
+    let new_date_time =
+        lua.create_function(|lua, (dt, fmt): (mlua::Value, Option<String>)| match dt {
+            mlua::Value::Integer(ts) => LuaDateTime::from_timestamp(ts as f64),
+            mlua::Value::Number(ts) => LuaDateTime::from_timestamp(ts),
+            dt => LuaDateTime::parse(&lua.unpack::<String>(dt)?, fmt.as_deref()),
+        })?;
+    sws.set(sws::DATE_TIME, new_date_time)?;
+
+    let new_url = lua.create_function(|_, url: String| LuaUrl::new(&url))?;
+    sws.set(sws::URL, new_url)?;
+
+    let new_regex = lua.create_function(|_, re: String| LuaRegex::new(&re))?;
+    sws.set(sws::REGEX, new_regex)?;
+
+    let html_decode = lua.create_function(|_, s: String| Ok(text::html_decode(&s)))?;
+    sws.set(sws::HTML_DECODE, html_decode)?;
+
+    let decode = lua.create_function(|_, (bytes, charset): (mlua::String, String)| {
+        text::decode(bytes.as_bytes(), &charset)
+            .ok_or_else(|| mlua::Error::RuntimeError(format!("Unknown charset {charset}")))
+    })?;
+    sws.set(sws::DECODE, decode)?;
+
+    let parse_number = lua.create_function(|_, (s, locale): (String, Option<String>)| {
+        let decimal = locale.as_deref().map(text::decimal_separator);
+        let (value, currency) = text::parse_number(&s, decimal).unzip();
+        Ok((value, currency.flatten()))
+    })?;
+    sws.set(sws::PARSE_NUMBER, parse_number)?;
+
+    let str = lua.create_table()?;
+    let trim = lua.create_function(|_, s: String| Ok(s.trim().to_string()))?;
+    str.set(sws::string::TRIM, trim)?;
+    let squish = lua.create_function(|_, s: String| Ok(text::squish(&s)))?;
+    str.set(sws::string::SQUISH, squish)?;
+    let slugify = lua.create_function(|_, s: String| Ok(text::slugify(&s)))?;
+    str.set(sws::string::SLUGIFY, slugify)?;
+    let strip_tags = lua.create_function(|_, s: String| Ok(text::strip_tags(&s)))?;
+    str.set(sws::string::STRIP_TAGS, strip_tags)?;
+    sws.set(sws::STR, str)?;
+
+    let json = lua.create_table()?;
+    let decode = lua.create_function(|lua, s: String| {
+        let value = serde_json::from_str::<serde_json::Value>(&s)
+            .map_err(|e| mlua::Error::RuntimeError(format!("Couldn't decode JSON got: {e}")))?;
+        lua.to_value(&value)
+    })?;
+    json.set(sws::json::DECODE, decode)?;
+    let encode = lua.create_function(|lua, value: mlua::Value| {
+        let options = mlua::DeserializeOptions::new().sort_keys(true);
+        let value: serde_json::Value = lua.from_value_with(value, options)?;
+        Ok(value.to_string())
+    })?;
+    json.set(sws::json::ENCODE, encode)?;
+    json.set(sws::json::NULL, lua.null())?;
+    sws.set(sws::JSON, json)?;
+
+    let hash = lua.create_table()?;
+    let sha256 = lua
+        .create_function(|_, s: mlua::String| Ok(format!("{:x}", Sha256::digest(s.as_bytes()))))?;
+    hash.set(sws::hash::SHA256, sha256)?;
+    let md5 =
+        lua.create_function(|_, s: mlua::String| Ok(format!("{:x}", md5::compute(s.as_bytes()))))?;
+    hash.set(sws::hash::MD5, md5)?;
+    sws.set(sws::HASH, hash)?;
+
+    let base64 = lua.create_table()?;
+    let encode = lua.create_function(|_, s: mlua::String| Ok(BASE64.encode(s.as_bytes())))?;
+    base64.set(sws::base64::ENCODE, encode)?;
+    let decode = lua.create_function(|lua, s: String| {
+        // Both standard and URL-safe alphabets are accepted, with or without padding
+        let s = s
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .map(|c| match c {
+                '-' => '+',
+                '_' => '/',
+                c => c,
+            })
+            .collect::<String>();
+        let decoded = BASE64_NO_PAD
+            .decode(s.trim_end_matches('='))
+            .map_err(|e| mlua::Error::RuntimeError(format!("Couldn't decode base64 got: {e}")))?;
+        lua.create_string(decoded)
+    })?;
+    base64.set(sws::base64::DECODE, decode)?;
+    sws.set(sws::BASE64, base64)?;
+
+    sws.set(sws::KV, LuaKv(config.kv.clone()))?;
+
+    let location = lua.create_table()?;
+    location.set(sws::location::PATH, sws::location::PATH)?;
+    location.set(sws::location::URL, sws::location::URL)?;
+    sws.set(sws::LOCATION, location)?;
+
+    let sitemap = lua.create_table()?;
+    sitemap.set(sws::sitemap::INDEX, sws::sitemap::INDEX)?;
+    sitemap.set(sws::sitemap::URL_SET, sws::sitemap::URL_SET)?;
+    sws.set(sws::SITEMAP, sitemap)?;
+    globals.set(globals::SWS, sws)?;
+
+    Ok(())
+}
+
 impl TryFrom<&LuaScraperConfig> for CrawlerConfig {
     type Error = anyhow::Error;
 
@@ -234,15 +767,18 @@ impl TryFrom<&LuaScraperConfig> for CrawlerConfig {
         let lua = Lua::new();
         let globals = lua.globals();
 
-        let sws = lua.create_table()?;
-        globals.set(globals::SWS, sws)?;
-        lua.load(&fs_err::read_to_string(&c.script)?).exec()?;
+        setup_lua(&lua, c)?;
+        lua.load(&fs_err::read_to_string(&c.script)?)
+            .set_name(chunk_name(&c.script))
+            .exec()?;
 
-        let crawler_config: CrawlerConfig = globals
-            .get::<_, mlua::Table>(globals::SWS)?
-            .get::<_, Option<mlua::Value>>(sws::CRAWLER_CONFIG)?
-            .map(|h| lua.from_value(h))
-            .unwrap_or_else(|| Ok(CrawlerConfig::default()))?;
+        let crawler_config: CrawlerConfig = merged_config(
+            &lua,
+            &c.base_config.crawler,
+            globals
+                .get::<_, mlua::Table>(globals::SWS)?
+                .get(sws::CRAWLER_CONFIG)?,
+        )?;
 
         Ok(crawler_config)
     }
@@ -254,24 +790,127 @@ pub fn scrap_glob(
     on_error: OnError,
     num_workers: usize,
 ) -> anyhow::Result<()> {
+    let paths = glob::glob(pattern)?.map(|path| path.map_err(anyhow::Error::from));
+    scrap_paths(config, pattern, paths, on_error, num_workers)
+}
+
+/// Scraps some of the files matched by the glob `pattern`, for instance the ones that
+/// changed since they were last scraped.
+pub fn scrap_files(
+    config: &LuaScraperConfig,
+    pattern: &str,
+    paths: Vec<PathBuf>,
+    on_error: OnError,
+    num_workers: usize,
+) -> anyhow::Result<()> {
+    scrap_paths(
+        config,
+        pattern,
+        paths.into_iter().map(Ok),
+        on_error,
+        num_workers,
+    )
+}
+
+fn scrap_paths<I>(
+    config: &LuaScraperConfig,
+    pattern: &str,
+    paths: I,
+    on_error: OnError,
+    num_workers: usize,
+) -> anyhow::Result<()>
+where
+    I: Iterator<Item = anyhow::Result<PathBuf>>,
+{
     let (tx_path, rx_path) = unbounded::<PathBuf>();
 
+    let mut scraper = LuaScraper::new(config)?;
+    let base = glob_base(pattern);
     let mut workers = vec![];
     for id in 0..num_workers {
         let rx_path = rx_path.clone();
         let config = config.clone();
+        let base = base.clone();
         let worker = thread::Builder::new()
             .name(format!("{id}"))
             .spawn(move || {
                 let mut scraper = LuaScraper::new(&config)?;
+                scraper.glob_base = Some(Rc::from(base.as_path()));
+                scraper.init(ScrapingContext::default())?;
                 for path in rx_path.into_iter() {
-                    let page = fs::read_to_string(&path)?;
-                    let ctx = ScrapingContext::with_location(PageLocation::Path(path));
-                    match scraper.scrap(page, ctx) {
-                        Ok(()) => (),
+                    archive::for_each_page(&path, |page, location| {
+                        let ctx = ScrapingContext::with_location(location);
+                        match scraper.scrap(page, ctx) {
+                            Ok(()) => config.stats.add_page_ok(),
+                            Err(e) => match on_error {
+                                OnError::SkipAndLog => {
+                                    log::error!("Skipping page scrap: {e}");
+                                    config.stats.add_page_skipped();
+                                }
+                                OnError::Fail => {
+                                    return Err(e);
+                                }
+                            },
+                        }
+                        Ok(())
+                    })?;
+                }
+                scraper.finish(ScrapingContext::default())
+            })?;
+        workers.push(worker);
+    }
+
+    for path in paths {
+        tx_path.send(path?).ok();
+    }
+    drop(tx_path);
+
+    let res = workers
+        .into_iter()
+        .try_fold(vec![], |mut results, w| {
+            results.extend(w.join().unwrap()?);
+            Ok::<_, anyhow::Error>(results)
+        })
+        .and_then(|results| scraper.finalize(results, ScrapingContext::default()));
+    scraper.finalizer();
+    res
+}
+
+/// Scraps distant pages with multiple workers, each page being downloaded by `fetch`.
+pub fn scrap_urls<F>(
+    config: &LuaScraperConfig,
+    urls: Vec<String>,
+    fetch: F,
+    on_error: OnError,
+    num_workers: usize,
+) -> anyhow::Result<()>
+where
+    F: Fn(&str) -> anyhow::Result<String> + Clone + Send + 'static,
+{
+    let (tx_url, rx_url) = unbounded::<String>();
+
+    let mut scraper = LuaScraper::new(config)?;
+    let mut workers = vec![];
+    for id in 0..num_workers {
+        let rx_url = rx_url.clone();
+        let config = config.clone();
+        let fetch = fetch.clone();
+        let worker = thread::Builder::new()
+            .name(format!("{id}"))
+            .spawn(move || {
+                let mut scraper = LuaScraper::new(&config)?;
+                scraper.init(ScrapingContext::default())?;
+                for url in rx_url.into_iter() {
+                    let res = fetch(&url).and_then(|page| {
+                        let ctx = ScrapingContext::with_location(PageLocation::Url(url.clone()));
+                        scraper.scrap(page, ctx)
+                    });
+                    match res {
+                        Ok(()) => config.stats.add_page_ok(),
                         Err(e) => match on_error {
                             OnError::SkipAndLog => {
-                                log::error!("Skipping page scrap: {e}");
+                                log::error!("Skipping page {url} got: {e}");
+                                config.stats.add_page_skipped();
                             }
                             OnError::Fail => {
                                 return Err(e);
@@ -279,30 +918,396 @@ pub fn scrap_glob(
                         },
                     }
                 }
-                Ok::<(), anyhow::Error>(())
+                scraper.finish(ScrapingContext::default())
             })?;
         workers.push(worker);
     }
 
-    for path in glob::glob(pattern)? {
-        tx_path.send(path?).ok();
+    for url in urls {
+        tx_url.send(url).ok();
     }
-    drop(tx_path);
+    drop(tx_url);
 
-    for w in workers {
-        w.join().unwrap()?;
-    }
+    let res = workers
+        .into_iter()
+        .try_fold(vec![], |mut results, w| {
+            results.extend(w.join().unwrap()?);
+            Ok::<_, anyhow::Error>(results)
+        })
+        .and_then(|results| scraper.finalize(results, ScrapingContext::default()));
+    scraper.finalizer();
+    res
+}
 
-    Ok(())
+/// The directory of a glob pattern, that is its components before the first one with
+/// wildcards (or the parent directory of a single file).
+fn glob_base(pattern: &str) -> PathBuf {
+    let pattern = Path::new(pattern);
+    let base = pattern
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect::<PathBuf>();
+    if base == pattern {
+        base.parent().map(Path::to_path_buf).unwrap_or_default()
+    } else {
+        base
+    }
 }
 
+/// Scraps a single page, its location is `None` when it isn't known (e.g. read from stdin).
 pub fn scrap_page(
     config: &LuaScraperConfig,
     page: String,
-    location: PageLocation,
+    location: Option<PageLocation>,
 ) -> anyhow::Result<()> {
     let mut scraper = LuaScraper::new(config)?;
-    scraper.scrap(page, ScrapingContext::with_location(location))?;
+    scraper.init(ScrapingContext::default())?;
+    let ctx = location
+        .map(ScrapingContext::with_location)
+        .unwrap_or_default();
+    scraper.scrap(page, ctx)?;
+    config.stats.add_page_ok();
+    let results = scraper.finish(ScrapingContext::default())?;
+    scraper.finalize(results.into_iter().collect(), ScrapingContext::default())?;
     scraper.finalizer();
     Ok(())
 }
+
+fn open_writer(
+    config: &LuaScraperConfig,
+    csv_config: &writer::CsvWriterConfig,
+    partition_config: Option<&writer::PartitionConfig>,
+    postgres_config: Option<&writer::PostgresWriterConfig>,
+    upload_config: Option<&upload::UploadConfig>,
+) -> anyhow::Result<writer::RecordWriter> {
+    if let Some(format) = config.format {
+        return open_format_writer(config, csv_config, format);
+    }
+
+    #[cfg(feature = "postgres")]
+    if let Some(postgres_config) = postgres_config {
+        let wtr = writer::PostgresWriter::connect(postgres_config)?;
+        return Ok(writer::RecordWriter::Postgres(wtr));
+    }
+    #[cfg(not(feature = "postgres"))]
+    if postgres_config.is_some() {
+        anyhow::bail!(
+            "Invalid {}.{}, it requires sws-lua's `postgres` feature",
+            globals::SWS,
+            sws::POSTGRES_WRITER_CONFIG
+        );
+    }
+
+    if let Some(partition_config) = partition_config {
+        let file_mode = config.file_mode.clone().unwrap_or_default();
+        let wtr = writer::PartitionedWriter::new(
+            partition_config,
+            csv_config,
+            file_mode,
+            config.compression,
+        )?;
+        return Ok(writer::RecordWriter::Partitioned(wtr));
+    }
+
+    if let Some(max_records) = upload_config.and_then(|c| c.rotate_records) {
+        let path = config
+            .csv_file
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Rotating records requires an output file"))?;
+        let wtr = writer::RotatingWriter::new(
+            path,
+            csv_config,
+            config.file_mode.clone().unwrap_or_default(),
+            config.compression,
+            max_records,
+        )?;
+        return Ok(writer::RecordWriter::Rotating(wtr));
+    }
+
+    let wtr = writer::CsvWriter::open(
+        config.csv_file.as_deref(),
+        csv_config,
+        config.file_mode.clone().unwrap_or_default(),
+        config.compression,
+    )?;
+    Ok(writer::RecordWriter::Csv(wtr))
+}
+
+/// Opens the main output in the given format, regardless of the script's writer config.
+fn open_format_writer(
+    config: &LuaScraperConfig,
+    csv_config: &writer::CsvWriterConfig,
+    format: writer::Format,
+) -> anyhow::Result<writer::RecordWriter> {
+    let path = config.csv_file.as_deref();
+    let file_mode = config.file_mode.clone().unwrap_or_default();
+    match format {
+        writer::Format::Csv => {
+            let wtr = writer::CsvWriter::open(path, csv_config, file_mode, config.compression)?;
+            Ok(writer::RecordWriter::Csv(wtr))
+        }
+        writer::Format::Jsonl => {
+            let wtr = writer::JsonlWriter::open(path, file_mode, config.compression)?;
+            Ok(writer::RecordWriter::Jsonl(wtr))
+        }
+        #[cfg(feature = "parquet")]
+        writer::Format::Parquet => {
+            let wtr = writer::ParquetWriter::open(path, file_mode)?;
+            Ok(writer::RecordWriter::Parquet(wtr))
+        }
+        #[cfg(not(feature = "parquet"))]
+        writer::Format::Parquet => {
+            anyhow::bail!("The parquet format requires sws-lua's `parquet` feature")
+        }
+    }
+}
+
+fn open_outputs(
+    config: &LuaScraperConfig,
+    csv_config: &writer::CsvWriterConfig,
+    outputs: &HashMap<String, writer::NamedOutput>,
+) -> anyhow::Result<HashMap<String, (writer::RecordWriter, writer::Columns)>> {
+    outputs
+        .iter()
+        .map(|(name, output)| {
+            let wtr = writer::CsvWriter::open(
+                Some(output.path()),
+                csv_config,
+                config.file_mode.clone().unwrap_or_default(),
+                config.compression,
+            )?;
+            let wtr = writer::RecordWriter::Csv(wtr);
+            let columns = writer::Columns::new(output.columns());
+            Ok((name.clone(), (wtr, columns)))
+        })
+        .collect()
+}
+
+fn upload_files(uploader: Option<&upload::Uploader>, files: Vec<PathBuf>) {
+    let Some(uploader) = uploader else {
+        return;
+    };
+    for file in files {
+        if let Err(e) = uploader.upload(&file) {
+            log::error!("Couldn't upload {}: {e}", file.display());
+        }
+    }
+}
+
+/// Spawns the thread writing records to the outputs.
+fn spawn_writer(
+    config: &LuaScraperConfig,
+    csv_config: writer::CsvWriterConfig,
+    named_outputs: HashMap<String, writer::NamedOutput>,
+    partition_config: Option<writer::PartitionConfig>,
+    postgres_config: Option<writer::PostgresWriterConfig>,
+    upload_config: Option<upload::UploadConfig>,
+) -> anyhow::Result<WriterHandle> {
+    let (tx_record, rx_record) = unbounded::<writer::OutputRecord>();
+    let (tx_stop, rx_stop) = bounded::<()>(1);
+    let (tx_done, rx_done) = bounded::<()>(1);
+    let (tx_ready, rx_ready) = bounded::<anyhow::Result<()>>(1);
+
+    let config = config.clone();
+    // The writer is opened in its own thread, the postgres client must not be used
+    // from within an async runtime
+    thread::spawn(move || {
+        let writers = (|| {
+            let wtr = open_writer(
+                &config,
+                &csv_config,
+                partition_config.as_ref(),
+                postgres_config.as_ref(),
+                upload_config.as_ref(),
+            )?;
+            let outputs = open_outputs(&config, &csv_config, &named_outputs)?;
+            let uploader = upload_config
+                .as_ref()
+                .map(upload::Uploader::new)
+                .transpose()?;
+            Ok::<_, anyhow::Error>((wtr, outputs, uploader))
+        })();
+        let mut columns = writer::Columns::new(
+            postgres_config
+                .as_ref()
+                .and_then(|c| c.columns.clone())
+                .or_else(|| csv_config.columns.clone()),
+        );
+        let (mut wtr, mut outputs, uploader) = match writers {
+            Ok(writers) => {
+                tx_ready.send(Ok(())).ok();
+                writers
+            }
+            Err(e) => {
+                tx_ready.send(Err(e)).ok();
+                return;
+            }
+        };
+        loop {
+            select! {
+                recv(rx_stop) -> _ => {
+                    // Records sent right before stopping (e.g. by the finalize hook)
+                    for record in rx_record.try_iter() {
+                        write_output(record, &mut wtr, &mut columns, &mut outputs, &config.stats);
+                    }
+                    upload_files(uploader.as_ref(), wtr.take_finished());
+                    let named = outputs.values_mut().map(|(wtr, _)| wtr);
+                    for wtr in std::iter::once(&mut wtr).chain(named) {
+                        if let Err(e) = wtr.flush() {
+                            log::error!("Couldn't flush records: {e}");
+                        }
+                    }
+                    let mut files = wtr.files();
+                    if config.format.is_some() || matches!(wtr, writer::RecordWriter::Csv(_)) {
+                        files.extend(config.csv_file.clone());
+                    }
+                    files.extend(named_outputs.values().map(|o| o.path().to_path_buf()));
+                    // Finishes compressed outputs
+                    drop(wtr);
+                    drop(outputs);
+                    upload_files(uploader.as_ref(), files);
+                    tx_done.send(()).ok();
+                    break;
+                },
+                recv(rx_record) -> msg => {
+                    if let Ok(record) = msg {
+                        write_output(record, &mut wtr, &mut columns, &mut outputs, &config.stats);
+                    }
+                    upload_files(uploader.as_ref(), wtr.take_finished());
+                }
+            }
+        }
+    });
+    rx_ready.recv()??;
+
+    Ok(WriterHandle {
+        tx_record,
+        tx_stop,
+        rx_done,
+    })
+}
+
+/// Writes a record to the main output or to its named one.
+fn write_output(
+    writer::OutputRecord { output, record }: writer::OutputRecord,
+    wtr: &mut writer::RecordWriter,
+    columns: &mut writer::Columns,
+    outputs: &mut HashMap<String, (writer::RecordWriter, writer::Columns)>,
+    stats: &SharedStats,
+) {
+    let res = match output {
+        None => columns
+            .resolve(record)
+            .and_then(|record| wtr.write_record(record, columns.names())),
+        Some(name) => match outputs.get_mut(&name) {
+            Some((wtr, columns)) => columns
+                .resolve(record)
+                .and_then(|record| wtr.write_record(record, columns.names())),
+            None => Err(anyhow::anyhow!("Unknown output {name}")),
+        },
+    };
+    match res {
+        Ok(()) => stats.add_record_written(),
+        Err(e) => log::error!("Couldn't write record: {e}"),
+    }
+}
+
+/// Spawns the thread forwarding records to the given sink.
+fn spawn_sink(sink: writer::SharedSink) -> WriterHandle {
+    let (tx_record, rx_record) = unbounded::<writer::OutputRecord>();
+    let (tx_stop, rx_stop) = bounded::<()>(1);
+    let (tx_done, rx_done) = bounded::<()>(1);
+
+    thread::spawn(move || loop {
+        select! {
+            recv(rx_stop) -> _ => {
+                for record in rx_record.try_iter() {
+                    if let Err(e) = sink.0.send(record) {
+                        log::error!("Couldn't send record: {e}");
+                    }
+                }
+                tx_done.send(()).ok();
+                break;
+            },
+            recv(rx_record) -> msg => {
+                if let Ok(record) = msg {
+                    if let Err(e) = sink.0.send(record) {
+                        log::error!("Couldn't send record: {e}");
+                    }
+                }
+            }
+        }
+    });
+
+    WriterHandle {
+        tx_record,
+        tx_stop,
+        rx_done,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn init_reloaded_script() {
+        let script = std::env::temp_dir().join(format!("sws-reload-{}.lua", std::process::id()));
+        let write_script = |version: &str, modified: SystemTime| {
+            fs_err::write(
+                &script,
+                format!(
+                    r#"
+                    sws.seedPages = {{ "https://example.com" }}
+                    function init(context) version = "{version}" end
+                    function scrapPage(page, context)
+                       local record = sws.Record()
+                       record:pushField(version)
+                       context:sendRecord(record)
+                    end
+                    "#
+                ),
+            )
+            .unwrap();
+            File::options()
+                .write(true)
+                .open(&script)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        write_script("v1", SystemTime::UNIX_EPOCH);
+
+        let records = Arc::new(Mutex::new(vec![]));
+        let records_c = records.clone();
+        let config = LuaScraperConfig {
+            script: script.clone(),
+            hot_reload: true,
+            sink: Some(writer::SharedSink(Arc::new(
+                move |output: writer::OutputRecord| {
+                    if let writer::Record::Positional(record) = output.record {
+                        records_c.lock().unwrap().push(record[0].to_string());
+                    }
+                    Ok(())
+                },
+            ))),
+            ..Default::default()
+        };
+        let mut scraper = LuaScraper::new(&config).unwrap();
+        scraper.init(ScrapingContext::default()).unwrap();
+        scraper
+            .scrap("<html></html>".into(), ScrapingContext::default())
+            .unwrap();
+        write_script("v2", SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+        scraper
+            .scrap("<html></html>".into(), ScrapingContext::default())
+            .unwrap();
+        scraper.finalizer();
+        fs_err::remove_file(&script).unwrap();
+
+        assert_eq!(*records.lock().unwrap(), ["v1", "v2"]);
+    }
+}
@@ -0,0 +1,49 @@
+//! Counts of the pages and records of a run.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The counts shared by all the scrapers of a config (and its clones), see
+/// [`LuaScraperConfig::stats`](crate::LuaScraperConfig::stats).
+///
+/// Records are counted by the writer and pages by [`scrap_page`](crate::scrap_page),
+/// [`scrap_glob`](crate::scrap_glob) and [`scrap_urls`](crate::scrap_urls). When crawling,
+/// pages are counted by the caller instead, for instance from the crawl events.
+#[derive(Debug, Clone, Default)]
+pub struct SharedStats(Arc<Counts>);
+
+#[derive(Debug, Default)]
+struct Counts {
+    pages_ok: AtomicU64,
+    pages_skipped: AtomicU64,
+    records_written: AtomicU64,
+}
+
+impl SharedStats {
+    /// The number of successfully scraped pages
+    pub fn pages_ok(&self) -> u64 {
+        self.0.pages_ok.load(Ordering::Relaxed)
+    }
+
+    /// The number of pages skipped because of an error
+    pub fn pages_skipped(&self) -> u64 {
+        self.0.pages_skipped.load(Ordering::Relaxed)
+    }
+
+    /// The number of records written to the outputs
+    pub fn records_written(&self) -> u64 {
+        self.0.records_written.load(Ordering::Relaxed)
+    }
+
+    pub fn add_page_ok(&self) {
+        self.0.pages_ok.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_page_skipped(&self) {
+        self.0.pages_skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_record_written(&self) {
+        self.0.records_written.fetch_add(1, Ordering::Relaxed);
+    }
+}
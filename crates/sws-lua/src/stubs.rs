@@ -0,0 +1,1105 @@
+//! Definitions of the Lua API in the [LuaLS][luals] annotations format (also understood
+//! by EmmyLua), for editors to provide autocompletion and type checking of scripts.
+//!
+//! [luals]: https://luals.github.io/wiki/annotations/
+
+use std::fmt::Write;
+
+use crate::ns::{globals, sws};
+
+/// A function or a method, its parameters (optional when their name ends with `?`) and
+/// results being typed with LuaLS types.
+struct Func {
+    name: &'static str,
+    params: &'static [(&'static str, &'static str)],
+    returns: &'static [&'static str],
+    doc: &'static str,
+}
+
+const fn func(
+    name: &'static str,
+    params: &'static [(&'static str, &'static str)],
+    returns: &'static [&'static str],
+    doc: &'static str,
+) -> Func {
+    Func {
+        name,
+        params,
+        returns,
+        doc,
+    }
+}
+
+/// A class, instantiated by the `sws.<name>` functions of its constructors (if any)
+struct Class {
+    name: &'static str,
+    doc: &'static str,
+    constructors: &'static [Func],
+    methods: &'static [Func],
+}
+
+/// A table of functions of the `sws` namespace
+struct Table {
+    name: &'static str,
+    doc: &'static str,
+    functions: &'static [Func],
+}
+
+/// An enum of the `sws` namespace, its variants are strings of the same name
+struct Enum {
+    name: &'static str,
+    doc: &'static str,
+    variants: &'static [&'static str],
+}
+
+const HOOKS: &[Func] = &[
+    func(
+        globals::SCRAP_PAGE,
+        &[("page", "Html"), ("context", "ScrapingContext")],
+        &[],
+        "Define the scraping logic for a single HTML page",
+    ),
+    func(
+        globals::ACCEPT_URL,
+        &[("url", "string"), ("context", "CrawlingContext")],
+        &["boolean"],
+        "Specify whether to accept a URL when crawling an XML Sitemap, `true` by default",
+    ),
+    func(
+        globals::PRIORITY_URL,
+        &[("url", "string"), ("context", "CrawlingContext")],
+        &["integer"],
+        "Specify the crawling priority of an accepted URL, `0` by default",
+    ),
+    func(
+        globals::INIT,
+        &[("context", "ScrapingContext")],
+        &[],
+        "Called once by each worker before it scraps its first page",
+    ),
+    func(
+        globals::FINISH,
+        &[("context", "ScrapingContext")],
+        &["any"],
+        "Called once by each worker when it stops",
+    ),
+    func(
+        globals::FINALIZE,
+        &[("results", "any[]"), ("context", "ScrapingContext")],
+        &[],
+        "Called once after all the workers stopped, with the values returned by their `finish`",
+    ),
+];
+
+/// The variables of the `sws` table, set by scripts
+const FIELDS: &[(&str, &str, &str)] = &[
+    (sws::SEED_SITEMAPS, "string[]", "A list of sitemap URLs"),
+    (sws::SEED_PAGES, "string[]", "A list of HTML page URLs"),
+    (sws::SEED_ROBOTS_TXT, "string", "A single robots.txt URL"),
+    (
+        sws::SEED_REQUESTS,
+        "{ url: string, method: string?, body: string?, contentType: string? }[]",
+        "A list of HTTP requests",
+    ),
+    (
+        sws::SEED_GENERATOR,
+        "fun(): string?",
+        "A function returning the next page URL to crawl, or `nil` when done",
+    ),
+    (
+        sws::CSV_WRITER_CONFIG,
+        "table",
+        "Config used to write output csv records",
+    ),
+    (
+        sws::CSV_PARTITION_CONFIG,
+        "table",
+        "Config used to partition output csv records into several files",
+    ),
+    (
+        sws::OUTPUTS,
+        "table<string, string|table>",
+        "Named CSV outputs (name to file path) records can be sent to",
+    ),
+    (
+        sws::UPLOAD_CONFIG,
+        "table",
+        "Config used to upload output files to S3-compatible object storage",
+    ),
+    (
+        sws::POSTGRES_WRITER_CONFIG,
+        "table",
+        "Config used to insert output records in PostgreSQL instead",
+    ),
+    (
+        sws::SCHEMA,
+        "table<string, string>",
+        "The types of named records fields (name to type) records are validated against",
+    ),
+    (
+        sws::ON_INVALID_RECORD,
+        "\"SkipAndLog\"|\"Fail\"",
+        "Whether invalid records are skipped and logged or raise an error",
+    ),
+    (
+        sws::CRAWLER_CONFIG,
+        "table",
+        "Config used to customize crawler behavior",
+    ),
+    (
+        sws::ARGS,
+        "table<string, string>",
+        "The variables given with `--var key=value`",
+    ),
+    (sws::KV, "Kv", "A key-value store shared by all the workers"),
+];
+
+const FUNCTIONS: Table = Table {
+    name: "sws",
+    doc: "The sws namespace",
+    functions: &[
+        func(
+            sws::ENV,
+            &[("name", "string")],
+            &["string?"],
+            "Returns the value of the environment variable `name` (nil if unset), it must be allowed with `--allow-env`",
+        ),
+        func(
+            sws::HTML_DECODE,
+            &[("text", "string")],
+            &["string"],
+            "Decodes the HTML entities (named or numeric) of `text`",
+        ),
+        func(
+            sws::DECODE,
+            &[("bytes", "string"), ("charset", "string")],
+            &["string"],
+            "Decodes the raw `bytes` with the given `charset` (e.g. `windows-1252`)",
+        ),
+        func(
+            sws::PARSE_NUMBER,
+            &[("text", "string"), ("locale?", "string")],
+            &["number?", "string? currency"],
+            "Parses the first number of `text` along with the ISO code of its currency, if any",
+        ),
+    ],
+};
+
+const TABLES: &[Table] = &[
+    Table {
+        name: sws::STR,
+        doc: "String normalization helpers",
+        functions: &[
+            func(
+                sws::string::TRIM,
+                &[("text", "string")],
+                &["string"],
+                "Removes the leading and trailing whitespaces of `text`",
+            ),
+            func(
+                sws::string::SQUISH,
+                &[("text", "string")],
+                &["string"],
+                "Trims `text` and collapses its inner whitespace runs into single spaces",
+            ),
+            func(
+                sws::string::SLUGIFY,
+                &[("text", "string")],
+                &["string"],
+                "A lowercase ASCII version of `text` with words separated by `-`",
+            ),
+            func(
+                sws::string::STRIP_TAGS,
+                &[("html", "string")],
+                &["string"],
+                "The text content of the `html` fragment",
+            ),
+        ],
+    },
+    Table {
+        name: sws::HASH,
+        doc: "Hashing helpers",
+        functions: &[
+            func(
+                sws::hash::SHA256,
+                &[("data", "string")],
+                &["string"],
+                "The SHA-256 digest of `data`, as a lowercase hexadecimal string",
+            ),
+            func(
+                sws::hash::MD5,
+                &[("data", "string")],
+                &["string"],
+                "The MD5 digest of `data`, as a lowercase hexadecimal string",
+            ),
+        ],
+    },
+    Table {
+        name: sws::BASE64,
+        doc: "Base64 helpers",
+        functions: &[
+            func(
+                sws::base64::ENCODE,
+                &[("data", "string")],
+                &["string"],
+                "Encodes `data` with the standard alphabet and padding",
+            ),
+            func(
+                sws::base64::DECODE,
+                &[("text", "string")],
+                &["string"],
+                "Decodes `text`, written with either the standard or the URL-safe alphabet",
+            ),
+        ],
+    },
+    Table {
+        name: sws::JSON,
+        doc: "JSON helpers",
+        functions: &[
+            func(
+                sws::json::DECODE,
+                &[("json", "string")],
+                &["any"],
+                "Decodes the given `json` string into Lua values",
+            ),
+            func(
+                sws::json::ENCODE,
+                &[("value", "any")],
+                &["string"],
+                "Encodes the given Lua value (with sorted object keys)",
+            ),
+        ],
+    },
+];
+
+const CLASSES: &[Class] = &[
+    Class {
+        name: sws::HTML,
+        doc: "A parsed HTML page",
+        constructors: &[func(
+            sws::HTML,
+            &[("html", "string")],
+            &["Html"],
+            "Parses the given `html` document",
+        )],
+        methods: &[
+            func(
+                sws::html::SELECT,
+                &[("selector", "string")],
+                &["Select"],
+                "Parses the given CSS `selector` and returns a Select instance",
+            ),
+            func(
+                sws::html::SELECT_FIRST,
+                &[("selector", "string")],
+                &["ElemRef?"],
+                "Returns the first ElemRef matching the given CSS `selector`",
+            ),
+            func(
+                sws::html::ROOT,
+                &[],
+                &["ElemRef"],
+                "Returns an ElemRef to the HTML root node",
+            ),
+            func(
+                sws::html::META,
+                &[],
+                &["table<string, string>"],
+                "Returns the `content` of the `<meta>` tags keyed by their `property` or `name`",
+            ),
+        ],
+    },
+    Class {
+        name: "Select",
+        doc: "A selection made with CSS selectors",
+        constructors: &[],
+        methods: &[
+            func(
+                sws::select::ITER,
+                &[],
+                &["fun(): ElemRef?"],
+                "An iterator of ElemRef over the selected HTML nodes",
+            ),
+            func(
+                sws::select::ENUMERATE,
+                &[],
+                &["fun(): integer?, ElemRef"],
+                "An iterator of ElemRef and their indices over the selected HTML nodes",
+            ),
+        ],
+    },
+    Class {
+        name: "ElemRef",
+        doc: "An HTML element reference",
+        constructors: &[],
+        methods: &[
+            func(
+                sws::elem_ref::SELECT,
+                &[("selector", "string")],
+                &["Select"],
+                "Parses the given CSS `selector` and returns a Select instance over its descendants",
+            ),
+            func(
+                sws::elem_ref::SELECT_FIRST,
+                &[("selector", "string")],
+                &["ElemRef?"],
+                "Returns the first descendant ElemRef matching the given CSS `selector`",
+            ),
+            func(
+                sws::elem_ref::MATCHES,
+                &[("selector", "string")],
+                &["boolean"],
+                "Whether the HTML element matches the given CSS `selector`",
+            ),
+            func(
+                sws::elem_ref::CLOSEST,
+                &[("selector", "string")],
+                &["ElemRef?"],
+                "Returns the closest ElemRef matching the given CSS `selector` among the element and its ancestors",
+            ),
+            func(
+                sws::elem_ref::HTML,
+                &[],
+                &["string"],
+                "The outer HTML string of this element",
+            ),
+            func(
+                sws::elem_ref::INNER_HTML,
+                &[],
+                &["string"],
+                "The inner HTML string of this element",
+            ),
+            func(
+                sws::elem_ref::INNER_TEXT,
+                &[],
+                &["string"],
+                "Returns all the descendent text nodes content concatenated",
+            ),
+            func(
+                sws::elem_ref::TEXTS,
+                &[],
+                &["fun(): string?"],
+                "An iterator over the content of the descendent text nodes",
+            ),
+            func(
+                sws::elem_ref::OWN_TEXT,
+                &[],
+                &["string"],
+                "Returns the content of the child text nodes concatenated",
+            ),
+            func(
+                sws::elem_ref::NAME,
+                &[],
+                &["string"],
+                "The HTML element name",
+            ),
+            func(
+                sws::elem_ref::ID,
+                &[],
+                &["string?"],
+                "The HTML element id, if any",
+            ),
+            func(
+                sws::elem_ref::HAS_CLASS,
+                &[("class", "string")],
+                &["boolean"],
+                "Whether the HTML element has the given `class`",
+            ),
+            func(
+                sws::elem_ref::CLASSES,
+                &[],
+                &["string[]"],
+                "Returns all classes of the HTML element",
+            ),
+            func(
+                sws::elem_ref::ATTR,
+                &[("name", "string")],
+                &["string?"],
+                "The value of the `name` attribute, if any",
+            ),
+            func(
+                sws::elem_ref::ATTRS,
+                &[],
+                &["table<string, string>"],
+                "Returns all attributes of the HTML element",
+            ),
+        ],
+    },
+    Class {
+        name: sws::DATE,
+        doc: "A helper class for parsing and formatting dates",
+        constructors: &[func(
+            sws::DATE,
+            &[("date", "string"), ("fmt", "string")],
+            &["Date"],
+            "Parses the given `date` accordingly to `fmt`",
+        )],
+        methods: &[func(
+            sws::date::FORMAT,
+            &[("fmt", "string")],
+            &["string"],
+            "Formats the current date accordingly to `fmt`",
+        )],
+    },
+    Class {
+        name: sws::DATE_TIME,
+        doc: "A helper class for date times with a timezone offset",
+        constructors: &[func(
+            sws::DATE_TIME,
+            &[("dt", "string|number"), ("fmt?", "string")],
+            &["DateTime"],
+            "Parses the given `dt` as RFC 3339, RFC 2822 or accordingly to `fmt`, or converts a unix timestamp",
+        )],
+        methods: &[
+            func(
+                sws::date_time::FORMAT,
+                &[("fmt", "string")],
+                &["string"],
+                "Formats the current date time accordingly to `fmt`",
+            ),
+            func(
+                sws::date_time::TIMESTAMP,
+                &[],
+                &["integer"],
+                "The unix timestamp in seconds of the current date time",
+            ),
+            func(
+                sws::date_time::OFFSET,
+                &[],
+                &["integer"],
+                "The timezone offset of the current date time, in seconds east of UTC",
+            ),
+            func(
+                sws::date_time::WITH_OFFSET,
+                &[("offset", "string|integer")],
+                &["DateTime"],
+                "The same instant in the timezone `offset`",
+            ),
+            func(
+                sws::date_time::ADD,
+                &[("secs", "number")],
+                &["DateTime"],
+                "The current date time shifted by `secs` seconds",
+            ),
+            func(
+                sws::date_time::DIFF,
+                &[("other", "DateTime")],
+                &["number"],
+                "The number of seconds from `other` to the current date time",
+            ),
+            func(
+                sws::date_time::DATE,
+                &[],
+                &["Date"],
+                "The (local) Date of the current date time",
+            ),
+        ],
+    },
+    Class {
+        name: sws::URL,
+        doc: "A helper class for parsing and building URLs",
+        constructors: &[func(
+            sws::URL,
+            &[("url", "string")],
+            &["Url"],
+            "Parses the given absolute `url`",
+        )],
+        methods: &[
+            func(
+                sws::url::JOIN,
+                &[("url", "string")],
+                &["Url"],
+                "Resolves the given (possibly relative) `url` against the current one",
+            ),
+            func(
+                sws::url::HOST,
+                &[],
+                &["string?"],
+                "The host of the URL, if any",
+            ),
+            func(sws::url::PATH, &[], &["string"], "The path of the URL"),
+            func(
+                sws::url::PARAM,
+                &[("name", "string")],
+                &["string?"],
+                "The (decoded) value of the `name` query parameter",
+            ),
+            func(
+                sws::url::SET_PARAM,
+                &[("name", "string"), ("value?", "string")],
+                &[],
+                "Sets the `name` query parameter to `value`, removes it when `value` is nil",
+            ),
+        ],
+    },
+    Class {
+        name: sws::REGEX,
+        doc: "A compiled regular expression",
+        constructors: &[func(
+            sws::REGEX,
+            &[("re", "string")],
+            &["Regex"],
+            "Compiles the given regular expression `re`",
+        )],
+        methods: &[
+            func(
+                sws::regex::MATCH,
+                &[("text", "string")],
+                &["string?"],
+                "The leftmost match in `text`, nil if there is none",
+            ),
+            func(
+                sws::regex::CAPTURES,
+                &[("text", "string")],
+                &["table?"],
+                "The capture groups of the leftmost match in `text`, by index and by name",
+            ),
+            func(
+                sws::regex::REPLACE,
+                &[("text", "string"), ("rep", "string")],
+                &["string"],
+                "Replaces all matches in `text` with `rep`",
+            ),
+        ],
+    },
+    Class {
+        name: "Kv",
+        doc: "A key-value store shared by all the workers",
+        constructors: &[],
+        methods: &[
+            func(
+                sws::kv::GET,
+                &[("key", "string")],
+                &["any"],
+                "The value of `key`, nil if there is none",
+            ),
+            func(
+                sws::kv::SET,
+                &[("key", "string"), ("value", "any")],
+                &[],
+                "Sets the value of `key`, removes it when `value` is nil",
+            ),
+            func(
+                sws::kv::INCR,
+                &[("key", "string"), ("by?", "number")],
+                &["number"],
+                "Atomically increments the value of `key` by `by` (`1` by default)",
+            ),
+        ],
+    },
+    Class {
+        name: "ScrapingContext",
+        doc: "The context available when an HTML page is scraped",
+        constructors: &[],
+        methods: &[
+            func(
+                sws::scraping_context::PAGE_LOCATION,
+                &[],
+                &["PageLocation"],
+                "Returns the current PageLocation",
+            ),
+            func(
+                sws::scraping_context::SEND_RECORD,
+                &[("rec", "Record")],
+                &[],
+                "Sends a CSV Record to the current output",
+            ),
+            func(
+                sws::scraping_context::SEND_RECORD_TO,
+                &[("output", "string"), ("rec", "Record")],
+                &[],
+                "Sends a CSV Record to the named `output` declared in `sws.outputs`",
+            ),
+            func(
+                sws::scraping_context::SEND_MAP,
+                &[
+                    ("fields", "table<string, string|number|boolean>"),
+                    ("output?", "string"),
+                ],
+                &[],
+                "Sends a record with the named `fields` to the current output, or to the named `output`",
+            ),
+            func(
+                sws::scraping_context::SEND_URL,
+                &[
+                    ("url", "string"),
+                    ("meta?", "table|integer"),
+                    ("priority?", "integer"),
+                ],
+                &[],
+                "Adds the given `url` to the crawling queue, with an optional `meta` table and `priority`",
+            ),
+            func(
+                sws::scraping_context::SEND_URLS,
+                &[("urls", "string[]"), ("priority?", "integer")],
+                &[],
+                "Adds all the given `urls` to the crawling queue in a single call",
+            ),
+            func(
+                sws::scraping_context::FETCH,
+                &[("url", "string")],
+                &["string", "FetchInfo"],
+                "Downloads the given `url` right away and returns its body and FetchInfo",
+            ),
+            func(
+                sws::scraping_context::STOP_CRAWL,
+                &[("reason?", "string")],
+                &[],
+                "Stops the crawl once the workers are done with their current page",
+            ),
+            func(
+                sws::scraping_context::RETRY,
+                &[("reason?", "string")],
+                &[],
+                "Requeues the current page to be fetched and scraped again",
+            ),
+            func(
+                sws::scraping_context::SKIP,
+                &[("reason?", "string")],
+                &[],
+                "Marks the current page as intentionally skipped",
+            ),
+            func(
+                sws::scraping_context::META,
+                &[],
+                &["table?"],
+                "The metadata attached to the current page's URL when it was sent",
+            ),
+            func(
+                sws::scraping_context::DEPTH,
+                &[],
+                &["integer"],
+                "The number of links followed from a seed page to reach the current page",
+            ),
+            func(
+                sws::scraping_context::REFERRER,
+                &[],
+                &["string?"],
+                "The URL of the page that sent the current page's URL, nil for seeds",
+            ),
+            func(
+                sws::scraping_context::WORKER_ID,
+                &[],
+                &["string"],
+                "A string identifying the current worker thread",
+            ),
+            func(
+                sws::scraping_context::ROBOT,
+                &[],
+                &["Robot?"],
+                "Returns the current Robot if it was setup",
+            ),
+            func(
+                sws::scraping_context::FETCH_INFO,
+                &[],
+                &["FetchInfo?"],
+                "Returns the current page's FetchInfo if it was downloaded",
+            ),
+            func(
+                sws::scraping_context::RESPONSE,
+                &[],
+                &["FetchInfo?"],
+                "Alias of `fetchInfo()`",
+            ),
+            func(
+                sws::scraping_context::SCRATCH_DIR,
+                &[],
+                &["string?"],
+                "A temporary directory shared by all workers, removed at the end of the crawl",
+            ),
+            func(
+                sws::scraping_context::WORKER_SCRATCH_DIR,
+                &[],
+                &["string?"],
+                "A temporary directory dedicated to the current worker",
+            ),
+        ],
+    },
+    Class {
+        name: "FetchInfo",
+        doc: "The metadata of the HTTP response of an HTML page",
+        constructors: &[],
+        methods: &[
+            func(
+                sws::fetch_info::STATUS,
+                &[],
+                &["integer"],
+                "The HTTP status code of the response",
+            ),
+            func(
+                sws::fetch_info::HEADER,
+                &[("name", "string")],
+                &["string?"],
+                "The value of the given response header (case insensitive)",
+            ),
+            func(
+                sws::fetch_info::HEADERS,
+                &[],
+                &["table<string, string>"],
+                "All the response headers, as a table of lowercase names to values",
+            ),
+            func(
+                sws::fetch_info::CONTENT_TYPE,
+                &[],
+                &["string?"],
+                "The `Content-Type` of the response",
+            ),
+            func(
+                sws::fetch_info::CONTENT_LENGTH,
+                &[],
+                &["integer?"],
+                "The `Content-Length` of the (possibly compressed) response body",
+            ),
+            func(
+                sws::fetch_info::LATENCY,
+                &[],
+                &["number"],
+                "The duration in seconds between sending the request and receiving the whole response",
+            ),
+            func(
+                sws::fetch_info::FINAL_URL,
+                &[],
+                &["string"],
+                "The URL of the response, which differs from the page's URL after redirects",
+            ),
+            func(
+                sws::fetch_info::BODY,
+                &[],
+                &["string"],
+                "The raw bytes of the (decompressed) response body",
+            ),
+        ],
+    },
+    Class {
+        name: "PageLocation",
+        doc: "The location of an HTML page",
+        constructors: &[],
+        methods: &[
+            func(
+                sws::page_location::KIND,
+                &[],
+                &["Location?"],
+                "Get the page's Location kind",
+            ),
+            func(
+                sws::page_location::GET,
+                &[],
+                &["string?"],
+                "The URL of a `Location.URL` page, or the path on disk of a `Location.PATH` one",
+            ),
+            func(
+                sws::page_location::RELATIVE_PATH,
+                &[],
+                &["string?"],
+                "The path of a page scraped with `--files`, relative to the directory of the glob pattern",
+            ),
+            func(
+                sws::page_location::SIZE,
+                &[],
+                &["integer?"],
+                "The size in bytes of the file of a `Location.PATH` page",
+            ),
+            func(
+                sws::page_location::MODIFIED,
+                &[],
+                &["DateTime?"],
+                "The last modification time of the file of a `Location.PATH` page",
+            ),
+        ],
+    },
+    Class {
+        name: sws::RECORD,
+        doc: "A dynamic CSV record",
+        constructors: &[func(
+            sws::RECORD,
+            &[],
+            &["Record"],
+            "Creates a new empty CSV record",
+        )],
+        methods: &[
+            func(
+                sws::record::PUSH_FIELD,
+                &[("field", "string")],
+                &[],
+                "Adds the given `field` value to this CSV record",
+            ),
+            func(
+                sws::record::SET,
+                &[("name", "string"), ("value", "string")],
+                &[],
+                "Sets the value of the field `name`, written in the output column of the same name",
+            ),
+        ],
+    },
+    Class {
+        name: "CrawlingContext",
+        doc: "The context available when an XML Sitemap page is crawled",
+        constructors: &[],
+        methods: &[
+            func(
+                sws::crawling_context::ROBOT,
+                &[],
+                &["Robot?"],
+                "Returns the current Robot if it was setup",
+            ),
+            func(
+                sws::crawling_context::SITEMAP,
+                &[],
+                &["Sitemap"],
+                "The Sitemap format of the sitemap page being crawled",
+            ),
+            func(
+                sws::crawling_context::PARENT,
+                &[],
+                &["string?"],
+                "The URL of the sitemap (or robots.txt) where the URL was found",
+            ),
+            func(
+                sws::crawling_context::DEPTH,
+                &[],
+                &["integer"],
+                "The nesting depth of the parent sitemap",
+            ),
+            func(
+                sws::crawling_context::LASTMOD,
+                &[],
+                &["string?"],
+                "The `<lastmod>` of the URL's sitemap entry, if any",
+            ),
+        ],
+    },
+    Class {
+        name: "Robot",
+        doc: "A parsed robots.txt",
+        constructors: &[],
+        methods: &[
+            func(
+                sws::robot::ALLOWED,
+                &[("url", "string")],
+                &["boolean"],
+                "Whether the given `url` is allowed for crawling or not",
+            ),
+            func(
+                sws::robot::SITEMAPS,
+                &[],
+                &["string[]"],
+                "The list of sitemap URLs declared in the robots.txt",
+            ),
+            func(
+                sws::robot::DELAY,
+                &[],
+                &["number?"],
+                "The `Crawl-delay` (in seconds) declared in the robots.txt, if any",
+            ),
+        ],
+    },
+];
+
+const ENUMS: &[Enum] = &[
+    Enum {
+        name: sws::LOCATION,
+        doc: "Location kind",
+        variants: &[sws::location::URL, sws::location::PATH],
+    },
+    Enum {
+        name: sws::SITEMAP,
+        doc: "The Sitemaps formats of an XML Sitemap page",
+        variants: &[sws::sitemap::INDEX, sws::sitemap::URL_SET],
+    },
+];
+
+/// The LuaLS definition file of the whole API, to be put in the workspace (or library
+/// path) of the editor.
+pub fn definitions() -> String {
+    let mut out = String::new();
+    writeln!(out, "---@meta sws").unwrap();
+    writeln!(out, "-- Generated by `sws lua-stubs`, do not edit.").unwrap();
+
+    for hook in HOOKS {
+        write_func(&mut out, "", hook);
+    }
+
+    writeln!(out, "\n---{}", FUNCTIONS.doc).unwrap();
+    writeln!(out, "---@class sws").unwrap();
+    for (name, ty, doc) in FIELDS {
+        writeln!(out, "---@field {name} {ty} {doc}").unwrap();
+    }
+    writeln!(out, "{} = {{}}", globals::SWS).unwrap();
+    for function in FUNCTIONS.functions {
+        write_func(&mut out, "sws.", function);
+    }
+
+    for table in TABLES {
+        writeln!(out, "\n---{}", table.doc).unwrap();
+        writeln!(out, "sws.{} = {{}}", table.name).unwrap();
+        for function in table.functions {
+            write_func(&mut out, &format!("sws.{}.", table.name), function);
+        }
+    }
+    writeln!(out, "\n---The value of JSON `null`, kept in decoded tables").unwrap();
+    writeln!(out, "---@type lightuserdata").unwrap();
+    writeln!(out, "sws.{}.{} = nil", sws::JSON, sws::json::NULL).unwrap();
+
+    for class in CLASSES {
+        writeln!(out, "\n---{}", class.doc).unwrap();
+        writeln!(out, "---@class {}", class.name).unwrap();
+        writeln!(out, "local {} = {{}}", class.name).unwrap();
+        for method in class.methods {
+            write_func(&mut out, &format!("{}:", class.name), method);
+        }
+        for constructor in class.constructors {
+            write_func(&mut out, "sws.", constructor);
+        }
+    }
+
+    for e in ENUMS {
+        writeln!(out, "\n---{}", e.doc).unwrap();
+        writeln!(out, "---@enum {}", e.name).unwrap();
+        writeln!(out, "sws.{} = {{", e.name).unwrap();
+        for variant in e.variants {
+            writeln!(out, "    {variant} = \"{variant}\",").unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+
+    out
+}
+
+/// Writes the annotated declaration of `func`, named `{prefix}{name}`.
+fn write_func(out: &mut String, prefix: &str, func: &Func) {
+    writeln!(out, "\n---{}", func.doc).unwrap();
+    for (name, ty) in func.params {
+        writeln!(out, "---@param {name} {ty}").unwrap();
+    }
+    for ty in func.returns {
+        writeln!(out, "---@return {ty}").unwrap();
+    }
+    let params = func
+        .params
+        .iter()
+        .map(|(name, _)| name.trim_end_matches('?'))
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(out, "function {prefix}{}({params}) end", func.name).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use mlua::{AnyUserData, Lua, Value};
+
+    use super::*;
+    use crate::interop::*;
+    use crate::scraper::{setup_lua, LuaScraperConfig};
+
+    fn keys(table: &mlua::Table) -> BTreeSet<String> {
+        table
+            .clone()
+            .pairs::<String, Value>()
+            .map(|pair| pair.unwrap().0)
+            .collect()
+    }
+
+    fn names(funcs: &[Func]) -> BTreeSet<String> {
+        funcs.iter().map(|f| f.name.to_string()).collect()
+    }
+
+    #[test]
+    fn sws_namespace_is_stubbed() {
+        let lua = Lua::new();
+        setup_lua(&lua, &LuaScraperConfig::default()).unwrap();
+        let sws = lua.globals().get::<_, mlua::Table>(globals::SWS).unwrap();
+
+        let mut functions = names(FUNCTIONS.functions);
+        for class in CLASSES {
+            functions.extend(names(class.constructors));
+        }
+        let runtime_functions = sws
+            .clone()
+            .pairs::<String, Value>()
+            .map(|pair| pair.unwrap())
+            .filter(|(_, value)| value.is_function())
+            .map(|(key, _)| key)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(runtime_functions, functions);
+
+        for table in TABLES {
+            let runtime = sws.get::<_, mlua::Table>(table.name).unwrap();
+            let runtime_functions = runtime
+                .pairs::<String, Value>()
+                .map(|pair| pair.unwrap())
+                .filter(|(_, value)| value.is_function())
+                .map(|(key, _)| key)
+                .collect::<BTreeSet<_>>();
+            assert_eq!(
+                runtime_functions,
+                names(table.functions),
+                "sws.{}",
+                table.name
+            );
+        }
+
+        for e in ENUMS {
+            let runtime = sws.get::<_, mlua::Table>(e.name).unwrap();
+            let variants = e.variants.iter().map(|v| v.to_string()).collect();
+            assert_eq!(keys(&runtime), variants, "sws.{}", e.name);
+        }
+
+        let stubbed = functions
+            .into_iter()
+            .chain(TABLES.iter().map(|t| t.name.to_string()))
+            .chain(ENUMS.iter().map(|e| e.name.to_string()))
+            .chain(FIELDS.iter().map(|(name, _, _)| name.to_string()))
+            .collect::<BTreeSet<_>>();
+        let unstubbed = keys(&sws)
+            .into_iter()
+            .filter(|key| !stubbed.contains(key))
+            .collect::<Vec<_>>();
+        assert!(unstubbed.is_empty(), "Not stubbed: {unstubbed:?}");
+    }
+
+    #[test]
+    fn classes_methods_are_stubbed() {
+        let lua = Lua::new();
+        let proxies: Vec<(&str, AnyUserData)> = vec![
+            (sws::HTML, lua.create_proxy::<LuaHtml>().unwrap()),
+            ("Select", lua.create_proxy::<LuaSelect>().unwrap()),
+            ("ElemRef", lua.create_proxy::<LuaElementRef>().unwrap()),
+            (sws::DATE, lua.create_proxy::<LuaDate>().unwrap()),
+            (sws::DATE_TIME, lua.create_proxy::<LuaDateTime>().unwrap()),
+            (sws::URL, lua.create_proxy::<LuaUrl>().unwrap()),
+            (sws::REGEX, lua.create_proxy::<LuaRegex>().unwrap()),
+            ("Kv", lua.create_proxy::<LuaKv>().unwrap()),
+            (
+                "ScrapingContext",
+                lua.create_proxy::<LuaScrapingContext>().unwrap(),
+            ),
+            ("FetchInfo", lua.create_proxy::<LuaFetchInfo>().unwrap()),
+            (
+                "PageLocation",
+                lua.create_proxy::<LuaPageLocation>().unwrap(),
+            ),
+            (sws::RECORD, lua.create_proxy::<LuaStringRecord>().unwrap()),
+            (
+                "CrawlingContext",
+                lua.create_proxy::<LuaCrawlingContext>().unwrap(),
+            ),
+            ("Robot", lua.create_proxy::<LuaRobot>().unwrap()),
+        ];
+
+        let classes = CLASSES.iter().map(|c| c.name).collect::<BTreeSet<_>>();
+        let runtime_classes = proxies.iter().map(|(name, _)| *name).collect();
+        assert_eq!(classes, runtime_classes);
+
+        for (name, proxy) in proxies {
+            let class = CLASSES.iter().find(|c| c.name == name).unwrap();
+            let methods = proxy
+                .get_metatable()
+                .unwrap()
+                .get::<mlua::Table>("__index")
+                .unwrap();
+            assert_eq!(keys(&methods), names(class.methods), "{name}");
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Helpers to test scraping scripts against saved HTML pages, without any network access
+//! nor output files.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crossbeam_channel::unbounded;
+use sws_crawler::{CrawlingContext, PageLocation, Scrapable};
+
+use crate::error::ScriptError;
+use crate::ns::globals;
+use crate::scraper::{scrap_page, LuaScraper, LuaScraperConfig};
+use crate::writer::{OutputRecord, Record, SharedSink};
+
+/// Scraps the HTML file `html_fixture` with `script`, and returns the records it sent (to
+/// any output) instead of writing them.
+pub fn run_fixture(
+    script: impl AsRef<Path>,
+    html_fixture: impl AsRef<Path>,
+) -> anyhow::Result<Vec<Record>> {
+    let (tx_record, rx_record) = unbounded::<OutputRecord>();
+    let config = LuaScraperConfig {
+        script: script.as_ref().to_path_buf(),
+        sink: Some(SharedSink(Arc::new(tx_record))),
+        ..Default::default()
+    };
+
+    let path = html_fixture.as_ref();
+    let page = fs_err::read_to_string(path)?;
+    scrap_page(&config, page, Some(PageLocation::Path(path.to_path_buf())))?;
+
+    Ok(rx_record.try_iter().map(|r| r.record).collect())
+}
+
+/// Calls the `acceptUrl` function of `script` (which accepts any URL when it is not
+/// defined), its errors are returned instead of being logged.
+pub fn accept_url(
+    script: impl AsRef<Path>,
+    url: &str,
+    crawling_ctx: CrawlingContext,
+) -> anyhow::Result<bool> {
+    let config = LuaScraperConfig {
+        script: script.as_ref().to_path_buf(),
+        sink: Some(SharedSink(Arc::new(|_| Ok(())))),
+        ..Default::default()
+    };
+
+    let mut scraper = LuaScraper::new(&config)?;
+    let accepted = scraper
+        .try_accept(url, crawling_ctx)
+        .map_err(|e| ScriptError::new(globals::ACCEPT_URL, e));
+    scraper.finalizer();
+    Ok(accepted?)
+}
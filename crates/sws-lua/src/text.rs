@@ -0,0 +1,311 @@
+//! Text cleanup helpers exposed in Lua.
+
+use html5ever::data::NAMED_ENTITIES;
+use sws_scraper::Html;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Entities are decoded again as long as this leaves others (e.g. `&amp;lt;`).
+const MAX_DECODE_PASSES: usize = 8;
+
+/// The length of the longest named entity, `&CounterClockwiseContourIntegral;`
+const MAX_ENTITY_LEN: usize = 33;
+
+/// Decodes the named and numeric character references of an HTML text, including
+/// double-encoded ones.
+pub fn html_decode(text: &str) -> String {
+    let mut decoded = text.to_string();
+    for _ in 0..MAX_DECODE_PASSES {
+        match decode_entities(&decoded) {
+            Some(next) => decoded = next,
+            None => break,
+        }
+    }
+    decoded
+}
+
+/// A single decoding pass, `None` if there wasn't any entity to decode.
+fn decode_entities(text: &str) -> Option<String> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut found = false;
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match decode_entity(rest) {
+            Some((c, len)) => {
+                decoded.extend(c.into_iter().flatten());
+                rest = &rest[len..];
+                found = true;
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    found.then_some(decoded)
+}
+
+/// Decodes the entity `text` starts with, returns its chars and its length.
+fn decode_entity(text: &str) -> Option<([Option<char>; 2], usize)> {
+    let end = text.bytes().take(MAX_ENTITY_LEN).position(|b| b == b';')?;
+    let name = &text[1..end];
+    let chars = match name.strip_prefix('#') {
+        Some(num) => {
+            let code = match num.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => num.parse().ok()?,
+            };
+            let c = match code {
+                0 => char::REPLACEMENT_CHARACTER,
+                code => char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+            };
+            [Some(c), None]
+        }
+        None => {
+            let &(c1, c2) = NAMED_ENTITIES.get(&text[1..=end])?;
+            [
+                char::from_u32(c1),
+                (c2 != 0).then(|| char::from_u32(c2)).flatten(),
+            ]
+        }
+    };
+    Some((chars, end + 1))
+}
+
+/// Collapses the whitespace runs of a text into single spaces, and trims it.
+pub fn squish(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A lowercase ASCII version of a text, with words separated by dashes.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    // Accented letters are decomposed and their accents dropped
+    for c in text.nfd().filter(|c| !is_combining_mark(*c)) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if let Some(letters) = ascii_letters(c) {
+            slug.push_str(letters);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    if slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
+
+/// The ASCII letters of the latin letters that don't decompose into one.
+fn ascii_letters(c: char) -> Option<&'static str> {
+    let letters = match c {
+        'æ' | 'Æ' => "ae",
+        'œ' | 'Œ' => "oe",
+        'ø' | 'Ø' => "o",
+        'ß' => "ss",
+        'đ' | 'Đ' | 'ð' | 'Ð' => "d",
+        'ł' | 'Ł' => "l",
+        'þ' | 'Þ' => "th",
+        _ => return None,
+    };
+    Some(letters)
+}
+
+/// The text content of an HTML fragment.
+pub fn strip_tags(html: &str) -> String {
+    Html::parse_fragment(html).root_element().inner_text()
+}
+
+/// Decodes `bytes` with the given `charset` label (such as `windows-1252` or `latin1`),
+/// `None` for unknown labels. Malformed sequences are replaced by `U+FFFD`.
+pub fn decode(bytes: &[u8], charset: &str) -> Option<String> {
+    let encoding = encoding_rs::Encoding::for_label(charset.trim().as_bytes())?;
+    let (text, _, _) = encoding.decode(bytes);
+    Some(text.into_owned())
+}
+
+/// Currency symbols and the ISO 4217 code they stand for, longest symbols first.
+const CURRENCY_SYMBOLS: &[(&str, &str)] = &[
+    ("US$", "USD"),
+    ("R$", "BRL"),
+    ("C$", "CAD"),
+    ("A$", "AUD"),
+    ("$", "USD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("¥", "JPY"),
+    ("₹", "INR"),
+    ("₽", "RUB"),
+    ("₩", "KRW"),
+    ("₺", "TRY"),
+    ("zł", "PLN"),
+    ("Kč", "CZK"),
+];
+
+/// ISO 4217 codes recognized as is.
+const CURRENCY_CODES: &[&str] = &[
+    "AUD", "BRL", "CAD", "CHF", "CNY", "CZK", "DKK", "EUR", "GBP", "HKD", "INR", "JPY", "KRW",
+    "MXN", "NOK", "NZD", "PLN", "RUB", "SEK", "SGD", "TRY", "USD", "ZAR",
+];
+
+/// Languages writing decimals with a dot, the other ones being assumed to use a comma.
+const DOT_DECIMAL_LANGS: &[&str] = &[
+    "en", "ga", "he", "hi", "ja", "ko", "ms", "mt", "ta", "th", "tl", "ur", "zh",
+];
+
+/// The decimal separator of a locale such as `fr`, `en-US` or `de_CH`.
+pub fn decimal_separator(locale: &str) -> char {
+    let lang = locale.split(['-', '_']).next().unwrap_or_default();
+    match lang.to_ascii_lowercase().as_str() {
+        lang if DOT_DECIMAL_LANGS.contains(&lang) => '.',
+        // Switzerland uses a dot whatever the language
+        _ if locale.ends_with("CH") => '.',
+        _ => ',',
+    }
+}
+
+/// Parses the first number of a text (e.g. `1 234,56 €`), along with the code of the
+/// currency it mentions, if any.
+///
+/// Without a `decimal` separator, a single `.` or `,` after 1 to 3 digits (not starting
+/// with `0`) and followed by exactly 3 digits is considered to be a thousands separator.
+pub fn parse_number(text: &str, decimal: Option<char>) -> Option<(f64, Option<&'static str>)> {
+    let is_group = |c: char| matches!(c, ' ' | '\'' | '\u{a0}' | '\u{202f}');
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let len = text[start..]
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ',' || is_group(c)))
+        .unwrap_or(text.len() - start);
+    let number = text[start..start + len].trim_end_matches(|c: char| !c.is_ascii_digit());
+
+    let decimal = decimal.or_else(|| {
+        let last = number.rfind(['.', ','])?;
+        let sep = number[last..].chars().next()?;
+        let other = if sep == '.' { ',' } else { '.' };
+        let single = number.matches(sep).count() == 1;
+        let grouping = !number.contains(other)
+            && single
+            && number.len() - last - 1 == 3
+            && number[..last].len() <= 3
+            && !number.starts_with('0');
+        (!grouping && (single || number.contains(other))).then_some(sep)
+    });
+    let digits = number
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_ascii_digit() => Some(c),
+            c if Some(c) == decimal => Some('.'),
+            _ => None,
+        })
+        .collect::<String>();
+    let mut value = digits.parse::<f64>().ok()?;
+
+    let currency = CURRENCY_SYMBOLS
+        .iter()
+        .find(|(symbol, _)| text.contains(symbol))
+        .map(|(_, code)| *code)
+        .or_else(|| {
+            text.split(|c: char| !c.is_ascii_alphabetic())
+                .find_map(|word| CURRENCY_CODES.iter().find(|code| **code == word))
+                .copied()
+        });
+
+    let prefix = CURRENCY_SYMBOLS
+        .iter()
+        .fold(text[..start].trim_end(), |prefix, (symbol, _)| {
+            prefix.trim_end_matches(symbol).trim_end()
+        });
+    if prefix.ends_with(['-', '−']) {
+        value = -value;
+    }
+
+    Some((value, currency))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_decode_entities() {
+        assert_eq!(html_decode("&lt;p&gt;Fish &amp; Chips"), "<p>Fish & Chips");
+        assert_eq!(html_decode("&#233;t&#xE9; &#X2014;"), "été —");
+        assert_eq!(html_decode("&amp;lt;double&amp;gt;"), "<double>");
+        assert_eq!(html_decode("&NotEqualTilde;"), "\u{2242}\u{338}");
+        assert_eq!(html_decode("&#0;"), "\u{fffd}");
+        assert_eq!(
+            html_decode("AT&T &unknown; &#xZZ; & ;"),
+            "AT&T &unknown; &#xZZ; & ;"
+        );
+    }
+
+    #[test]
+    fn string_helpers() {
+        assert_eq!(squish("  Hello \n\t  world  "), "Hello world");
+        assert_eq!(squish(" \n "), "");
+        assert_eq!(
+            slugify("Crème Brûlée & Œufs: l'Straße!"),
+            "creme-brulee-oeufs-l-strasse"
+        );
+        assert_eq!(slugify("--Already-a-slug--"), "already-a-slug");
+        assert_eq!(strip_tags("<p>Hello <b>world</b></p>"), "Hello world");
+        assert_eq!(decode(b"caf\xe9", "windows-1252").unwrap(), "café");
+        assert_eq!(decode(b"caf\xe9", " Latin1 ").unwrap(), "café");
+        assert_eq!(decode(b"caf\xe9", "utf-8").unwrap(), "caf\u{fffd}");
+        assert_eq!(decode(b"cafe", "unknown"), None);
+    }
+
+    #[test]
+    fn decimal_separators() {
+        assert_eq!(decimal_separator("en-US"), '.');
+        assert_eq!(decimal_separator("ja"), '.');
+        assert_eq!(decimal_separator("fr"), ',');
+        assert_eq!(decimal_separator("de_DE"), ',');
+        assert_eq!(decimal_separator("de_CH"), '.');
+    }
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(
+            parse_number("1 234,56 €", None),
+            Some((1234.56, Some("EUR")))
+        );
+        assert_eq!(parse_number("US$1,234", None), Some((1234., Some("USD"))));
+        assert_eq!(
+            parse_number("1,234.5 CHF", None),
+            Some((1234.5, Some("CHF")))
+        );
+        assert_eq!(parse_number("1.234.567", None), Some((1234567., None)));
+        assert_eq!(parse_number("1'234.50", None), Some((1234.5, None)));
+        assert_eq!(parse_number("0,123", None), Some((0.123, None)));
+        assert_eq!(parse_number("12.5%", None), Some((12.5, None)));
+        assert_eq!(
+            parse_number("Total: - £3.99", None),
+            Some((-3.99, Some("GBP")))
+        );
+        assert_eq!(parse_number("−42 zł", None), Some((-42., Some("PLN"))));
+    }
+
+    #[test]
+    fn parse_numbers_with_separator() {
+        assert_eq!(parse_number("1.234", Some(',')), Some((1234., None)));
+        assert_eq!(parse_number("1.234", Some('.')), Some((1.234, None)));
+        assert_eq!(
+            parse_number("1\u{202f}234,5", Some(',')),
+            Some((1234.5, None))
+        );
+        assert_eq!(
+            parse_number("1,234,567.89", Some('.')),
+            Some((1234567.89, None))
+        );
+    }
+
+    #[test]
+    fn parse_invalid_numbers() {
+        assert_eq!(parse_number("", None), None);
+        assert_eq!(parse_number("free", None), None);
+        assert_eq!(parse_number("€ -", None), None);
+        assert_eq!(parse_number("1.2.3", Some('.')), None);
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Config of the S3-compatible bucket output files are uploaded to once they are
+/// finished. Requires the `s3` feature.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadConfig {
+    pub bucket: String,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_upload_region")]
+    pub region: String,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub path_style: bool,
+    /// Rotates the main output file into chunks of this many records, uploaded as soon as
+    /// they are complete
+    #[serde(default)]
+    pub rotate_records: Option<usize>,
+}
+
+fn default_upload_region() -> String {
+    String::from("us-east-1")
+}
+
+/// The object key of a file, its path (without root) appended to the `prefix`.
+#[cfg(feature = "s3")]
+fn object_key(prefix: &str, path: &Path) -> String {
+    let path = path
+        .components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(c) => Some(c.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{prefix}{path}")
+}
+
+/// Files from this size (in bytes) are uploaded in parts
+#[cfg(feature = "s3")]
+const MULTIPART_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+#[cfg(feature = "s3")]
+pub struct Uploader {
+    bucket: s3::Bucket,
+    prefix: String,
+}
+
+/// Uploads are unavailable without the `s3` feature.
+#[cfg(not(feature = "s3"))]
+pub enum Uploader {}
+
+impl Uploader {
+    #[cfg(feature = "s3")]
+    pub fn new(config: &UploadConfig) -> anyhow::Result<Self> {
+        let region = match &config.endpoint {
+            Some(endpoint) => s3::Region::Custom {
+                region: config.region.clone(),
+                endpoint: endpoint.clone(),
+            },
+            None => config.region.parse()?,
+        };
+        let credentials = s3::creds::Credentials::from_env()?;
+        let mut bucket = s3::Bucket::new(&config.bucket, region, credentials)?;
+        if config.path_style {
+            bucket = bucket.with_path_style();
+        }
+        Ok(Self {
+            bucket,
+            prefix: config.prefix.clone(),
+        })
+    }
+
+    #[cfg(not(feature = "s3"))]
+    pub fn new(_config: &UploadConfig) -> anyhow::Result<Self> {
+        anyhow::bail!("Uploading outputs requires sws-lua's `s3` feature")
+    }
+
+    #[cfg(feature = "s3")]
+    pub fn upload(&self, path: &Path) -> anyhow::Result<()> {
+        let key = object_key(&self.prefix, path);
+        let status = if fs_err::metadata(path)?.len() < MULTIPART_THRESHOLD {
+            self.bucket
+                .put_object(&key, &fs_err::read(path)?)?
+                .status_code()
+        } else {
+            let mut file = fs_err::File::open(path)?;
+            self.bucket.put_object_stream(&mut file, &key)?
+        };
+        anyhow::ensure!(
+            (200..300).contains(&status),
+            "Couldn't upload {} to {key}, got status {status}",
+            path.display()
+        );
+        log::info!("Uploaded {} to {key}", path.display());
+        Ok(())
+    }
+
+    #[cfg(not(feature = "s3"))]
+    pub fn upload(&self, _path: &Path) -> anyhow::Result<()> {
+        match *self {}
+    }
+}
@@ -1,6 +1,12 @@
-use std::io;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
+use sws_crawler::{serde_json, OnError};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CsvWriterConfig {
@@ -12,6 +18,18 @@ pub struct CsvWriterConfig {
     pub flexible: bool,
     #[serde(default = "default_csv_terminator")]
     pub terminator: CsvTerminator,
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+    #[serde(default = "default_csv_quote")]
+    pub quote: char,
+    #[serde(default, rename = "quoteStyle")]
+    pub quote_style: CsvQuoteStyle,
+    /// Written instead of empty fields
+    #[serde(default)]
+    pub null: Option<String>,
+    /// Whether new files start with a UTF-8 BOM, as expected by Excel
+    #[serde(default)]
+    pub bom: bool,
 }
 
 impl Default for CsvWriterConfig {
@@ -21,6 +39,11 @@ impl Default for CsvWriterConfig {
             escape: None,
             flexible: false,
             terminator: default_csv_terminator(),
+            columns: None,
+            quote: default_csv_quote(),
+            quote_style: CsvQuoteStyle::default(),
+            null: None,
+            bom: false,
         }
     }
 }
@@ -33,6 +56,10 @@ fn default_csv_terminator() -> CsvTerminator {
     CsvTerminator::Any('\n')
 }
 
+fn default_csv_quote() -> char {
+    '"'
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum CsvTerminator {
     CRLF,
@@ -48,12 +75,34 @@ impl From<CsvTerminator> for csv::Terminator {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum CsvQuoteStyle {
+    Always,
+    #[default]
+    Necessary,
+    NonNumeric,
+    Never,
+}
+
+impl From<CsvQuoteStyle> for csv::QuoteStyle {
+    fn from(source: CsvQuoteStyle) -> Self {
+        match source {
+            CsvQuoteStyle::Always => Self::Always,
+            CsvQuoteStyle::Necessary => Self::Necessary,
+            CsvQuoteStyle::NonNumeric => Self::NonNumeric,
+            CsvQuoteStyle::Never => Self::Never,
+        }
+    }
+}
+
 impl From<&CsvWriterConfig> for csv::WriterBuilder {
     fn from(c: &CsvWriterConfig) -> Self {
         let mut builder = csv::WriterBuilder::new();
         builder.delimiter(c.delimiter as u8);
         builder.terminator(c.terminator.into());
         builder.flexible(c.flexible);
+        builder.quote(c.quote as u8);
+        builder.quote_style(c.quote_style.into());
         if let Some(escape) = c.escape {
             builder.double_quote(false);
             builder.escape(escape as u8);
@@ -64,20 +113,375 @@ impl From<&CsvWriterConfig> for csv::WriterBuilder {
     }
 }
 
-pub enum CsvWriter {
+/// Config of the PostgreSQL table records are inserted into, instead of being written as
+/// CSV. Requires the `postgres` feature.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostgresWriterConfig {
+    pub url: String,
+    pub table: String,
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+    #[serde(default = "default_postgres_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_postgres_batch_size() -> usize {
+    1000
+}
+
+/// A record sent to the default output, or to a named one.
+#[derive(Debug)]
+pub struct OutputRecord {
+    pub output: Option<String>,
+    pub record: Record,
+}
+
+/// Receives the records of a scrap (along with their output) instead of them being
+/// written, for applications embedding the scraper.
+pub trait RecordSink: Send + Sync {
+    fn send(&self, record: OutputRecord) -> anyhow::Result<()>;
+}
+
+impl<F> RecordSink for F
+where
+    F: Fn(OutputRecord) -> anyhow::Result<()> + Send + Sync,
+{
+    fn send(&self, record: OutputRecord) -> anyhow::Result<()> {
+        self(record)
+    }
+}
+
+impl RecordSink for crossbeam_channel::Sender<OutputRecord> {
+    fn send(&self, record: OutputRecord) -> anyhow::Result<()> {
+        Ok(crossbeam_channel::Sender::send(self, record)?)
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedSink(pub Arc<dyn RecordSink>);
+
+impl fmt::Debug for SharedSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedSink").finish()
+    }
+}
+
+/// A record whose fields are either positional, or named after the output's columns.
+#[derive(Debug, Clone)]
+pub enum Record {
+    Positional(csv::StringRecord),
+    Named(Vec<(String, String)>),
+}
+
+impl Default for Record {
+    fn default() -> Self {
+        Self::Positional(csv::StringRecord::new())
+    }
+}
+
+/// The columns of an output, named records fields are written in their order. When they
+/// are not declared, they are the fields of the first named record.
+#[derive(Debug, Clone, Default)]
+pub struct Columns(Option<Vec<String>>);
+
+impl Columns {
+    pub fn new(names: Option<Vec<String>>) -> Self {
+        Self(names)
+    }
+
+    pub fn names(&self) -> Option<&[String]> {
+        self.0.as_deref()
+    }
+
+    pub fn resolve(&mut self, record: Record) -> anyhow::Result<csv::StringRecord> {
+        let fields = match record {
+            Record::Positional(record) => return Ok(record),
+            Record::Named(fields) => fields,
+        };
+        let names = self
+            .0
+            .get_or_insert_with(|| fields.iter().map(|(name, _)| name.clone()).collect());
+        let mut values = vec![""; names.len()];
+        for (name, value) in &fields {
+            let i = names
+                .iter()
+                .position(|column| column == name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown column {name}"))?;
+            values[i] = value;
+        }
+        Ok(csv::StringRecord::from(values))
+    }
+}
+
+/// The type of a named records field, its values are coerced to their canonical form.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Int,
+    Float,
+    Bool,
+    /// A `%Y-%m-%d` date
+    Date,
+    /// A RFC 3339 date and time
+    Datetime,
+}
+
+impl FieldType {
+    fn coerce(self, value: &str) -> Option<String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Some(String::new());
+        }
+        match self {
+            Self::String => Some(value.to_string()),
+            Self::Int => value.parse::<i64>().ok().map(|n| n.to_string()),
+            Self::Float => value
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(|n| n.to_string()),
+            Self::Bool => match value.to_lowercase().as_str() {
+                "true" | "yes" | "1" => Some("true".into()),
+                "false" | "no" | "0" => Some("false".into()),
+                _ => None,
+            },
+            Self::Date => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|d| d.format("%Y-%m-%d").to_string()),
+            Self::Datetime => chrono::DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|dt| dt.to_rfc3339()),
+        }
+    }
+}
+
+/// The declared types of named records fields, the fields it doesn't declare (and the
+/// positional records) are left as is.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub fields: HashMap<String, FieldType>,
+    /// Whether invalid records fail the scrap, or are logged and skipped
+    pub on_invalid: OnError,
+}
+
+impl Schema {
+    /// Validates the record, and coerces its fields to their declared type.
+    pub fn coerce(&self, record: Record) -> anyhow::Result<Record> {
+        let fields = match record {
+            Record::Positional(_) => return Ok(record),
+            Record::Named(fields) => fields,
+        };
+        fields
+            .into_iter()
+            .map(|(name, value)| match self.fields.get(&name) {
+                Some(ty) => match ty.coerce(&value) {
+                    Some(value) => Ok((name, value)),
+                    None => Err(anyhow::anyhow!(
+                        "Invalid field {name}, `{value}` isn't a {}",
+                        format!("{ty:?}").to_lowercase()
+                    )),
+                },
+                None => Ok((name, value)),
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Record::Named)
+    }
+}
+
+/// A named output, either its path or a table with its path and columns.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum NamedOutput {
+    Path(PathBuf),
+    Config {
+        path: PathBuf,
+        #[serde(default)]
+        columns: Option<Vec<String>>,
+    },
+}
+
+impl NamedOutput {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Path(path) | Self::Config { path, .. } => path,
+        }
+    }
+
+    pub fn columns(&self) -> Option<Vec<String>> {
+        match self {
+            Self::Path(_) => None,
+            Self::Config { columns, .. } => columns.clone(),
+        }
+    }
+}
+
+/// Config of the output files records are partitioned into, given as a path template
+/// such as `out/{1}/{%Y-%m-%d}.csv` where `{N}` is the value of the record's field `N`
+/// (starting from 1) and `{%...}` is the current date with the given `strftime` format.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PartitionConfig {
+    pub path: String,
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum RecordWriter {
+    Csv(CsvWriter),
+    Jsonl(JsonlWriter),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetWriter),
+    Partitioned(PartitionedWriter),
+    Rotating(RotatingWriter),
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresWriter),
+}
+
+impl RecordWriter {
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Csv(wtr) => Ok(wtr.flush()?),
+            Self::Jsonl(wtr) => Ok(wtr.flush()?),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(wtr) => wtr.flush(),
+            Self::Partitioned(wtr) => wtr.flush(),
+            Self::Rotating(wtr) => wtr.flush(),
+            #[cfg(feature = "postgres")]
+            Self::Postgres(wtr) => wtr.flush(),
+        }
+    }
+
+    /// Writes a record, `names` being the columns of the output when they are known.
+    pub fn write_record(
+        &mut self,
+        record: csv::StringRecord,
+        names: Option<&[String]>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Csv(wtr) => Ok(wtr.write_record(&record)?),
+            Self::Jsonl(wtr) => wtr.write_record(&record, names),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(wtr) => wtr.write_record(record, names),
+            Self::Partitioned(wtr) => wtr.write_record(record),
+            Self::Rotating(wtr) => wtr.write_record(record),
+            #[cfg(feature = "postgres")]
+            Self::Postgres(wtr) => wtr.write_record(record),
+        }
+    }
+
+    /// The files being written, excluding the single file of a `Csv` writer.
+    pub fn files(&self) -> Vec<PathBuf> {
+        match self {
+            Self::Partitioned(wtr) => wtr.files.iter().cloned().collect(),
+            Self::Rotating(wtr) => wtr.wtr.iter().map(|_| wtr.chunk_path()).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The files completely written since the last call.
+    pub fn take_finished(&mut self) -> Vec<PathBuf> {
+        match self {
+            Self::Rotating(wtr) => std::mem::take(&mut wtr.finished),
+            _ => vec![],
+        }
+    }
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+pub struct CsvWriter {
+    wtr: CsvOutput,
+    null: Option<String>,
+}
+
+enum CsvOutput {
     File(csv::Writer<fs_err::File>),
     Stdout(csv::Writer<io::Stdout>),
+    Compressed(csv::Writer<Box<dyn io::Write + Send>>),
 }
 
 impl CsvWriter {
+    /// Opens the CSV file at `path` (stdout if `None`), compressed with the given
+    /// `compression` or the one matching the file extension.
+    pub fn open(
+        path: Option<&Path>,
+        csv_config: &CsvWriterConfig,
+        file_mode: FileMode,
+        compression: Option<Compression>,
+    ) -> io::Result<Self> {
+        let builder = csv::WriterBuilder::from(csv_config);
+        let compression = compression.or_else(|| path.and_then(Compression::from_extension));
+        // The BOM is only written at the start of the file, not when appending to it
+        let bom = |file: &fs_err::File| -> io::Result<bool> {
+            Ok(csv_config.bom && file.metadata()?.len() == 0)
+        };
+        let wtr = match (path, compression) {
+            (Some(path), None) => {
+                let opts: fs_err::OpenOptions = file_mode.into();
+                let mut file = opts.open(path)?;
+                if bom(&file)? {
+                    file.write_all(UTF8_BOM)?;
+                }
+                CsvOutput::File(builder.from_writer(file))
+            }
+            (None, None) => {
+                let mut stdout = io::stdout();
+                if csv_config.bom {
+                    stdout.write_all(UTF8_BOM)?;
+                }
+                CsvOutput::Stdout(builder.from_writer(stdout))
+            }
+            (Some(path), Some(compression)) => {
+                let opts: fs_err::OpenOptions = file_mode.into();
+                let file = opts.open(path)?;
+                let bom = bom(&file)?;
+                let mut encoder = compression.encoder(file)?;
+                if bom {
+                    encoder.write_all(UTF8_BOM)?;
+                }
+                CsvOutput::Compressed(builder.from_writer(encoder))
+            }
+            (None, Some(compression)) => {
+                let mut encoder = compression.encoder(io::stdout())?;
+                if csv_config.bom {
+                    encoder.write_all(UTF8_BOM)?;
+                }
+                CsvOutput::Compressed(builder.from_writer(encoder))
+            }
+        };
+        Ok(Self {
+            wtr,
+            null: csv_config.null.clone(),
+        })
+    }
+
     pub fn flush(&mut self) -> io::Result<()> {
-        match self {
-            Self::File(wtr) => wtr.flush(),
-            Self::Stdout(wtr) => wtr.flush(),
+        match &mut self.wtr {
+            CsvOutput::File(wtr) => wtr.flush(),
+            CsvOutput::Stdout(wtr) => wtr.flush(),
+            CsvOutput::Compressed(wtr) => wtr.flush(),
         }
     }
 
     pub fn write_record<I, T>(&mut self, record: I) -> csv::Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let Some(null) = &self.null else {
+            return self.wtr.write_record(record);
+        };
+        let fields = record.into_iter().collect::<Vec<_>>();
+        self.wtr
+            .write_record(fields.iter().map(|field| match field.as_ref() {
+                b"" => null.as_bytes(),
+                field => field,
+            }))
+    }
+}
+
+impl CsvOutput {
+    fn write_record<I, T>(&mut self, record: I) -> csv::Result<()>
     where
         I: IntoIterator<Item = T>,
         T: AsRef<[u8]>,
@@ -85,23 +489,447 @@ impl CsvWriter {
         match self {
             Self::File(wtr) => wtr.write_record(record),
             Self::Stdout(wtr) => wtr.write_record(record),
+            Self::Compressed(wtr) => wtr.write_record(record),
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// The format of the main output, overriding the writer configured by the script.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum Format {
+    Csv,
+    /// One JSON object per line, or one array for positional records
+    Jsonl,
+    /// String columns, named after the output's columns or numbered from 1
+    Parquet,
+}
+
+/// Writes records as JSON lines, objects keyed by the output's columns when they are known.
+pub struct JsonlWriter {
+    wtr: io::BufWriter<Box<dyn io::Write + Send>>,
+}
+
+impl JsonlWriter {
+    /// Opens the file at `path` (stdout if `None`), compressed with the given
+    /// `compression` or the one matching the file extension.
+    pub fn open(
+        path: Option<&Path>,
+        file_mode: FileMode,
+        compression: Option<Compression>,
+    ) -> io::Result<Self> {
+        let compression = compression.or_else(|| path.and_then(Compression::from_extension));
+        let wtr = output(path, file_mode)?;
+        let wtr = match compression {
+            Some(compression) => compression.encoder(wtr)?,
+            None => wtr,
+        };
+        Ok(Self {
+            wtr: io::BufWriter::new(wtr),
+        })
+    }
+
+    pub fn write_record(
+        &mut self,
+        record: &csv::StringRecord,
+        names: Option<&[String]>,
+    ) -> anyhow::Result<()> {
+        let value = match names {
+            Some(names) => serde_json::Value::Object(
+                names
+                    .iter()
+                    .cloned()
+                    .zip(record.iter().map(serde_json::Value::from))
+                    .collect(),
+            ),
+            None => serde_json::Value::from(record.iter().collect::<Vec<_>>()),
+        };
+        serde_json::to_writer(&mut self.wtr, &value)?;
+        self.wtr.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+}
+
+/// The file at `path` opened with `file_mode`, or stdout if `None`.
+fn output(path: Option<&Path>, file_mode: FileMode) -> io::Result<Box<dyn io::Write + Send>> {
+    Ok(match path {
+        Some(path) => {
+            let opts: fs_err::OpenOptions = file_mode.into();
+            Box::new(opts.open(path)?)
+        }
+        None => Box::new(io::stdout()),
+    })
+}
+
+#[cfg(feature = "parquet")]
+const PARQUET_BATCH_SIZE: usize = 10_000;
+
+/// Batches records into row groups of string columns, the schema being set by the first
+/// record. The file is only valid once the writer is dropped.
+#[cfg(feature = "parquet")]
+pub struct ParquetWriter {
+    output: Option<Box<dyn io::Write + Send>>,
+    wtr: Option<parquet::arrow::ArrowWriter<Box<dyn io::Write + Send>>>,
+    names: Option<Vec<String>>,
+    batch: Vec<csv::StringRecord>,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetWriter {
+    pub fn open(path: Option<&Path>, file_mode: FileMode) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !matches!(file_mode, FileMode::Append),
+            "Parquet files cannot be appended to"
+        );
+        Ok(Self {
+            output: Some(output(path, file_mode)?),
+            wtr: None,
+            names: None,
+            batch: Vec::with_capacity(PARQUET_BATCH_SIZE),
+        })
+    }
+
+    pub fn write_record(
+        &mut self,
+        record: csv::StringRecord,
+        names: Option<&[String]>,
+    ) -> anyhow::Result<()> {
+        self.names.get_or_insert_with(|| match names {
+            Some(names) => names.to_vec(),
+            None => (1..=record.len()).map(|i| i.to_string()).collect(),
+        });
+        self.batch.push(record);
+        if self.batch.len() >= PARQUET_BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        use arrow_array::{ArrayRef, RecordBatch, StringArray};
+        use arrow_schema::{DataType, Field, Schema};
+
+        let Some(names) = &self.names else {
+            return Ok(());
+        };
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let schema = Arc::new(Schema::new(
+            names
+                .iter()
+                .map(|name| Field::new(name, DataType::Utf8, true))
+                .collect::<Vec<_>>(),
+        ));
+        if self.wtr.is_none() {
+            let output = self.output.take().expect("parquet output not taken");
+            let wtr = parquet::arrow::ArrowWriter::try_new(output, schema.clone(), None)?;
+            self.wtr = Some(wtr);
+        }
+        let wtr = self.wtr.as_mut().expect("parquet writer created");
+        // Missing fields are null, extra ones are dropped
+        let columns = (0..names.len())
+            .map(|i| {
+                let column = self.batch.iter().map(|record| record.get(i));
+                Arc::new(column.collect::<StringArray>()) as ArrayRef
+            })
+            .collect::<Vec<_>>();
+        wtr.write(&RecordBatch::try_new(schema, columns)?)?;
+        wtr.flush()?;
+        self.batch.clear();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl Drop for ParquetWriter {
+    fn drop(&mut self) {
+        let res = self.flush().and_then(|()| match self.wtr.take() {
+            Some(wtr) => Ok(wtr.close().map(|_| ())?),
+            None => Ok(()),
+        });
+        if let Err(e) = res {
+            log::error!("Couldn't finish parquet file: {e}");
+        }
+    }
+}
+
+/// The maximum number of partition files kept open at once.
+const MAX_OPEN_PARTITIONS: usize = 64;
+
+/// Writes records to the CSV file their partition path resolves to, files are created
+/// (along with their parent directories) on their first record.
+///
+/// Only the most recently written files are kept open, the others are closed and then
+/// reopened in append mode on their next record.
+pub struct PartitionedWriter {
+    template: Vec<PathPart>,
+    csv_config: CsvWriterConfig,
+    file_mode: FileMode,
+    compression: Option<Compression>,
+    /// The open files, along with the number of the last record written to them
+    writers: HashMap<PathBuf, (CsvWriter, u64)>,
+    /// All the files written, whether they are open or not
+    files: HashSet<PathBuf>,
+    max_open: usize,
+    records: u64,
+}
+
+enum PathPart {
+    Literal(String),
+    Field(usize),
+    Date(String),
+}
+
+impl PartitionedWriter {
+    pub fn new(
+        config: &PartitionConfig,
+        csv_config: &CsvWriterConfig,
+        file_mode: FileMode,
+        compression: Option<Compression>,
+    ) -> anyhow::Result<Self> {
+        let mut template = vec![];
+        let mut rest = config.path.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| anyhow::anyhow!("Unclosed {{ in partition path {}", config.path))?;
+            if start > 0 {
+                template.push(PathPart::Literal(rest[..start].to_string()));
+            }
+            let placeholder = &rest[start + 1..end];
+            let part = if placeholder.starts_with('%') {
+                PathPart::Date(placeholder.to_string())
+            } else {
+                match placeholder.parse::<usize>() {
+                    Ok(field) if field > 0 => PathPart::Field(field - 1),
+                    _ => anyhow::bail!(
+                        "Invalid placeholder {{{placeholder}}} in partition path {}",
+                        config.path
+                    ),
+                }
+            };
+            template.push(part);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            template.push(PathPart::Literal(rest.to_string()));
+        }
+
+        Ok(Self {
+            template,
+            csv_config: csv_config.clone(),
+            file_mode,
+            compression,
+            writers: HashMap::new(),
+            files: HashSet::new(),
+            max_open: MAX_OPEN_PARTITIONS,
+            records: 0,
+        })
+    }
+
+    fn path(&self, record: &csv::StringRecord) -> anyhow::Result<PathBuf> {
+        let now = chrono::Local::now();
+        let mut path = String::new();
+        for part in &self.template {
+            match part {
+                PathPart::Literal(s) => path.push_str(s),
+                PathPart::Field(i) => {
+                    let field = record
+                        .get(*i)
+                        .ok_or_else(|| anyhow::anyhow!("Missing field {} to partition", i + 1))?;
+                    // Field values must not escape the partition directory
+                    let field = match field.replace(['/', '\\'], "_") {
+                        f if f.is_empty() || f == "." || f == ".." => "_".to_string(),
+                        f => f,
+                    };
+                    path.push_str(&field);
+                }
+                PathPart::Date(fmt) => path.push_str(&now.format(fmt).to_string()),
+            }
+        }
+        Ok(PathBuf::from(path))
+    }
+
+    pub fn write_record(&mut self, record: csv::StringRecord) -> anyhow::Result<()> {
+        let path = self.path(&record)?;
+        if !self.writers.contains_key(&path) && self.writers.len() >= self.max_open {
+            self.close_least_recent()?;
+        }
+        self.records += 1;
+        let (wtr, last_record) = match self.writers.entry(path) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = entry.key();
+                // A file closed earlier is appended to, whatever the file mode
+                let file_mode = if self.files.contains(path) {
+                    FileMode::Append
+                } else {
+                    if let Some(dir) = path.parent() {
+                        fs_err::create_dir_all(dir)?;
+                    }
+                    self.file_mode.clone()
+                };
+                let wtr =
+                    CsvWriter::open(Some(path), &self.csv_config, file_mode, self.compression)?;
+                self.files.insert(path.clone());
+                entry.insert((wtr, self.records))
+            }
+        };
+        *last_record = self.records;
+        wtr.write_record(&record)?;
+        Ok(())
+    }
+
+    /// Closes the file whose last record is the oldest, finishing its compression if any.
+    fn close_least_recent(&mut self) -> anyhow::Result<()> {
+        let path = self
+            .writers
+            .iter()
+            .min_by_key(|(_, (_, last_record))| *last_record)
+            .map(|(path, _)| path.clone());
+        if let Some((mut wtr, _)) = path.and_then(|path| self.writers.remove(&path)) {
+            wtr.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        for (wtr, _) in self.writers.values_mut() {
+            wtr.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// The compression of output files, whose stream is finished when the writer is dropped.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Self::Gzip),
+            "zst" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    fn encoder<W>(self, wtr: W) -> io::Result<Box<dyn io::Write + Send>>
+    where
+        W: io::Write + Send + 'static,
+    {
+        let encoder: Box<dyn io::Write + Send> = match self {
+            Self::Gzip => Box::new(flate2::write::GzEncoder::new(
+                wtr,
+                flate2::Compression::default(),
+            )),
+            Self::Zstd => Box::new(zstd::Encoder::new(wtr, 0)?.auto_finish()),
+        };
+        Ok(encoder)
+    }
+}
+
+/// Writes records to successive chunk files of `max_records` records, named after `path`
+/// with their index (such as `out-00001.csv`).
+pub struct RotatingWriter {
+    path: PathBuf,
+    csv_config: CsvWriterConfig,
+    file_mode: FileMode,
+    compression: Option<Compression>,
+    max_records: usize,
+    records: usize,
+    chunk: usize,
+    wtr: Option<CsvWriter>,
+    finished: Vec<PathBuf>,
+}
+
+impl RotatingWriter {
+    pub fn new(
+        path: PathBuf,
+        csv_config: &CsvWriterConfig,
+        file_mode: FileMode,
+        compression: Option<Compression>,
+        max_records: usize,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(max_records > 0, "Rotated chunks must have > 0 records");
+        Ok(Self {
+            path,
+            csv_config: csv_config.clone(),
+            file_mode,
+            compression,
+            max_records,
+            records: 0,
+            chunk: 1,
+            wtr: None,
+            finished: vec![],
+        })
+    }
+
+    fn chunk_path(&self) -> PathBuf {
+        let file_name = self
+            .path
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (stem, ext) = match file_name.split_once('.') {
+            Some((stem, ext)) => (stem, format!(".{ext}")),
+            None => (file_name.as_str(), String::new()),
+        };
+        self.path
+            .with_file_name(format!("{stem}-{:05}{ext}", self.chunk))
+    }
+
+    pub fn write_record(&mut self, record: csv::StringRecord) -> anyhow::Result<()> {
+        let wtr = match &mut self.wtr {
+            Some(wtr) => wtr,
+            None => {
+                let wtr = CsvWriter::open(
+                    Some(&self.chunk_path()),
+                    &self.csv_config,
+                    self.file_mode.clone(),
+                    self.compression,
+                )?;
+                self.wtr.insert(wtr)
+            }
+        };
+        wtr.write_record(&record)?;
+
+        self.records += 1;
+        if self.records >= self.max_records {
+            if let Some(mut wtr) = self.wtr.take() {
+                wtr.flush()?;
+            }
+            self.finished.push(self.chunk_path());
+            self.records = 0;
+            self.chunk += 1;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some(wtr) = &mut self.wtr {
+            wtr.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub enum FileMode {
+    #[default]
     Create,
     Append,
     Truncate,
 }
 
-impl Default for FileMode {
-    fn default() -> Self {
-        Self::Create
-    }
-}
-
 impl From<FileMode> for fs_err::OpenOptions {
     fn from(mode: FileMode) -> Self {
         let mut opts = fs_err::OpenOptions::new();
@@ -114,3 +942,113 @@ impl From<FileMode> for fs_err::OpenOptions {
         opts
     }
 }
+
+/// Batches records and inserts them with a `COPY` statement once `batch_size` is reached.
+#[cfg(feature = "postgres")]
+pub struct PostgresWriter {
+    client: postgres::Client,
+    copy_query: String,
+    batch: Vec<csv::StringRecord>,
+    batch_size: usize,
+}
+
+#[cfg(feature = "postgres")]
+impl PostgresWriter {
+    pub fn connect(config: &PostgresWriterConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(config.batch_size > 0, "Postgres batch_size must be > 0");
+        let tls = postgres_native_tls::MakeTlsConnector::new(native_tls::TlsConnector::new()?);
+        let client = postgres::Client::connect(&config.url, tls)?;
+        let columns = match &config.columns {
+            Some(columns) => {
+                let columns = columns.iter().map(String::as_str).map(quote_identifier);
+                format!(" ({})", columns.collect::<Vec<_>>().join(", "))
+            }
+            None => String::new(),
+        };
+        // The table may be qualified by its schema
+        let table = config.table.split('.').map(quote_identifier);
+        Ok(Self {
+            client,
+            copy_query: format!(
+                "COPY {}{columns} FROM STDIN WITH (FORMAT csv)",
+                table.collect::<Vec<_>>().join(".")
+            ),
+            batch: Vec::with_capacity(config.batch_size),
+            batch_size: config.batch_size,
+        })
+    }
+
+    pub fn write_record(&mut self, record: csv::StringRecord) -> anyhow::Result<()> {
+        self.batch.push(record);
+        if self.batch.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let mut copy = self.client.copy_in(&self.copy_query)?;
+        let mut wtr = csv::Writer::from_writer(&mut copy);
+        for record in self.batch.drain(..) {
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        drop(wtr);
+        copy.finish()?;
+        Ok(())
+    }
+}
+
+/// Quotes a PostgreSQL identifier, so that it is used verbatim (without being folded to
+/// lowercase) and can't be mistaken for SQL.
+#[cfg(feature = "postgres")]
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn quoted_identifiers() {
+        assert_eq!(quote_identifier("products"), r#""products""#);
+        assert_eq!(quote_identifier("Products"), r#""Products""#);
+        assert_eq!(
+            quote_identifier(r#"x" (a) FROM STDIN; --"#),
+            r#""x"" (a) FROM STDIN; --""#
+        );
+    }
+
+    #[test]
+    fn partitions_reopened_in_append_mode() {
+        let dir = std::env::temp_dir().join(format!("sws-partitions-{}", std::process::id()));
+        let config = PartitionConfig {
+            path: format!("{}/{{1}}.csv", dir.display()),
+        };
+        let mut wtr =
+            PartitionedWriter::new(&config, &CsvWriterConfig::default(), FileMode::Create, None)
+                .unwrap();
+        wtr.max_open = 2;
+
+        for (partition, value) in [("a", "1"), ("b", "2"), ("c", "3"), ("a", "4"), ("c", "5")] {
+            wtr.write_record(csv::StringRecord::from(vec![partition, value]))
+                .unwrap();
+            assert!(wtr.writers.len() <= 2);
+        }
+        wtr.flush().unwrap();
+
+        let mut files = RecordWriter::Partitioned(wtr).files();
+        files.sort();
+        assert_eq!(files, ["a", "b", "c"].map(|p| dir.join(format!("{p}.csv"))));
+        let read = |p: &str| fs_err::read_to_string(dir.join(format!("{p}.csv"))).unwrap();
+        assert_eq!(read("a"), "a,1\na,4\n");
+        assert_eq!(read("b"), "b,2\n");
+        assert_eq!(read("c"), "c,3\nc,5\n");
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -139,9 +139,8 @@ impl Element for ElementRef {
     }
 
     fn is_root(&self) -> bool {
-        self.parent().map_or(false, |parent| {
-            parent.map_value(|v| v.is_document()).unwrap_or(false)
-        })
+        self.parent()
+            .is_some_and(|parent| parent.map_value(|v| v.is_document()).unwrap_or(false))
     }
 
     fn apply_selector_flags(&self, _flags: matching::ElementSelectorFlags) {}
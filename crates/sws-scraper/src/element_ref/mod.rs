@@ -1,15 +1,17 @@
 //! Element references.
 
 mod element;
+mod pretty;
 mod serializable;
 
 use std::iter::FusedIterator;
 use std::ops::Deref;
 
-use html5ever::serialize::{serialize, SerializeOpts, TraversalScope};
+use html5ever::serialize::{serialize, Serialize, SerializeOpts, TraversalScope};
 use sws_tree::iter::{Edge, Traverse};
 use sws_tree::NodeRef;
 
+use self::pretty::PrettySerializer;
 use crate::node::{Element, Node};
 use crate::selector::Selector;
 
@@ -55,12 +57,24 @@ impl ElementRef {
         }
     }
 
-    fn serialize(&self, traversal_scope: TraversalScope) -> String {
-        let opts = SerializeOpts {
+    /// Returns the closest inclusive ancestor element matching a selector.
+    pub fn closest(&self, selector: &Selector) -> Option<Self> {
+        std::iter::once(self.node.clone())
+            .chain(self.ancestors())
+            .filter_map(ElementRef::wrap)
+            .find(|element| selector.matches(element))
+    }
+
+    fn serialize_opts(traversal_scope: TraversalScope) -> SerializeOpts {
+        SerializeOpts {
             scripting_enabled: false, // It's not clear what this does.
             traversal_scope,
             create_missing_parent: false,
-        };
+        }
+    }
+
+    fn serialize(&self, traversal_scope: TraversalScope) -> String {
+        let opts = Self::serialize_opts(traversal_scope);
         let mut buf = Vec::new();
         serialize(&mut buf, self, opts).unwrap();
         String::from_utf8(buf).unwrap()
@@ -76,6 +90,16 @@ impl ElementRef {
         self.serialize(TraversalScope::ChildrenOnly(None))
     }
 
+    /// Returns the HTML of this element with one node per line, indented by nesting
+    /// level. Text nodes are trimmed and blank ones are dropped.
+    pub fn pretty_html(&self) -> String {
+        let opts = Self::serialize_opts(TraversalScope::IncludeNode);
+        let mut buf = Vec::new();
+        let mut serializer = PrettySerializer::new(&mut buf, opts);
+        Serialize::serialize(self, &mut serializer, TraversalScope::IncludeNode).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
     /// Returns an iterator over descendent text nodes.
     pub fn text(&self) -> Text {
         Text {
@@ -97,6 +121,20 @@ impl ElementRef {
         }
         all_text
     }
+
+    /// Returns the content of the child text nodes concatenated, without the text of
+    /// descendent elements.
+    pub fn own_text(&self) -> String {
+        let mut own_text = String::new();
+        for child in self.children() {
+            child.map_value(|v| {
+                if let Node::Text(ref text) = v {
+                    own_text.push_str(text);
+                }
+            });
+        }
+        own_text
+    }
 }
 
 impl Deref for ElementRef {
@@ -191,4 +229,61 @@ mod tests {
         let element2 = element1.select(sel2).next().unwrap();
         assert_eq!(element2.inner_html(), "3");
     }
+
+    #[test]
+    fn test_closest() {
+        let html = r#"
+            <table>
+                <tr class="row"><td><b>1</b></td></tr>
+            </table>
+        "#;
+        let fragment = Html::parse_fragment(html);
+        let b = fragment
+            .select(Selector::parse("b").unwrap())
+            .next()
+            .unwrap();
+
+        let row = b.closest(&Selector::parse("tr.row").unwrap()).unwrap();
+        assert_eq!(row.map_value(|el| el.name().to_string()).unwrap(), "tr");
+        let itself = b.closest(&Selector::parse("b").unwrap()).unwrap();
+        assert_eq!(itself, b);
+        assert!(b.closest(&Selector::parse("div").unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_own_text() {
+        let fragment = Html::parse_fragment("<p><b>Price:</b> 12 <i>EUR</i>.</p>");
+        let p = fragment
+            .select(Selector::parse("p").unwrap())
+            .next()
+            .unwrap();
+        assert_eq!(p.own_text(), " 12 .");
+        assert_eq!(p.inner_text(), "Price: 12 EUR.");
+        assert_eq!(p.text().collect::<Vec<_>>(), ["Price:", " 12 ", "EUR", "."]);
+    }
+
+    #[test]
+    fn test_pretty_html() {
+        let fragment = Html::parse_fragment(
+            "<ul class=\"list\">\n  <li>A &amp; B</li><li><br><i>C</i></li>\n</ul>",
+        );
+        let ul = fragment
+            .select(Selector::parse("ul").unwrap())
+            .next()
+            .unwrap();
+        let expected = [
+            r#"<ul class="list">"#,
+            "  <li>",
+            "    A &amp; B",
+            "  </li>",
+            "  <li>",
+            "    <br>",
+            "    <i>",
+            "      C",
+            "    </i>",
+            "  </li>",
+            "</ul>",
+        ];
+        assert_eq!(ul.pretty_html(), expected.join("\n"));
+    }
 }
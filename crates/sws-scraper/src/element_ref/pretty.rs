@@ -0,0 +1,97 @@
+use std::io::{self, Write};
+
+use html5ever::serialize::{AttrRef, HtmlSerializer, SerializeOpts, Serializer};
+use html5ever::QualName;
+
+/// Serializer putting each node on its own line, indented by its nesting level.
+pub(crate) struct PrettySerializer<Wr: Write> {
+    inner: HtmlSerializer<Wr>,
+    depth: usize,
+    started: bool,
+}
+
+impl<Wr: Write> PrettySerializer<Wr> {
+    pub(crate) fn new(writer: Wr, opts: SerializeOpts) -> Self {
+        Self {
+            inner: HtmlSerializer::new(writer, opts),
+            depth: 0,
+            started: false,
+        }
+    }
+
+    fn new_line(&mut self) -> io::Result<()> {
+        if self.started {
+            writeln!(self.inner.writer)?;
+        }
+        self.started = true;
+        write!(self.inner.writer, "{:1$}", "", 2 * self.depth)
+    }
+}
+
+/// Whether the element has no end tag.
+fn is_void(name: &QualName) -> bool {
+    name.ns == ns!(html)
+        && matches!(
+            &*name.local,
+            "area"
+                | "base"
+                | "br"
+                | "col"
+                | "embed"
+                | "hr"
+                | "img"
+                | "input"
+                | "link"
+                | "meta"
+                | "param"
+                | "source"
+                | "track"
+                | "wbr"
+        )
+}
+
+impl<Wr: Write> Serializer for PrettySerializer<Wr> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.new_line()?;
+        if !is_void(&name) {
+            self.depth += 1;
+        }
+        self.inner.start_elem(name, attrs)
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        if !is_void(&name) {
+            self.depth -= 1;
+            self.new_line()?;
+        }
+        self.inner.end_elem(name)
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        // Blank text nodes are only layout between elements
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.new_line()?;
+        self.inner.write_text(text)
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.new_line()?;
+        self.inner.write_comment(text)
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.new_line()?;
+        self.inner.write_doctype(name)
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.new_line()?;
+        self.inner.write_processing_instruction(target, data)
+    }
+}
@@ -41,7 +41,7 @@ impl TreeSink for Html {
     // What is the name of this element?
     //
     // Should never be called on a non-element node; feel free to panic!.
-    fn elem_name(&self, target: &Self::Handle) -> ExpandedName {
+    fn elem_name(&self, target: &Self::Handle) -> ExpandedName<'_> {
         self.tree
             .get(*target)
             .unwrap()
@@ -108,7 +108,7 @@ impl TreeSink for Html {
             NodeOrText::AppendText(text) => {
                 let can_concat = parent
                     .last_child()
-                    .map_or(false, |n| n.map_value(|v| v.is_text()).unwrap_or(false));
+                    .is_some_and(|n| n.map_value(|v| v.is_text()).unwrap_or(false));
 
                 if can_concat {
                     let last_child = parent.last_child().unwrap();
@@ -150,7 +150,7 @@ impl TreeSink for Html {
                 NodeOrText::AppendText(text) => {
                     let can_concat = sibling
                         .prev_sibling()
-                        .map_or(false, |n| n.map_value(|v| v.is_text()).unwrap_or(false));
+                        .is_some_and(|n| n.map_value(|v| v.is_text()).unwrap_or(false));
 
                     if can_concat {
                         let prev_sibling = sibling.prev_sibling().unwrap();
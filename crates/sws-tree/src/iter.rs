@@ -51,7 +51,7 @@ impl<T> Tree<T> {
         Rc::try_unwrap(self).map(|tree| tree.into_iter())
     }
 
-    pub fn nodes(&self) -> Nodes<T> {
+    pub fn nodes(&self) -> Nodes<'_, T> {
         Nodes {
             r: self.sm.borrow(),
         }
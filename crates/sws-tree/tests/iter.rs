@@ -176,8 +176,8 @@ fn iter_traverse() {
         .root()
         .traverse()
         .filter_map(|edge| match edge {
-            Edge::Open(nref) => nref.map_value(|&c| c).map(|c| Value::Open(c)),
-            Edge::Close(nref) => nref.map_value(|&c| c).map(|c| Value::Close(c)),
+            Edge::Open(nref) => nref.map_value(|&c| c).map(Value::Open),
+            Edge::Close(nref) => nref.map_value(|&c| c).map(Value::Close),
         })
         .collect::<Vec<_>>();
 
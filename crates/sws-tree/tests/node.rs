@@ -52,15 +52,15 @@ fn node_last_child() {
 #[test]
 fn node_has_siblings() {
     let tree = tree!('a' => { 'b', 'c' });
-    assert_eq!(false, tree.root().has_siblings());
-    assert_eq!(true, tree.root().first_child().unwrap().has_siblings());
+    assert!(!tree.root().has_siblings());
+    assert!(tree.root().first_child().unwrap().has_siblings());
 }
 
 #[test]
 fn node_has_children() {
     let tree = tree!('a' => { 'b', 'c' });
-    assert_eq!(true, tree.root().has_children());
-    assert_eq!(false, tree.root().first_child().unwrap().has_children());
+    assert!(tree.root().has_children());
+    assert!(!tree.root().first_child().unwrap().has_children());
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////
@@ -298,7 +298,7 @@ fn reparent_from_id_append() {
     let g = b.last_child().unwrap();
     let f = g.prev_sibling().unwrap();
 
-    assert_eq!(false, e.has_children());
+    assert!(!e.has_children());
     assert_eq!('f', f.map_value(|&c| c).unwrap());
     assert_eq!('g', g.map_value(|&c| c).unwrap());
     assert_eq!(Some(&f), d.next_sibling().as_ref());